@@ -3,14 +3,15 @@
 //! This command helps identify test interactions by bisecting the test suite
 //! to find which tests affect the outcome of a target test.
 
-use crate::commands::utils::open_repository;
+use crate::commands::utils::open_repository_with;
 use crate::commands::Command;
 use crate::error::Result;
-use crate::repository::TestId;
+use crate::repository::{RepositoryFactory, TestId};
 use crate::testcommand::TestCommand;
 use crate::ui::UI;
 use std::path::Path;
 use std::process::{Command as ProcessCommand, Stdio};
+use std::sync::Arc;
 
 /// Command to analyze test isolation issues using bisection.
 ///
@@ -19,6 +20,7 @@ use std::process::{Command as ProcessCommand, Stdio};
 /// pass when run in isolation.
 pub struct AnalyzeIsolationCommand {
     base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
     target_test: String,
 }
 
@@ -31,6 +33,7 @@ impl AnalyzeIsolationCommand {
     pub fn new(base_path: Option<String>, target_test: String) -> Self {
         AnalyzeIsolationCommand {
             base_path,
+            repository_factory: None,
             target_test,
         }
     }
@@ -134,17 +137,31 @@ impl AnalyzeIsolationCommand {
 
         Ok(final_set)
     }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
 }
 
 impl Command for AnalyzeIsolationCommand {
     fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
-        let base = Path::new(self.base_path.as_deref().unwrap_or("."));
-
         // Open repository (to verify it exists)
-        let _repo = open_repository(self.base_path.as_deref())?;
+        let _repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
 
-        // Load test command
-        let test_cmd = TestCommand::from_directory(base)?;
+        // Load test command, and run tests from the root it was found in
+        let test_cmd =
+            TestCommand::from_directory(Path::new(self.base_path.as_deref().unwrap_or(".")))?;
+        let base = test_cmd.base_dir();
 
         ui.output(&format!(
             "Analyzing test isolation for: {}",
@@ -0,0 +1,160 @@
+//! Name test runs
+
+use crate::commands::utils::open_repository_with;
+use crate::commands::Command;
+use crate::error::{Error, Result};
+use crate::repository::run_spec::{resolve_run, validate_bookmark_name};
+use crate::repository::RepositoryFactory;
+use crate::ui::UI;
+use std::sync::Arc;
+
+/// What the bookmark command should do
+enum Action {
+    List,
+    Set(String, Option<String>),
+    Delete(String),
+}
+
+/// Command to list, set or delete bookmarks.
+///
+/// A bookmark names a run, so it can be referred to in any run spec, for
+/// example `testr failing good..` to see what broke since the run bookmarked
+/// as `good`.
+pub struct BookmarkCommand {
+    base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    action: Action,
+}
+
+impl BookmarkCommand {
+    /// Creates a command that lists every bookmark.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    pub fn new(base_path: Option<String>) -> Self {
+        BookmarkCommand {
+            base_path,
+            repository_factory: None,
+            action: Action::List,
+        }
+    }
+
+    /// Creates a command that points a bookmark at a run.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    /// * `name` - Name of the bookmark
+    /// * `run` - Run spec naming the run (defaults to the latest run)
+    pub fn with_set(base_path: Option<String>, name: String, run: Option<String>) -> Self {
+        BookmarkCommand {
+            base_path,
+            repository_factory: None,
+            action: Action::Set(name, run),
+        }
+    }
+
+    /// Creates a command that deletes a bookmark.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    /// * `name` - Name of the bookmark
+    pub fn with_delete(base_path: Option<String>, name: String) -> Self {
+        BookmarkCommand {
+            base_path,
+            repository_factory: None,
+            action: Action::Delete(name),
+        }
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+impl Command for BookmarkCommand {
+    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+        let mut repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+
+        match self.action {
+            Action::List => {
+                for (name, run_id) in repo.get_bookmarks()? {
+                    ui.output(&format!("{} -> {}", name, run_id))?;
+                }
+            }
+            Action::Set(ref name, ref run) => {
+                validate_bookmark_name(name)?;
+                let run_id = resolve_run(repo.as_ref(), run.as_deref().unwrap_or("-1"))?;
+                repo.set_bookmark(name, Some(&run_id))?;
+                ui.output(&format!("{} -> {}", name, run_id))?;
+            }
+            Action::Delete(ref name) => {
+                if !repo.get_bookmarks()?.contains_key(name) {
+                    return Err(Error::Config(format!("No bookmark named '{}'", name)));
+                }
+                repo.set_bookmark(name, None)?;
+            }
+        }
+        Ok(0)
+    }
+
+    fn name(&self) -> &str {
+        "bookmark"
+    }
+
+    fn help(&self) -> &str {
+        "List, set or delete names for test runs"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::TestRun;
+    use crate::ui::test_ui::TestUI;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bookmark_set_list_delete() {
+        let temp = TempDir::new().unwrap();
+        let base = Some(temp.path().to_string_lossy().to_string());
+        let mut repo = super::super::utils::init_repository(base.as_deref()).unwrap();
+        for i in 0..3 {
+            repo.insert_test_run(TestRun::new(i.to_string())).unwrap();
+        }
+
+        let mut ui = TestUI::new();
+        let cmd = BookmarkCommand::with_set(base.clone(), "good".to_string(), None);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        let cmd = BookmarkCommand::with_set(
+            base.clone(),
+            "before".to_string(),
+            Some("good~1".to_string()),
+        );
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+
+        let mut ui = TestUI::new();
+        let cmd = BookmarkCommand::new(base.clone());
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(ui.output, vec!["before -> 1", "good -> 2"]);
+
+        let cmd = BookmarkCommand::with_delete(base.clone(), "good".to_string());
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert!(cmd.execute(&mut ui).is_err());
+
+        // Names that would read as run references are rejected
+        let cmd = BookmarkCommand::with_set(base.clone(), "12".to_string(), None);
+        assert!(cmd.execute(&mut ui).is_err());
+        let cmd = BookmarkCommand::with_set(base, "new".to_string(), Some("7".to_string()));
+        assert!(cmd.execute(&mut ui).is_err());
+    }
+}
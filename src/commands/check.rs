@@ -0,0 +1,142 @@
+//! Check the repository for damage
+
+use crate::commands::utils::open_repository_with;
+use crate::commands::Command;
+use crate::error::Result;
+use crate::repository::RepositoryFactory;
+use crate::ui::UI;
+use std::sync::Arc;
+
+/// Command to validate every stored stream and the files derived from them.
+///
+/// Corrupt packets are reported with their byte offset. With `--repair`, torn
+/// writes at the end of a stream are cut off, the run counter is set to follow
+/// the highest run and the failing tests and times database are rebuilt from
+/// the stored runs.
+pub struct CheckCommand {
+    base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    repair: bool,
+}
+
+impl CheckCommand {
+    /// Creates a check command that only reports problems.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    pub fn new(base_path: Option<String>) -> Self {
+        CheckCommand {
+            base_path,
+            repository_factory: None,
+            repair: false,
+        }
+    }
+
+    /// Creates a check command that repairs what it can.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    /// * `repair` - Whether to repair the problems found
+    pub fn with_repair(base_path: Option<String>, repair: bool) -> Self {
+        CheckCommand {
+            base_path,
+            repository_factory: None,
+            repair,
+        }
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+impl Command for CheckCommand {
+    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+        let mut repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+
+        let issues = repo.check(self.repair)?;
+        if issues.is_empty() {
+            ui.output("Repository OK")?;
+            return Ok(0);
+        }
+
+        for issue in &issues {
+            let suffix = if issue.repaired { " (repaired)" } else { "" };
+            ui.output(&format!("{}: {}{}", issue.location, issue.message, suffix))?;
+        }
+
+        let remaining = issues.iter().filter(|issue| !issue.repaired).count();
+        ui.output(&format!(
+            "{} problem(s) found, {} repaired",
+            issues.len(),
+            issues.len() - remaining
+        ))?;
+
+        Ok(if remaining == 0 { 0 } else { 1 })
+    }
+
+    fn name(&self) -> &str {
+        "check"
+    }
+
+    fn help(&self) -> &str {
+        "Check the repository for damage"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{TestResult, TestRun};
+    use crate::ui::test_ui::TestUI;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_command() {
+        let temp = TempDir::new().unwrap();
+        let base = Some(temp.path().to_string_lossy().to_string());
+        let mut repo = super::super::utils::init_repository(base.as_deref()).unwrap();
+        let mut run = TestRun::new("0".to_string());
+        run.add_result(TestResult::failure("test1", "Failed"));
+        repo.insert_test_run(run).unwrap();
+
+        let mut ui = TestUI::new();
+        assert_eq!(CheckCommand::new(base.clone()).execute(&mut ui).unwrap(), 0);
+        assert_eq!(ui.output, vec!["Repository OK"]);
+
+        let next_stream = temp.path().join(".testrepository").join("next-stream");
+        std::fs::write(&next_stream, "0\n").unwrap();
+
+        let mut ui = TestUI::new();
+        assert_eq!(CheckCommand::new(base.clone()).execute(&mut ui).unwrap(), 1);
+        assert_eq!(
+            ui.output,
+            vec![
+                "next-stream: next-stream is 0 but run 0 exists",
+                "1 problem(s) found, 0 repaired"
+            ]
+        );
+
+        let mut ui = TestUI::new();
+        let cmd = CheckCommand::with_repair(base.clone(), true);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(
+            ui.output,
+            vec![
+                "next-stream: next-stream is 0 but run 0 exists (repaired)",
+                "1 problem(s) found, 1 repaired"
+            ]
+        );
+        assert_eq!(std::fs::read_to_string(&next_stream).unwrap(), "1\n");
+    }
+}
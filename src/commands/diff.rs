@@ -0,0 +1,305 @@
+//! Compare two test runs
+
+use crate::commands::utils::{json_string, open_repository_with};
+use crate::commands::Command;
+use crate::error::Result;
+use crate::repository::run_diff::{ChangeKind, RunDiff, TestChange};
+use crate::repository::run_spec::resolve_run;
+use crate::repository::{RepositoryFactory, TestStatus};
+use crate::ui::UI;
+use std::sync::Arc;
+
+/// Default percentage by which a test must slow down to be reported
+pub const DEFAULT_THRESHOLD: f64 = 50.0;
+
+/// Section headings for each kind of change, in display order
+const SECTIONS: &[(ChangeKind, &str, &str)] = &[
+    (ChangeKind::NewFailure, "New failures", "new_failures"),
+    (ChangeKind::Fixed, "Fixed", "fixed"),
+    (ChangeKind::Added, "Added", "added"),
+    (ChangeKind::Removed, "Removed", "removed"),
+    (ChangeKind::StatusChange, "Status changes", "status_changes"),
+];
+
+/// Command to show what changed between two test runs.
+///
+/// Reports tests that started failing, were fixed, appeared, disappeared or
+/// changed status, and tests that got noticeably slower. Exits with 1 if any
+/// test fails in the new run that didn't fail in the old one.
+pub struct DiffCommand {
+    base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    old_run: Option<String>,
+    new_run: Option<String>,
+    threshold: f64,
+    json: bool,
+}
+
+impl DiffCommand {
+    /// Creates a diff command comparing the previous run with the latest run.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    pub fn new(base_path: Option<String>) -> Self {
+        DiffCommand {
+            base_path,
+            repository_factory: None,
+            old_run: None,
+            new_run: None,
+            threshold: DEFAULT_THRESHOLD,
+            json: false,
+        }
+    }
+
+    /// Creates a diff command with all options.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    /// * `old_run` - Run spec for the old run (defaults to the previous run)
+    /// * `new_run` - Run spec for the new run (defaults to the latest run)
+    /// * `threshold` - Percentage by which a test must slow down to be reported
+    /// * `json` - Whether to output JSON instead of text
+    pub fn with_options(
+        base_path: Option<String>,
+        old_run: Option<String>,
+        new_run: Option<String>,
+        threshold: f64,
+        json: bool,
+    ) -> Self {
+        DiffCommand {
+            base_path,
+            repository_factory: None,
+            old_run,
+            new_run,
+            threshold,
+            json,
+        }
+    }
+
+    fn show_text(&self, ui: &mut dyn UI, diff: &RunDiff) -> Result<()> {
+        ui.output(&format!(
+            "Comparing run {} with run {}",
+            diff.old_run, diff.new_run
+        ))?;
+        if diff.is_empty() {
+            ui.output("No differences")?;
+            return Ok(());
+        }
+
+        for (kind, heading, _) in SECTIONS {
+            let changes: Vec<&TestChange> = diff.changes_of_kind(*kind).collect();
+            if changes.is_empty() {
+                continue;
+            }
+            ui.output(&format!("{} ({}):", heading, changes.len()))?;
+            for change in changes {
+                let status = match (change.old_status, change.new_status) {
+                    (Some(old), Some(new)) => format!("{} -> {}", old, new),
+                    (Some(status), None) | (None, Some(status)) => status.to_string(),
+                    (None, None) => String::new(),
+                };
+                ui.output(&format!("  {} ({})", change.test_id, status))?;
+            }
+        }
+
+        if !diff.slower.is_empty() {
+            ui.output(&format!("Slower ({}):", diff.slower.len()))?;
+            for change in &diff.slower {
+                ui.output(&format!(
+                    "  {} {:.3}s -> {:.3}s (+{:.1}%)",
+                    change.test_id,
+                    change.old_duration.as_secs_f64(),
+                    change.new_duration.as_secs_f64(),
+                    change.percent_change()
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn show_json(&self, ui: &mut dyn UI, diff: &RunDiff) -> Result<()> {
+        let status = |status: Option<TestStatus>| match status {
+            Some(status) => json_string(&status.to_string()),
+            None => "null".to_string(),
+        };
+
+        let mut fields = vec![
+            format!("\"old_run\": {}", json_string(&diff.old_run)),
+            format!("\"new_run\": {}", json_string(&diff.new_run)),
+        ];
+        for (kind, _, key) in SECTIONS {
+            let changes: Vec<String> = diff
+                .changes_of_kind(*kind)
+                .map(|change| {
+                    format!(
+                        "{{\"test_id\": {}, \"old_status\": {}, \"new_status\": {}}}",
+                        json_string(change.test_id.as_str()),
+                        status(change.old_status),
+                        status(change.new_status)
+                    )
+                })
+                .collect();
+            fields.push(format!("\"{}\": [{}]", key, changes.join(", ")));
+        }
+        let slower: Vec<String> = diff
+            .slower
+            .iter()
+            .map(|change| {
+                format!(
+                    "{{\"test_id\": {}, \"old_duration\": {:.6}, \"new_duration\": {:.6}}}",
+                    json_string(change.test_id.as_str()),
+                    change.old_duration.as_secs_f64(),
+                    change.new_duration.as_secs_f64()
+                )
+            })
+            .collect();
+        fields.push(format!("\"slower\": [{}]", slower.join(", ")));
+
+        ui.output(&format!("{{{}}}", fields.join(", ")))
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+impl Command for DiffCommand {
+    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+        let repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+
+        let old_id = resolve_run(repo.as_ref(), self.old_run.as_deref().unwrap_or("-2"))?;
+        let new_id = resolve_run(repo.as_ref(), self.new_run.as_deref().unwrap_or("-1"))?;
+        let diff = RunDiff::compare(
+            &repo.get_test_run(&old_id)?,
+            &repo.get_test_run(&new_id)?,
+            self.threshold,
+        );
+
+        if self.json {
+            self.show_json(ui, &diff)?;
+        } else {
+            self.show_text(ui, &diff)?;
+        }
+
+        Ok(if diff.has_new_failures() { 1 } else { 0 })
+    }
+
+    fn name(&self) -> &str {
+        "diff"
+    }
+
+    fn help(&self) -> &str {
+        "Compare two test runs"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::file::FileRepositoryFactory;
+    use crate::repository::{RepositoryFactory, TestResult, TestRun};
+    use crate::ui::test_ui::TestUI;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, Option<String>) {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+
+        let mut test_run = TestRun::new("0".to_string());
+        test_run.add_result(TestResult::success("test1"));
+        test_run.add_result(TestResult::failure("test2", "boom"));
+        test_run.add_result(TestResult::success("test3").with_duration(Duration::from_secs(1)));
+        repo.insert_test_run(test_run).unwrap();
+
+        let mut test_run = TestRun::new("1".to_string());
+        test_run.add_result(TestResult::failure("test1", "boom"));
+        test_run.add_result(TestResult::success("test2"));
+        test_run.add_result(TestResult::success("test3").with_duration(Duration::from_secs(3)));
+        test_run.add_result(TestResult::skip("test4"));
+        repo.insert_test_run(test_run).unwrap();
+
+        let base_path = Some(temp.path().to_string_lossy().to_string());
+        (temp, base_path)
+    }
+
+    #[test]
+    fn test_diff_command() {
+        let (_temp, base_path) = setup();
+
+        let mut ui = TestUI::new();
+        let cmd = DiffCommand::new(base_path.clone());
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+        assert_eq!(
+            ui.output,
+            vec![
+                "Comparing run 0 with run 1",
+                "New failures (1):",
+                "  test1 (success -> failure)",
+                "Fixed (1):",
+                "  test2 (failure -> success)",
+                "Added (1):",
+                "  test4 (skip)",
+                "Slower (1):",
+                "  test3 1.000s -> 3.000s (+200.0%)",
+            ]
+        );
+
+        // Reversed, test2 is the new failure and nothing got slower
+        let mut ui = TestUI::new();
+        let cmd = DiffCommand::with_options(
+            base_path.clone(),
+            Some("1".to_string()),
+            Some("0".to_string()),
+            DEFAULT_THRESHOLD,
+            false,
+        );
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+        assert!(ui
+            .output
+            .contains(&"  test2 (success -> failure)".to_string()));
+        assert!(ui.output.contains(&"  test4 (skip)".to_string()));
+        assert!(!ui.output.iter().any(|line| line.starts_with("Slower")));
+
+        let mut ui = TestUI::new();
+        let cmd = DiffCommand::with_options(
+            base_path,
+            Some("1".to_string()),
+            Some("-1".to_string()),
+            DEFAULT_THRESHOLD,
+            false,
+        );
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(ui.output[1], "No differences");
+    }
+
+    #[test]
+    fn test_diff_command_json() {
+        let (_temp, base_path) = setup();
+
+        let mut ui = TestUI::new();
+        let cmd = DiffCommand::with_options(base_path, None, None, 500.0, true);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+        assert_eq!(
+            ui.output,
+            vec![concat!(
+                r#"{"old_run": "0", "new_run": "1", "#,
+                r#""new_failures": [{"test_id": "test1", "old_status": "success", "new_status": "failure"}], "#,
+                r#""fixed": [{"test_id": "test2", "old_status": "failure", "new_status": "success"}], "#,
+                r#""added": [{"test_id": "test4", "old_status": null, "new_status": "skip"}], "#,
+                r#""removed": [], "status_changes": [], "slower": []}"#
+            )]
+        );
+    }
+}
@@ -1,9 +1,11 @@
 //! Show currently failing tests
 
-use crate::commands::utils::open_repository;
+use crate::commands::utils::{load_selected_runs, open_repository_with};
 use crate::commands::Command;
 use crate::error::Result;
+use crate::repository::{RepositoryFactory, TestId, TestRun};
 use crate::ui::UI;
+use std::sync::Arc;
 
 /// Command to display currently failing tests from the repository.
 ///
@@ -11,8 +13,10 @@ use crate::ui::UI;
 /// displays the test IDs in various formats.
 pub struct FailingCommand {
     base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
     list_only: bool,
     subunit: bool,
+    run: Option<String>,
 }
 
 impl FailingCommand {
@@ -23,8 +27,10 @@ impl FailingCommand {
     pub fn new(base_path: Option<String>) -> Self {
         FailingCommand {
             base_path,
+            repository_factory: None,
             list_only: false,
             subunit: false,
+            run: None,
         }
     }
 
@@ -35,8 +41,10 @@ impl FailingCommand {
     pub fn with_list_only(base_path: Option<String>) -> Self {
         FailingCommand {
             base_path,
+            repository_factory: None,
             list_only: true,
             subunit: false,
+            run: None,
         }
     }
 
@@ -47,18 +55,68 @@ impl FailingCommand {
     pub fn with_subunit(base_path: Option<String>) -> Self {
         FailingCommand {
             base_path,
+            repository_factory: None,
             list_only: false,
             subunit: true,
+            run: None,
         }
     }
+
+    /// Show the tests that failed in the runs named by a run spec instead of
+    /// the repository's current failing tests.
+    ///
+    /// # Arguments
+    /// * `run` - Run spec naming a run or a range of runs (see
+    ///   [`crate::repository::run_spec`])
+    pub fn for_run(mut self, run: Option<String>) -> Self {
+        self.run = run;
+        self
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
 }
 
 impl Command for FailingCommand {
     fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
-        let repo = open_repository(self.base_path.as_deref())?;
+        let repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
 
-        // Get failing tests from the repository's failing file
-        let failing_tests = repo.get_failing_tests()?;
+        // Tests whose most recent result in the selected runs is a failure
+        let selected = match &self.run {
+            Some(spec) => {
+                let test_run = load_selected_runs(repo.as_ref(), Some(spec))?;
+                let mut failing = TestRun::new(test_run.id);
+                failing.timestamp = test_run.timestamp;
+                for result in test_run.results.into_values() {
+                    if result.status.is_failure() {
+                        failing.add_result(result);
+                    }
+                }
+                Some(failing)
+            }
+            None => None,
+        };
+
+        // Otherwise get failing tests from the repository's failing file
+        let failing_tests = match &selected {
+            Some(failing) => {
+                let mut test_ids: Vec<TestId> = failing.results.keys().cloned().collect();
+                test_ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+                test_ids
+            }
+            None => repo.get_failing_tests()?,
+        };
 
         if failing_tests.is_empty() {
             if !self.list_only && !self.subunit {
@@ -68,6 +126,13 @@ impl Command for FailingCommand {
         }
 
         if self.subunit {
+            if let Some(failing) = &selected {
+                let mut buffer = Vec::new();
+                crate::subunit_stream::write_stream(failing, &mut buffer)?;
+                ui.output_bytes(&buffer)?;
+                return Ok(0);
+            }
+
             // Output the failing tests as a raw subunit stream
             // The failing file is already a subunit stream with all attachments
             use std::io::Read;
@@ -98,7 +163,7 @@ impl Command for FailingCommand {
     }
 
     fn help(&self) -> &str {
-        "Show tests that failed in the last run"
+        "Show tests that failed in the last run, or in other runs"
     }
 }
 
@@ -213,4 +278,40 @@ mod tests {
         // No header in list mode
         assert!(!ui.output.iter().any(|s| s.contains("failing test(s):")));
     }
+
+    #[test]
+    fn test_failing_command_for_runs() {
+        let temp = TempDir::new().unwrap();
+
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+
+        let mut test_run = TestRun::new("0".to_string());
+        test_run.add_result(TestResult::failure("test1", "Failed"));
+        test_run.add_result(TestResult::failure("test2", "Failed"));
+        repo.insert_test_run(test_run).unwrap();
+        let mut test_run = TestRun::new("1".to_string());
+        test_run.add_result(TestResult::success("test1"));
+        test_run.add_result(TestResult::failure("test3", "Failed"));
+        repo.insert_test_run(test_run).unwrap();
+        let mut test_run = TestRun::new("2".to_string());
+        test_run.add_result(TestResult::success("test3"));
+        repo.insert_test_run(test_run).unwrap();
+
+        let base_path = Some(temp.path().to_string_lossy().to_string());
+        let failing = |spec: &str| {
+            let mut ui = TestUI::new();
+            let cmd =
+                FailingCommand::with_list_only(base_path.clone()).for_run(Some(spec.to_string()));
+            cmd.execute(&mut ui).unwrap();
+            ui.output
+        };
+
+        assert_eq!(failing("0"), vec!["test1", "test2"]);
+        assert_eq!(failing("-2"), vec!["test3"]);
+        // test1 passed later in the range, test2 never ran again
+        assert_eq!(failing("0..1"), vec!["test2", "test3"]);
+        assert_eq!(failing("..").len(), 1);
+        assert!(failing("2").is_empty());
+    }
 }
@@ -0,0 +1,154 @@
+//! Show flaky tests
+
+use crate::commands::utils::open_repository_with;
+use crate::commands::Command;
+use crate::error::Result;
+use crate::repository::flaky::find_flaky_tests;
+use crate::repository::run_spec::resolve_runs;
+use crate::repository::{RepositoryFactory, TestSelector};
+use crate::ui::UI;
+use std::sync::Arc;
+
+/// Command to list tests whose outcome flips between runs of the same code.
+///
+/// See [`crate::repository::flaky`] for which runs are compared.
+pub struct FlakyCommand {
+    base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    run: Option<String>,
+}
+
+impl FlakyCommand {
+    /// Creates a flaky command that scans every stored run.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    pub fn new(base_path: Option<String>) -> Self {
+        FlakyCommand {
+            base_path,
+            repository_factory: None,
+            run: None,
+        }
+    }
+
+    /// Only scan the runs named by a run spec.
+    ///
+    /// # Arguments
+    /// * `run` - Run spec naming a range of runs (see
+    ///   [`crate::repository::run_spec`])
+    pub fn for_run(mut self, run: Option<String>) -> Self {
+        self.run = run;
+        self
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+impl Command for FlakyCommand {
+    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+        let repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+
+        let run_ids = match &self.run {
+            Some(spec) => resolve_runs(repo.as_ref(), spec)?,
+            None => repo.list_run_ids()?,
+        };
+        let flaky = find_flaky_tests(repo.as_ref(), &run_ids, &TestSelector::All)?;
+
+        if flaky.is_empty() {
+            ui.output("No flaky tests found")?;
+            return Ok(0);
+        }
+
+        ui.output(&format!("{} flaky test(s):", flaky.len()))?;
+        for test in &flaky {
+            ui.output(&format!(
+                "  {:.2} {} (flipped {} of {} times, last in run {})",
+                test.score(),
+                test.test_id,
+                test.flips,
+                test.comparisons,
+                test.last_flip
+            ))?;
+        }
+        Ok(0)
+    }
+
+    fn name(&self) -> &str {
+        "flaky"
+    }
+
+    fn help(&self) -> &str {
+        "Show tests whose outcome flips between runs of the same code"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryRepositoryFactory;
+    use crate::repository::{RunMetadata, TestResult, TestRun};
+    use crate::ui::test_ui::TestUI;
+    use std::path::Path;
+
+    #[test]
+    fn test_flaky_command() {
+        let factory = Arc::new(MemoryRepositoryFactory::new());
+        let base_path = Some("/repo".to_string());
+        let mut repo = factory.initialise(Path::new("/repo")).unwrap();
+        let flaky = |run: Option<&str>| {
+            FlakyCommand::new(base_path.clone())
+                .for_run(run.map(str::to_string))
+                .with_repository_factory(factory.clone())
+        };
+
+        let mut ui = TestUI::new();
+        assert_eq!(flaky(None).execute(&mut ui).unwrap(), 0);
+        assert_eq!(ui.output, vec!["No flaky tests found"]);
+
+        for (i, passed) in [true, false, false, true].into_iter().enumerate() {
+            let mut test_run = TestRun::new(i.to_string());
+            test_run.add_result(if passed {
+                TestResult::success("test1")
+            } else {
+                TestResult::failure("test1", "boom")
+            });
+            test_run.add_result(TestResult::success("test2"));
+            let run_id = repo.insert_test_run(test_run).unwrap();
+            repo.set_run_metadata(
+                &run_id,
+                &RunMetadata {
+                    git_commit: Some("0123456789abcdef".to_string()),
+                    git_dirty: Some(false),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+
+        let mut ui = TestUI::new();
+        assert_eq!(flaky(None).execute(&mut ui).unwrap(), 0);
+        assert_eq!(
+            ui.output,
+            vec![
+                "1 flaky test(s):",
+                "  0.67 test1 (flipped 2 of 3 times, last in run 3)",
+            ]
+        );
+
+        let mut ui = TestUI::new();
+        assert_eq!(flaky(Some("1..2")).execute(&mut ui).unwrap(), 0);
+        assert_eq!(ui.output, vec!["No flaky tests found"]);
+    }
+}
@@ -29,14 +29,20 @@ impl Command for HelpCommand {
                 "init" => {
                     r#"testr init - Initialize a new test repository
 
-Usage: testr init [PATH]
+Usage: testr init [PATH] [--type TYPE]
 
 Creates a new test repository in the .testrepository directory.
 If PATH is provided, initializes the repository at that location.
 
+Options:
+  --type TYPE       Kind of repository: 'file' (the default, compatible with
+                    the Python testrepository) or 'sql', which also indexes
+                    every run's results in SQLite for faster queries
+
 Examples:
   testr init              # Initialize in current directory
   testr init /path/to/dir # Initialize at specific path
+  testr init --type sql   # Initialize an indexed repository
 "#
                 }
                 "load" => {
@@ -63,22 +69,55 @@ Usage: testr run [OPTIONS]
 Executes the test command from .testr.conf and loads the results.
 
 Options:
-  --failing         Only run tests that failed in the last run
-  --load-list FILE  Run only tests listed in FILE
-  --partial         Keep previous failures and add new ones
+  --failing               Only run tests that failed in the last run
+  --load-list FILE        Run only tests listed in FILE
+  --partial               Keep previous failures and add new ones
+  --retry N               Re-run failing tests up to N more times
+  --flaky-policy POLICY   Whether a test that passes on retry still fails
+                          the run and stays failing: pass (default) or fail
+  --fail-fast             Stop the run after the first failure (the same
+                          as --max-failures 1, so not combined with it)
+  --max-failures N        Stop the run after N failures
+  --test-timeout TIMEOUT  Terminate a test that runs longer than TIMEOUT
+                          seconds, or a multiple of its recorded time (5x)
+  --run-timeout SECONDS   Terminate the whole run after SECONDS
+  --reschedule-timeouts   Run the tests a timed-out process never got to
+  --dynamic               Hand tests to parallel workers in batches as they
+                          become free, instead of partitioning them up front
+  --batch-size N          Maximum number of tests in a batch with --dynamic
+
+Tests that fail and then pass on retry are reported as flaky. Every attempt
+is stored in the run, tagged retry-1, retry-2 and so on. Failed tests that
+testr flaky finds flaky in the stored runs are listed after the summary.
+
+A run stopped by --fail-fast, --max-failures or --run-timeout stores the
+results it has. The tests it never ran keep their previous state in the
+failing tests. A test that times out is recorded as an error, as is a test
+that was running when its test process died. Tests a dead process never got
+to are reported as not run.
+
+Interrupting a run with Ctrl-C (or sending testr SIGTERM) passes the signal
+on to the test processes and stores what they reported as a partial run,
+marked as interrupted. testr then exits with 128 plus the signal number. A
+second Ctrl-C exits at once without storing anything.
 
 Examples:
   testr run
   testr run --failing
   testr run --load-list tests_to_run.txt
+  testr run --retry 2 --flaky-policy fail
+  testr run --parallel --max-failures 5
+  testr run --parallel --dynamic --batch-size 20
+  testr run --test-timeout 5x --reschedule-timeouts
 "#
                 }
                 "failing" => {
                     r#"testr failing - Show currently failing tests
 
-Usage: testr failing [OPTIONS]
+Usage: testr failing [OPTIONS] [RUN]
 
-Lists all tests that failed in the most recent run.
+Lists all tests that failed in the most recent run. Given RUN, lists the tests
+whose latest result in those runs was a failure instead.
 
 Options:
   --list      Show test IDs only (one per line)
@@ -88,14 +127,15 @@ Examples:
   testr failing
   testr failing --list
   testr failing --subunit
+  testr failing good..
 "#
                 }
                 "last" => {
                     r#"testr last - Show results from the last test run
 
-Usage: testr last [OPTIONS]
+Usage: testr last [OPTIONS] [RUN]
 
-Displays test results from the most recent run.
+Displays test results from the most recent run, or from RUN.
 
 Options:
   --subunit   Output in subunit format
@@ -103,30 +143,116 @@ Options:
 Examples:
   testr last
   testr last --subunit
+  testr last 17
+  testr last -2
+"#
+                }
+                "diff" => {
+                    r#"testr diff - Compare two test runs
+
+Usage: testr diff [OPTIONS] [A] [B]
+
+Shows what changed between run A (default: the run before the latest) and
+run B (default: the latest run): tests that started failing, were fixed,
+were added or removed, or changed status, and tests that got slower by more
+than the threshold. Exits with 1 if there are new failures.
+
+Options:
+  --threshold PERCENT   Report tests that slowed down by more than this (default: 50)
+  --json                Output the differences as JSON
+
+Examples:
+  testr diff
+  testr diff good
+  testr diff 10 15 --json
+"#
+                }
+                "flaky" => {
+                    r#"testr flaky - Show tests whose outcome flips between runs of the same code
+
+Usage: testr flaky [RUN]
+
+Scans the stored runs (or the runs named by RUN) for tests that passed in
+one run and failed in the next, or the other way around, while the code
+stayed the same. Flips are only counted between runs that recorded the same
+git commit in a checkout with no uncommitted changes; runs made outside a
+git checkout, or stored without their git_commit metadata, are never
+compared. A test that passed when testr run --retry re-ran it has flipped
+within its run, whatever the commit. Each test is listed with its
+score - the fraction of comparable pairs of runs in which it flipped - and
+the run it last flipped in.
+
+After its summary, testr run lists the failed tests that are known to be
+flaky.
+
+Examples:
+  testr flaky
+  testr flaky -20..
+"#
+                }
+                "history" => {
+                    r#"testr history - Show how tests behaved across stored runs
+
+Usage: testr history TEST
+
+Lists the status and duration of TEST in every stored run it appeared in,
+followed by the run it first failed in, the run it last passed in, its pass
+rate and statistics over its durations. TEST is a test ID or a regex; every
+matching test is shown.
+
+Examples:
+  testr history pkg.tests.test_foo
+  testr history 'test_foo|test_bar'
 "#
                 }
                 "stats" => {
                     r#"testr stats - Show repository statistics
 
-Usage: testr stats
+Usage: testr stats [RUN]
 
 Displays statistics about the test repository, including total runs,
-test counts, and success/failure rates.
+test counts, and success/failure rates. Given RUN, only those runs are
+counted.
 
-Example:
+Examples:
   testr stats
+  testr stats 10..15
 "#
                 }
                 "slowest" => {
                     r#"testr slowest - Show the slowest tests
 
-Usage: testr slowest [N]
+Usage: testr slowest [OPTIONS] [RUN]
+
+Shows the N slowest tests from the last run (default: 10). Given RUN, uses
+the timings from those runs instead.
 
-Shows the N slowest tests from the last run (default: 10).
+Options:
+  -n, --count N   Number of tests to show
+  --all           Show every test
 
 Examples:
   testr slowest
-  testr slowest 20
+  testr slowest -n 20
+  testr slowest last~1
+"#
+                }
+                "times" => {
+                    r#"testr times - Show the recorded duration of every test
+
+Usage: testr times [--by-time] [TESTFILTER...]
+
+Dumps the times database, which holds the most recent duration of every test
+that has been run and is used to balance parallel runs. Each line holds the
+duration in seconds followed by the test ID.
+
+Options:
+  --by-time    Sort slowest first instead of by test ID
+
+Examples:
+  testr times
+  testr times --by-time
+  testr times '^mypackage\.tests'
 "#
                 }
                 "list-tests" => {
@@ -138,6 +264,114 @@ Lists all available tests by querying the test command with --list-tests.
 
 Example:
   testr list-tests
+"#
+                }
+                "check" => {
+                    r#"testr check - Check the repository for damage
+
+Usage: testr check [--repair]
+
+Validates every stored run and the failing tests stream, reporting the byte
+offset of each packet whose checksum is wrong and of a final packet that was
+only partly written. It also reports a run counter that is behind or past
+the highest run, gaps in the run numbering, and a times database that can't
+be read. Bytes that don't look like a packet are ordinary test output, not damage.
+The exit code is 1 if any problem is left unrepaired.
+
+With --repair, a stream that ends in an incomplete packet, as left by an
+interrupted write, is truncated before that packet, the run
+counter is set to follow the highest run, and the failing tests and times
+database are rebuilt from the runs that are intact.
+
+Options:
+  --repair          Repair the problems found
+
+Examples:
+  testr check
+  testr check --repair
+"#
+                }
+                "rebuild" => {
+                    r#"testr rebuild - Rebuild the failing tests and times from stored runs
+
+Usage: testr rebuild [--from RUN] [--to RUN]
+
+Recomputes the failing tests and the times database by replaying stored runs
+in order. Full runs replace the failing tests and partial runs update them,
+as when they were first loaded; runs stored before this was recorded are
+replayed as partial. Use this to recover after loading the wrong results.
+
+Options:
+  --from RUN        First run to replay
+  --to RUN          Last run to replay
+
+Examples:
+  testr rebuild
+  testr rebuild --from 40
+"#
+                }
+                "prune" => {
+                    r#"testr prune - Delete old test runs from the repository
+
+Usage: testr prune [--keep N] [--max-age DAYS] [--dry-run]
+
+Deletes runs that are not among the N most recent, or that started more
+than DAYS days ago. Without options the keep_runs and keep_days settings from
+'testr repo-config' are used. The most recent run, the failing tests and the
+recorded test times are always kept. Set auto_prune to prune after every
+'testr run' and 'testr load'.
+
+Options:
+  --keep N          Keep the N most recent runs
+  --max-age DAYS    Keep runs stored within the last DAYS days
+  --dry-run         Only show which runs would be deleted
+
+Examples:
+  testr prune --keep 50
+  testr prune --max-age 30 --dry-run
+"#
+                }
+                "repo-config" => {
+                    r#"testr repo-config - Show or change repository settings
+
+Usage: testr repo-config [KEY [VALUE]]
+       testr repo-config --unset KEY
+
+Settings are stored in .testrepository/repo.conf and only affect this
+checkout. With no arguments all settings are shown; with KEY the value of
+that setting is printed; with KEY and VALUE the setting is changed.
+
+Settings:
+  keep_runs       Number of most recent runs to keep when pruning
+  keep_days       Maximum age in days of runs to keep when pruning
+  auto_prune      Prune runs after every 'testr run' and 'testr load': 'true' or 'false'
+  concurrency     Number of workers for 'testr run' when --parallel is not given
+  output          Test output to show during 'testr run': 'failures' or 'all'
+  time_estimator  How to estimate test times for partitioning: 'mean', 'p90' or 'last'
+
+Examples:
+  testr repo-config
+  testr repo-config concurrency 4
+  testr repo-config --unset concurrency
+"#
+                }
+                "bookmark" => {
+                    r#"testr bookmark - List, set or delete names for test runs
+
+Usage: testr bookmark
+       testr bookmark NAME [RUN]
+       testr bookmark --delete NAME
+
+A bookmark names a run so it can be used wherever a run is expected. With no
+arguments all bookmarks are listed; with NAME the bookmark is pointed at RUN,
+or at the latest run. Names start with a letter and may contain letters,
+digits, '_', '-' and '.'.
+
+Examples:
+  testr bookmark good
+  testr bookmark before-upgrade 17
+  testr failing good..
+  testr bookmark --delete good
 "#
                 }
                 "quickstart" => {
@@ -182,14 +416,31 @@ Available commands:
   run           Run tests and load results
   failing       Show currently failing tests
   last          Show results from the last test run
+  diff          Compare two test runs
+  flaky         Show tests whose outcome flips between runs of the same code
+  history       Show how tests behaved across stored runs
   stats         Show repository statistics
   slowest       Show the slowest tests
+  times         Show the recorded duration of every test
   list-tests    List available tests
+  check         Check the repository for damage
+  rebuild       Rebuild the failing tests and times from stored runs
+  prune         Delete old test runs
+  repo-config   Show or change repository settings
+  bookmark      List, set or delete names for test runs
   quickstart    Show quickstart documentation
   help          Show this help message
 
 Run 'testr help <command>' for more information on a specific command.
 
+Commands can be run from anywhere in a project: testr uses the nearest
+directory above that contains .testrepository or .testr.conf, or the
+directory named by TESTR_DIR.
+
+last, failing, slowest and stats take an optional RUN: a run ID such as 17,
+an offset from the latest run such as -2 or last~1, a bookmark, or a range
+such as 10..15 or good.. (ranges are not accepted by last).
+
 Examples:
   testr init
   testr run
@@ -0,0 +1,194 @@
+//! Show how tests behaved across stored runs
+
+use crate::commands::utils::open_repository_with;
+use crate::commands::Command;
+use crate::error::Result;
+use crate::repository::{HistoryEntry, HistorySummary, RepositoryFactory, TestSelector};
+use crate::ui::UI;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Command to show the history of one or more tests.
+///
+/// Lists the test's status and duration in every stored run it appeared in,
+/// followed by when it first failed, when it last passed, its pass rate and
+/// statistics over its durations.
+pub struct HistoryCommand {
+    base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    pattern: String,
+}
+
+impl HistoryCommand {
+    /// Creates a history command.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    /// * `pattern` - A test ID, or a regex matching the tests to show
+    pub fn new(base_path: Option<String>, pattern: String) -> Self {
+        HistoryCommand {
+            base_path,
+            repository_factory: None,
+            pattern,
+        }
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+fn format_duration(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!("{:.3}s", duration.as_secs_f64()),
+        None => "-".to_string(),
+    }
+}
+
+/// Show the history of a single test
+fn show_history(ui: &mut dyn UI, entries: &[HistoryEntry]) -> Result<()> {
+    for entry in entries {
+        ui.output(&format!(
+            "  run {}: {} {}",
+            entry.run_id,
+            entry.result.status,
+            format_duration(entry.result.duration)
+        ))?;
+    }
+
+    let summary = HistorySummary::from_entries(entries);
+    let pass_rate = match summary.pass_rate() {
+        Some(rate) => format!("{:.1}%", rate * 100.0),
+        None => "-".to_string(),
+    };
+    ui.output(&format!(
+        "  Runs: {} ({} passed, {} failed, {} skipped), pass rate {}",
+        summary.runs, summary.passes, summary.failures, summary.skips, pass_rate
+    ))?;
+    if let Some(run_id) = &summary.first_failure {
+        ui.output(&format!("  First failure: run {}", run_id))?;
+    }
+    if let Some(run_id) = &summary.last_success {
+        ui.output(&format!("  Last success: run {}", run_id))?;
+    }
+    if let Some(stats) = &summary.duration {
+        ui.output(&format!(
+            "  Duration: mean {:.3}s, min {:.3}s, max {:.3}s, stddev {:.3}s",
+            stats.mean.as_secs_f64(),
+            stats.min.as_secs_f64(),
+            stats.max.as_secs_f64(),
+            stats.stddev.as_secs_f64()
+        ))?;
+    }
+    Ok(())
+}
+
+impl Command for HistoryCommand {
+    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+        let repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+
+        let history = repo.get_test_history(&TestSelector::pattern(&self.pattern)?)?;
+
+        if history.is_empty() {
+            ui.output(&format!("No results for tests matching '{}'", self.pattern))?;
+            return Ok(0);
+        }
+
+        let mut by_test: BTreeMap<String, Vec<HistoryEntry>> = BTreeMap::new();
+        for entry in history {
+            by_test
+                .entry(entry.result.test_id.as_str().to_string())
+                .or_default()
+                .push(entry);
+        }
+
+        for (i, (test_id, entries)) in by_test.iter().enumerate() {
+            if i > 0 {
+                ui.output("")?;
+            }
+            ui.output(test_id)?;
+            show_history(ui, entries)?;
+        }
+
+        Ok(0)
+    }
+
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn help(&self) -> &str {
+        "Show how tests behaved across stored runs"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryRepositoryFactory;
+    use crate::repository::{TestResult, TestRun};
+    use crate::ui::test_ui::TestUI;
+    use std::path::Path;
+
+    #[test]
+    fn test_history_command() {
+        let factory = Arc::new(MemoryRepositoryFactory::new());
+        let base_path = Some("/repo".to_string());
+        let mut repo = factory.initialise(Path::new("/repo")).unwrap();
+
+        let results = [
+            TestResult::success("pkg.test_a").with_duration(Duration::from_millis(100)),
+            TestResult::failure("pkg.test_a", "boom").with_duration(Duration::from_millis(300)),
+            TestResult::success("pkg.test_b"),
+        ];
+        for (i, result) in results.into_iter().enumerate() {
+            let mut test_run = TestRun::new(i.to_string());
+            test_run.add_result(result);
+            repo.insert_test_run(test_run).unwrap();
+        }
+
+        let mut ui = TestUI::new();
+        let cmd = HistoryCommand::new(base_path.clone(), "pkg.test_a".to_string())
+            .with_repository_factory(factory.clone());
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(
+            ui.output,
+            vec![
+                "pkg.test_a",
+                "  run 0: success 0.100s",
+                "  run 1: failure 0.300s",
+                "  Runs: 2 (1 passed, 1 failed, 0 skipped), pass rate 50.0%",
+                "  First failure: run 1",
+                "  Last success: run 0",
+                "  Duration: mean 0.200s, min 0.100s, max 0.300s, stddev 0.100s",
+            ]
+        );
+
+        let mut ui = TestUI::new();
+        let cmd = HistoryCommand::new(base_path.clone(), "^pkg\\.".to_string())
+            .with_repository_factory(factory.clone());
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert!(ui.output.contains(&"pkg.test_b".to_string()));
+        assert!(ui
+            .output
+            .iter()
+            .any(|line| line.starts_with("  run 2: success")));
+
+        let mut ui = TestUI::new();
+        let cmd =
+            HistoryCommand::new(base_path, "missing".to_string()).with_repository_factory(factory);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(ui.output, vec!["No results for tests matching 'missing'"]);
+    }
+}
@@ -2,10 +2,10 @@
 
 use crate::commands::Command;
 use crate::error::Result;
-use crate::repository::file::FileRepositoryFactory;
-use crate::repository::RepositoryFactory;
+use crate::repository::{root_override, RepositoryFactory, RepositoryType};
 use crate::ui::UI;
 use std::path::Path;
+use std::sync::Arc;
 
 /// Command to initialize a new test repository.
 ///
@@ -13,6 +13,8 @@ use std::path::Path;
 /// to store test results and metadata.
 pub struct InitCommand {
     base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    repo_type: RepositoryType,
 }
 
 impl InitCommand {
@@ -21,7 +23,39 @@ impl InitCommand {
     /// # Arguments
     /// * `base_path` - Optional base directory path where the repository will be created
     pub fn new(base_path: Option<String>) -> Self {
-        InitCommand { base_path }
+        InitCommand {
+            base_path,
+            repository_factory: None,
+            repo_type: RepositoryType::File,
+        }
+    }
+
+    /// Creates an init command for a particular kind of repository.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path where the repository will be created
+    /// * `repo_type` - Kind of repository to create
+    pub fn with_type(base_path: Option<String>, repo_type: RepositoryType) -> Self {
+        InitCommand {
+            base_path,
+            repository_factory: None,
+            repo_type,
+        }
+    }
+
+    /// Create the repository through a factory instead of on disk.
+    ///
+    /// The factory decides the kind of repository, so the type is ignored.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory to hold the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
     }
 }
 
@@ -32,10 +66,15 @@ impl Command for InitCommand {
             .as_deref()
             .map(Path::new)
             .unwrap_or_else(|| Path::new("."));
+        // Like `git init`, don't look upwards, but do honour TESTR_DIR
+        let base = root_override(base).unwrap_or_else(|| base.to_path_buf());
 
-        let factory = FileRepositoryFactory;
+        let created = match &self.repository_factory {
+            Some(factory) => factory.initialise(&base),
+            None => self.repo_type.factory().initialise(&base),
+        };
 
-        match factory.initialise(base) {
+        match created {
             Ok(_) => {
                 ui.output("Initialized empty test repository")?;
                 Ok(0)
@@ -129,4 +168,18 @@ mod tests {
         assert_eq!(ui.errors.len(), 1);
         assert!(ui.errors[0].contains("Failed"));
     }
+
+    #[test]
+    fn test_init_command_sql() {
+        let temp = TempDir::new().unwrap();
+        let mut ui = TestUI::new();
+
+        let base = Some(temp.path().to_string_lossy().to_string());
+        let cmd = InitCommand::with_type(base.clone(), RepositoryType::Sql);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert!(temp.path().join(".testrepository/results.db").exists());
+
+        let repo = crate::commands::utils::open_repository(base.as_deref()).unwrap();
+        assert_eq!(repo.count().unwrap(), 0);
+    }
 }
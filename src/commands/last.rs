@@ -1,9 +1,12 @@
 //! Show the last test run
 
-use crate::commands::utils::open_repository;
+use crate::commands::utils::{load_filter_tags, open_repository_with};
 use crate::commands::Command;
 use crate::error::Result;
+use crate::repository::run_spec::resolve_run;
+use crate::repository::{RepositoryFactory, RunMetadata};
 use crate::ui::UI;
+use std::sync::Arc;
 
 /// Command to display results from the last test run.
 ///
@@ -11,8 +14,10 @@ use crate::ui::UI;
 /// most recent run stored in the repository.
 pub struct LastCommand {
     base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
     subunit: bool,
     show_output: bool,
+    run: Option<String>,
 }
 
 impl LastCommand {
@@ -23,8 +28,10 @@ impl LastCommand {
     pub fn new(base_path: Option<String>) -> Self {
         LastCommand {
             base_path,
+            repository_factory: None,
             subunit: false,
             show_output: true, // By default, show output for failed tests (matches Python behavior)
+            run: None,
         }
     }
 
@@ -35,8 +42,10 @@ impl LastCommand {
     pub fn with_subunit(base_path: Option<String>) -> Self {
         LastCommand {
             base_path,
+            repository_factory: None,
             subunit: true,
             show_output: false, // Subunit mode doesn't show formatted output
+            run: None,
         }
     }
 
@@ -48,16 +57,76 @@ impl LastCommand {
     pub fn with_output_control(base_path: Option<String>, show_output: bool) -> Self {
         LastCommand {
             base_path,
+            repository_factory: None,
             subunit: false,
             show_output,
+            run: None,
         }
     }
+
+    /// Show the run named by a run spec instead of the latest run.
+    ///
+    /// # Arguments
+    /// * `run` - Run spec naming a single run (see [`crate::repository::run_spec`])
+    pub fn for_run(mut self, run: Option<String>) -> Self {
+        self.run = run;
+        self
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+/// Show how a run was made, as far as that was recorded
+fn show_run_metadata(ui: &mut dyn UI, metadata: &RunMetadata) -> Result<()> {
+    if let Some(duration) = metadata.duration() {
+        ui.output(&format!("Wall time: {:.3}s", duration.as_secs_f64()))?;
+    }
+    if let Some(command) = &metadata.command {
+        ui.output(&format!("Command: {}", command))?;
+    }
+    if let Some(concurrency) = metadata.concurrency {
+        ui.output(&format!("Concurrency: {}", concurrency))?;
+    }
+    if !metadata.exit_codes.is_empty() {
+        let exit_codes: Vec<String> = metadata.exit_codes.iter().map(i32::to_string).collect();
+        ui.output(&format!("Exit codes: {}", exit_codes.join(", ")))?;
+    }
+    if metadata.interrupted == Some(true) {
+        ui.output("Interrupted: yes")?;
+    }
+    if let Some(commit) = &metadata.git_commit {
+        let dirty = if metadata.git_dirty == Some(true) {
+            " (dirty)"
+        } else {
+            ""
+        };
+        ui.output(&format!("Revision: {}{}", commit, dirty))?;
+    }
+    if let Some(hostname) = &metadata.hostname {
+        ui.output(&format!("Host: {}", hostname))?;
+    }
+    Ok(())
 }
 
 impl Command for LastCommand {
     fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
-        let repo = open_repository(self.base_path.as_deref())?;
-        let test_run = repo.get_latest_run()?;
+        let repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+        let test_run = match &self.run {
+            Some(spec) => repo.get_test_run(&resolve_run(repo.as_ref(), spec)?)?,
+            None => repo.get_latest_run()?,
+        };
 
         if self.subunit {
             // Output the test run as a subunit stream
@@ -67,17 +136,23 @@ impl Command for LastCommand {
             return Ok(0); // Exit code 0 if we successfully wrote the stream
         }
 
+        // Tests tagged with filter_tags are only counted when they fail
+        let filter_tags = load_filter_tags(self.base_path.as_deref())?;
+        let visible_run = test_run.hide_filtered_tags(&filter_tags);
+
         ui.output(&format!("Test run: {}", test_run.id))?;
         ui.output(&format!("Timestamp: {}", test_run.timestamp))?;
-        ui.output(&format!("Total tests: {}", test_run.total_tests()))?;
-        ui.output(&format!("Passed: {}", test_run.count_successes()))?;
-        ui.output(&format!("Failed: {}", test_run.count_failures()))?;
+        ui.output(&format!("Total tests: {}", visible_run.total_tests()))?;
+        ui.output(&format!("Passed: {}", visible_run.count_successes()))?;
+        ui.output(&format!("Failed: {}", visible_run.count_failures()))?;
 
         // Show total duration if available
         if let Some(duration) = test_run.total_duration() {
             ui.output(&format!("Total time: {:.3}s", duration.as_secs_f64()))?;
         }
 
+        show_run_metadata(ui, &repo.get_run_metadata(&test_run.id)?)?;
+
         // Show test output based on show_output setting
         if self.show_output && test_run.count_failures() > 0 {
             // Replay the raw subunit stream with output filter to show failures
@@ -126,7 +201,7 @@ impl Command for LastCommand {
     }
 
     fn help(&self) -> &str {
-        "Show the results from the last test run"
+        "Show the results from the last test run, or another run"
     }
 }
 
@@ -219,4 +294,103 @@ mod tests {
         // The filtering only shows output for tests that have file attachments.
         assert_eq!(ui.bytes_output.len(), 0);
     }
+
+    #[test]
+    fn test_last_command_hides_filtered_tags() {
+        let temp = TempDir::new().unwrap();
+        std::fs::write(
+            temp.path().join(".testr.conf"),
+            "[DEFAULT]\ntest_command=true\nfilter_tags=zope:layer\n",
+        )
+        .unwrap();
+
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+
+        let mut test_run = TestRun::new("0".to_string());
+        test_run.add_result(TestResult::success("layer.setUp").with_tag("zope:layer"));
+        test_run.add_result(TestResult::success("test1"));
+        repo.insert_test_run(test_run).unwrap();
+
+        let mut ui = TestUI::new();
+        let cmd = LastCommand::new(Some(temp.path().to_string_lossy().to_string()));
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert!(ui.output.iter().any(|s| s == "Total tests: 1"));
+        assert!(ui.output.iter().any(|s| s == "Passed: 1"));
+    }
+
+    #[test]
+    fn test_last_command_shows_run_metadata() {
+        let temp = TempDir::new().unwrap();
+
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+        let mut test_run = TestRun::new("0".to_string());
+        test_run.add_result(TestResult::success("test1"));
+        let run_id = repo.insert_test_run(test_run).unwrap();
+        repo.set_run_metadata(
+            &run_id,
+            &RunMetadata {
+                start_time: chrono::DateTime::from_timestamp(1000000000, 0),
+                end_time: chrono::DateTime::from_timestamp(1000000012, 500_000_000),
+                command: Some("python -m test".to_string()),
+                concurrency: Some(2),
+                exit_codes: vec![0, 1],
+                interrupted: Some(true),
+                git_commit: Some("0123456789abcdef".to_string()),
+                git_dirty: Some(true),
+                hostname: Some("build-1".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut ui = TestUI::new();
+        let cmd = LastCommand::new(Some(temp.path().to_string_lossy().to_string()));
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(
+            ui.output,
+            vec![
+                "Test run: 0",
+                "Timestamp: 2001-09-09 01:46:40 UTC",
+                "Total tests: 1",
+                "Passed: 1",
+                "Failed: 0",
+                "Wall time: 12.500s",
+                "Command: python -m test",
+                "Concurrency: 2",
+                "Exit codes: 0, 1",
+                "Interrupted: yes",
+                "Revision: 0123456789abcdef (dirty)",
+                "Host: build-1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_last_command_for_run() {
+        let temp = TempDir::new().unwrap();
+
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+        let mut test_run = TestRun::new("0".to_string());
+        test_run.add_result(TestResult::failure("test1", "boom"));
+        repo.insert_test_run(test_run).unwrap();
+        let mut test_run = TestRun::new("1".to_string());
+        test_run.add_result(TestResult::success("test1"));
+        repo.insert_test_run(test_run).unwrap();
+
+        let base_path = Some(temp.path().to_string_lossy().to_string());
+        for spec in ["0", "-2", "last~1"] {
+            let mut ui = TestUI::new();
+            let cmd = LastCommand::with_output_control(base_path.clone(), false)
+                .for_run(Some(spec.to_string()));
+            assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+            assert_eq!(ui.output[0], "Test run: 0");
+        }
+
+        let mut ui = TestUI::new();
+        let cmd = LastCommand::new(base_path.clone()).for_run(Some("0..1".to_string()));
+        assert!(cmd.execute(&mut ui).is_err());
+    }
 }
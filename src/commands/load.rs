@@ -1,11 +1,13 @@
 //! Load test results from a subunit stream into the repository
 
-use crate::commands::utils::{init_repository, open_repository};
+use crate::commands::utils::{init_repository_with, open_repository_with};
 use crate::commands::Command;
 use crate::error::Result;
+use crate::repository::RepositoryFactory;
 use crate::subunit_stream;
 use crate::ui::UI;
 use std::io::{self, Read};
+use std::sync::Arc;
 
 /// Command to load test results from a subunit stream into the repository.
 ///
@@ -13,6 +15,7 @@ use std::io::{self, Read};
 /// and updates the repository's failing tests and test times.
 pub struct LoadCommand {
     base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
     input: Option<Box<dyn Read>>,
     force_init: bool,
     partial: bool,
@@ -26,6 +29,7 @@ impl LoadCommand {
     pub fn new(base_path: Option<String>) -> Self {
         LoadCommand {
             base_path,
+            repository_factory: None,
             input: None,
             force_init: false,
             partial: false,
@@ -39,6 +43,7 @@ impl LoadCommand {
     pub fn with_force_init(base_path: Option<String>) -> Self {
         LoadCommand {
             base_path,
+            repository_factory: None,
             input: None,
             force_init: true,
             partial: false,
@@ -54,6 +59,7 @@ impl LoadCommand {
     pub fn with_partial(base_path: Option<String>, partial: bool, force_init: bool) -> Self {
         LoadCommand {
             base_path,
+            repository_factory: None,
             input: None,
             force_init,
             partial,
@@ -68,11 +74,25 @@ impl LoadCommand {
     pub fn with_input(base_path: Option<String>, input: Box<dyn Read>) -> Self {
         LoadCommand {
             base_path,
+            repository_factory: None,
             input: Some(input),
             force_init: false,
             partial: false,
         }
     }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
 }
 
 impl Command for LoadCommand {
@@ -80,10 +100,15 @@ impl Command for LoadCommand {
         // Open repository
         let mut repo = if self.force_init {
             // Try to open, if it fails, initialize
-            open_repository(self.base_path.as_deref())
-                .or_else(|_| init_repository(self.base_path.as_deref()))?
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())
+                .or_else(|_| {
+                    init_repository_with(
+                        self.repository_factory.as_ref(),
+                        self.base_path.as_deref(),
+                    )
+                })?
         } else {
-            open_repository(self.base_path.as_deref())?
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?
         };
 
         // Begin the test run and get a writer for streaming raw bytes
@@ -110,7 +135,8 @@ impl Command for LoadCommand {
         raw_writer
             .write_all(&all_data)
             .map_err(crate::error::Error::Io)?;
-        raw_writer.flush().map_err(crate::error::Error::Io)?;
+        // The run is only stored once its writer is committed
+        raw_writer.commit()?;
 
         // Parse the subunit stream
         let test_run = subunit_stream::parse_stream(&all_data[..], run_id.clone())?;
@@ -125,12 +151,18 @@ impl Command for LoadCommand {
         // Update test times
         crate::commands::utils::update_test_times_from_run(&mut repo, &test_run)?;
 
+        // Tests tagged with filter_tags are only reported when they fail
+        let filter_tags = crate::commands::utils::load_filter_tags(self.base_path.as_deref())?;
+        let visible_run = test_run.hide_filtered_tags(&filter_tags);
+
         ui.output(&format!(
             "Loaded {} test(s) as run {}",
-            test_run.total_tests(),
+            visible_run.total_tests(),
             run_id
         ))?;
 
+        crate::commands::utils::auto_prune(ui, &mut repo)?;
+
         if test_run.count_failures() > 0 {
             ui.output(&format!("{} test(s) failed", test_run.count_failures()))?;
             Ok(1)
@@ -6,29 +6,47 @@ use crate::error::Result;
 use crate::ui::UI;
 
 pub mod analyze_isolation;
+pub mod bookmark;
+pub mod check;
+pub mod diff;
 pub mod failing;
+pub mod flaky;
 pub mod help;
+pub mod history;
 pub mod init;
 pub mod last;
 pub mod list_tests;
 pub mod load;
+pub mod prune;
 pub mod quickstart;
+pub mod rebuild;
+pub mod repo_config;
 pub mod run;
 pub mod slowest;
 pub mod stats;
+pub mod times;
 mod utils;
 
 pub use analyze_isolation::AnalyzeIsolationCommand;
+pub use bookmark::BookmarkCommand;
+pub use check::CheckCommand;
+pub use diff::DiffCommand;
 pub use failing::FailingCommand;
+pub use flaky::FlakyCommand;
 pub use help::HelpCommand;
+pub use history::HistoryCommand;
 pub use init::InitCommand;
 pub use last::LastCommand;
 pub use list_tests::ListTestsCommand;
 pub use load::LoadCommand;
+pub use prune::PruneCommand;
 pub use quickstart::QuickstartCommand;
-pub use run::RunCommand;
+pub use rebuild::RebuildCommand;
+pub use repo_config::RepoConfigCommand;
+pub use run::{FlakyPolicy, RunCommand};
 pub use slowest::SlowestCommand;
 pub use stats::StatsCommand;
+pub use times::{TimesCommand, TimesSort};
 
 /// Trait that all commands must implement
 pub trait Command {
@@ -0,0 +1,218 @@
+//! Delete old test runs from the repository
+
+use crate::commands::utils::{open_repository_with, prune_runs};
+use crate::commands::Command;
+use crate::error::{Error, Result};
+use crate::repository::RepositoryFactory;
+use crate::ui::UI;
+use std::sync::Arc;
+
+/// Command to delete old runs according to a retention policy.
+///
+/// Limits given on the command line take precedence over the `keep_runs`
+/// and `keep_days` settings in repo.conf. The failing tests and the times
+/// database are kept, as is the most recent run.
+pub struct PruneCommand {
+    base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    keep_runs: Option<usize>,
+    keep_days: Option<u64>,
+    dry_run: bool,
+}
+
+impl PruneCommand {
+    /// Creates a prune command that uses the limits from repo.conf.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    pub fn new(base_path: Option<String>) -> Self {
+        PruneCommand {
+            base_path,
+            repository_factory: None,
+            keep_runs: None,
+            keep_days: None,
+            dry_run: false,
+        }
+    }
+
+    /// Creates a prune command with explicit limits.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    /// * `keep_runs` - Number of most recent runs to keep
+    /// * `keep_days` - Maximum age in days of runs to keep
+    /// * `dry_run` - Only report which runs would be deleted
+    pub fn with_options(
+        base_path: Option<String>,
+        keep_runs: Option<usize>,
+        keep_days: Option<u64>,
+        dry_run: bool,
+    ) -> Self {
+        PruneCommand {
+            base_path,
+            repository_factory: None,
+            keep_runs,
+            keep_days,
+            dry_run,
+        }
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+impl Command for PruneCommand {
+    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+        let mut repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+
+        let (keep_runs, keep_days) = if self.keep_runs.is_some() || self.keep_days.is_some() {
+            (self.keep_runs, self.keep_days)
+        } else {
+            let config = repo.get_config()?;
+            (config.keep_runs, config.keep_days)
+        };
+
+        if keep_runs.is_none() && keep_days.is_none() {
+            return Err(Error::Config(
+                "No retention limit given: use --keep or --max-age, or set keep_runs or \
+                 keep_days with 'testr repo-config'"
+                    .to_string(),
+            ));
+        }
+
+        let pruned = prune_runs(&mut repo, keep_runs, keep_days, self.dry_run)?;
+
+        let verb = if self.dry_run {
+            "Would delete"
+        } else {
+            "Deleted"
+        };
+        for run_id in &pruned {
+            ui.output(&format!("{} run {}", verb, run_id))?;
+        }
+        let mut remaining = repo.count()?;
+        if self.dry_run {
+            remaining -= pruned.len();
+        }
+        ui.output(&format!(
+            "{} {} run(s), {} remaining",
+            verb,
+            pruned.len(),
+            remaining
+        ))?;
+
+        Ok(0)
+    }
+
+    fn name(&self) -> &str {
+        "prune"
+    }
+
+    fn help(&self) -> &str {
+        "Delete old test runs from the repository"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{RunMetadata, TestRun};
+    use crate::ui::test_ui::TestUI;
+    use tempfile::TempDir;
+
+    fn setup_repo(runs: usize) -> TempDir {
+        let temp = TempDir::new().unwrap();
+        let mut repo =
+            super::super::utils::init_repository(Some(&temp.path().to_string_lossy())).unwrap();
+        for i in 0..runs {
+            repo.insert_test_run(TestRun::new(i.to_string())).unwrap();
+        }
+        temp
+    }
+
+    #[test]
+    fn test_prune_keep_runs() {
+        let temp = setup_repo(4);
+        let base = Some(temp.path().to_string_lossy().to_string());
+
+        let mut ui = TestUI::new();
+        let cmd = PruneCommand::with_options(base.clone(), Some(2), None, false);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(
+            ui.output,
+            vec![
+                "Deleted run 0",
+                "Deleted run 1",
+                "Deleted 2 run(s), 2 remaining"
+            ]
+        );
+
+        let repo = super::super::utils::open_repository(base.as_deref()).unwrap();
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["2", "3"]);
+    }
+
+    #[test]
+    fn test_prune_dry_run() {
+        let temp = setup_repo(3);
+        let base = Some(temp.path().to_string_lossy().to_string());
+
+        let mut ui = TestUI::new();
+        let cmd = PruneCommand::with_options(base.clone(), Some(1), None, true);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(
+            ui.output.last().unwrap(),
+            "Would delete 2 run(s), 1 remaining"
+        );
+
+        let repo = super::super::utils::open_repository(base.as_deref()).unwrap();
+        assert_eq!(repo.count().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_prune_uses_repo_config() {
+        let temp = setup_repo(3);
+        let base = Some(temp.path().to_string_lossy().to_string());
+
+        let mut ui = TestUI::new();
+        let cmd = PruneCommand::new(base.clone());
+        assert!(cmd.execute(&mut ui).is_err());
+
+        let mut repo = super::super::utils::open_repository(base.as_deref()).unwrap();
+        let mut config = repo.get_config().unwrap();
+        config.keep_runs = Some(2);
+        repo.set_config(&config).unwrap();
+
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn test_prune_max_age_uses_start_time() {
+        let temp = setup_repo(3);
+        let base = Some(temp.path().to_string_lossy().to_string());
+
+        // Run 0 was only just stored, but started long ago
+        let mut repo = super::super::utils::open_repository(base.as_deref()).unwrap();
+        let metadata = RunMetadata {
+            start_time: Some(chrono::Utc::now() - chrono::Duration::days(30)),
+            ..Default::default()
+        };
+        repo.set_run_metadata("0", &metadata).unwrap();
+
+        let mut ui = TestUI::new();
+        let cmd = PruneCommand::with_options(base.clone(), None, Some(7), false);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["1", "2"]);
+    }
+}
@@ -0,0 +1,163 @@
+//! Rebuild the failing tests and times database from stored runs
+
+use crate::commands::utils::open_repository_with;
+use crate::commands::Command;
+use crate::error::{Error, Result};
+use crate::repository::RepositoryFactory;
+use crate::ui::UI;
+use std::sync::Arc;
+
+/// Command to recompute the failing tests and the times database by replaying
+/// stored runs in order.
+///
+/// Full runs replace the failing tests and partial runs update them, as they
+/// did when they were first stored. The replay can be limited to a range of
+/// runs, in which case runs outside it have no effect.
+pub struct RebuildCommand {
+    base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    from: Option<u64>,
+    to: Option<u64>,
+}
+
+impl RebuildCommand {
+    /// Creates a rebuild command that replays every stored run.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    pub fn new(base_path: Option<String>) -> Self {
+        RebuildCommand {
+            base_path,
+            repository_factory: None,
+            from: None,
+            to: None,
+        }
+    }
+
+    /// Creates a rebuild command that replays a range of runs.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    /// * `from` - First run to replay
+    /// * `to` - Last run to replay
+    pub fn with_range(base_path: Option<String>, from: Option<u64>, to: Option<u64>) -> Self {
+        RebuildCommand {
+            base_path,
+            repository_factory: None,
+            from,
+            to,
+        }
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+impl Command for RebuildCommand {
+    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return Err(Error::Config(format!(
+                    "Invalid run range: {} is after {}",
+                    from, to
+                )));
+            }
+        }
+
+        let mut repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+
+        let range = self.from.unwrap_or(0)..=self.to.unwrap_or(u64::MAX);
+        let run_ids: Vec<String> = repo
+            .list_run_ids()?
+            .into_iter()
+            .filter(|run_id| run_id.parse::<u64>().is_ok_and(|n| range.contains(&n)))
+            .collect();
+
+        if run_ids.is_empty() {
+            ui.output("No runs to replay")?;
+            return Ok(1);
+        }
+
+        repo.rebuild_failing_and_times(&run_ids)?;
+
+        ui.output(&format!(
+            "Replayed {} run(s): {} failing test(s), {} timed test(s)",
+            run_ids.len(),
+            repo.get_failing_tests()?.len(),
+            repo.get_test_times()?.len()
+        ))?;
+
+        Ok(0)
+    }
+
+    fn name(&self) -> &str {
+        "rebuild"
+    }
+
+    fn help(&self) -> &str {
+        "Rebuild the failing tests and times from stored runs"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{TestResult, TestRun};
+    use crate::ui::test_ui::TestUI;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rebuild_command() {
+        let temp = TempDir::new().unwrap();
+        let base = Some(temp.path().to_string_lossy().to_string());
+        let mut repo = super::super::utils::init_repository(base.as_deref()).unwrap();
+
+        let mut run = TestRun::new("0".to_string());
+        run.add_result(
+            TestResult::failure("test1", "Failed").with_duration(Duration::from_secs(1)),
+        );
+        run.add_result(TestResult::failure("test2", "Failed"));
+        repo.insert_test_run(run).unwrap();
+        let mut run = TestRun::new("1".to_string());
+        run.add_result(TestResult::success("test1"));
+        repo.insert_test_run_partial(run, true).unwrap();
+
+        let mut ui = TestUI::new();
+        let cmd = RebuildCommand::with_range(base.clone(), None, Some(0));
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(
+            ui.output,
+            vec!["Replayed 1 run(s): 2 failing test(s), 1 timed test(s)"]
+        );
+
+        let mut ui = TestUI::new();
+        assert_eq!(
+            RebuildCommand::new(base.clone()).execute(&mut ui).unwrap(),
+            0
+        );
+        assert_eq!(
+            ui.output,
+            vec!["Replayed 2 run(s): 1 failing test(s), 1 timed test(s)"]
+        );
+
+        let mut ui = TestUI::new();
+        let cmd = RebuildCommand::with_range(base.clone(), Some(5), None);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+        assert_eq!(ui.output, vec!["No runs to replay"]);
+
+        let cmd = RebuildCommand::with_range(base, Some(1), Some(0));
+        assert!(cmd.execute(&mut ui).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Show or change repository settings
+
+use crate::commands::utils::open_repository_with;
+use crate::commands::Command;
+use crate::error::Result;
+use crate::repository::config::SETTINGS;
+use crate::repository::RepositoryFactory;
+use crate::ui::UI;
+use std::sync::Arc;
+
+/// What the repo-config command should do
+enum Action {
+    Show,
+    Get(String),
+    Set(String, Option<String>),
+}
+
+/// Command to show, get or set settings stored in the repository's repo.conf.
+///
+/// These settings apply to this checkout only, unlike .testr.conf which is
+/// usually shared by everyone working on the project.
+pub struct RepoConfigCommand {
+    base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    action: Action,
+}
+
+impl RepoConfigCommand {
+    /// Creates a command that shows every repository setting.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    pub fn new(base_path: Option<String>) -> Self {
+        RepoConfigCommand {
+            base_path,
+            repository_factory: None,
+            action: Action::Show,
+        }
+    }
+
+    /// Creates a command that prints the value of one setting.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    /// * `key` - Name of the setting
+    pub fn with_get(base_path: Option<String>, key: String) -> Self {
+        RepoConfigCommand {
+            base_path,
+            repository_factory: None,
+            action: Action::Get(key),
+        }
+    }
+
+    /// Creates a command that sets (or, given `None`, unsets) one setting.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    /// * `key` - Name of the setting
+    /// * `value` - New value for the setting
+    pub fn with_set(base_path: Option<String>, key: String, value: Option<String>) -> Self {
+        RepoConfigCommand {
+            base_path,
+            repository_factory: None,
+            action: Action::Set(key, value),
+        }
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+impl Command for RepoConfigCommand {
+    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+        let mut repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+        let mut config = repo.get_config()?;
+
+        match self.action {
+            Action::Show => {
+                for (key, description) in SETTINGS {
+                    let value = config.get(key)?.unwrap_or_default();
+                    ui.output(&format!("{}={}", key, value))?;
+                    ui.output(&format!("  {}", description))?;
+                }
+                Ok(0)
+            }
+            Action::Get(ref key) => match config.get(key)? {
+                Some(value) => {
+                    ui.output(&value)?;
+                    Ok(0)
+                }
+                // Like `git config`, an unset value is not an error but
+                // is reported through the exit code
+                None => Ok(1),
+            },
+            Action::Set(ref key, ref value) => {
+                config.set(key, value.as_deref())?;
+                repo.set_config(&config)?;
+                Ok(0)
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "repo-config"
+    }
+
+    fn help(&self) -> &str {
+        "Show or change repository settings"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::test_ui::TestUI;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_repo_config_show_get_set() {
+        let temp = TempDir::new().unwrap();
+        let base = Some(temp.path().to_string_lossy().to_string());
+        super::super::utils::init_repository(base.as_deref()).unwrap();
+
+        let mut ui = TestUI::new();
+        let cmd = RepoConfigCommand::with_get(base.clone(), "keep_runs".to_string());
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+        assert!(ui.output.is_empty());
+
+        let cmd = RepoConfigCommand::with_set(
+            base.clone(),
+            "keep_runs".to_string(),
+            Some("25".to_string()),
+        );
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+
+        let cmd = RepoConfigCommand::with_get(base.clone(), "keep_runs".to_string());
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(ui.output, vec!["25"]);
+
+        let mut ui = TestUI::new();
+        let cmd = RepoConfigCommand::new(base.clone());
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert!(ui.output.contains(&"keep_runs=25".to_string()));
+        assert!(ui.output.contains(&"output=".to_string()));
+
+        // Unknown settings and bad values are rejected
+        let cmd = RepoConfigCommand::with_set(base.clone(), "bogus".to_string(), None);
+        assert!(cmd.execute(&mut ui).is_err());
+        let cmd = RepoConfigCommand::with_set(base, "output".to_string(), Some("loud".to_string()));
+        assert!(cmd.execute(&mut ui).is_err());
+    }
+}
@@ -1,13 +1,23 @@
 //! Run tests and load results into the repository
 
-use crate::commands::utils::{init_repository, open_repository};
+use crate::commands::utils::{init_repository_with, open_repository_with};
 use crate::commands::Command;
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::repository::flaky::{find_flaky_tests, retry_tag};
+use crate::repository::{
+    RepositoryFactory, RunMetadata, TestId, TestResult, TestRun, TestSelector, TestStatus,
+};
 use crate::subunit_stream;
+use crate::test_runner::{
+    CrashedTest, InterruptHandler, StopHandle, TestTimeout, TimedOutTest, Watchdog,
+};
 use crate::testcommand::TestCommand;
 use crate::ui::UI;
 use indicatif::{ProgressBar, ProgressStyle};
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Helper to truncate test name to fit in available space
 fn truncate_test_name(test_id: &str, max_len: usize, fail_msg_len: usize) -> String {
@@ -254,12 +264,185 @@ mod helper_tests {
     }
 }
 
+/// Whether a test that only passed when retried counts as passing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlakyPolicy {
+    /// The pass stands: the test neither fails the run nor stays failing
+    #[default]
+    Pass,
+    /// The test still fails the run and stays among the failing tests
+    Fail,
+}
+
+impl FromStr for FlakyPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pass" => Ok(FlakyPolicy::Pass),
+            "fail" => Ok(FlakyPolicy::Fail),
+            _ => Err(Error::Config(format!(
+                "Unknown flaky policy '{}' (expected 'pass' or 'fail')",
+                s
+            ))),
+        }
+    }
+}
+
+/// What happened when the failing tests of a run were retried
+#[derive(Debug, Default)]
+struct Retries {
+    /// Number of retry attempts made
+    attempts: usize,
+    /// The failed first attempts of the tests that passed when retried
+    flaky: Vec<TestResult>,
+    /// Retried tests that never finished
+    unfinished: Unfinished,
+}
+
+/// Tests that test processes started but never finished
+#[derive(Debug, Default)]
+struct Unfinished {
+    /// Tests the watchdog terminated their process for
+    timed_out: Vec<TimedOutTest>,
+    /// Tests that were running when their process exited by itself
+    crashed: Vec<CrashedTest>,
+}
+
+impl Unfinished {
+    /// Take what a worker's finished process left unfinished from the watchdog
+    fn take(watchdog: &Watchdog, worker: usize, status: std::process::ExitStatus) -> Self {
+        let exit_code = crate::test_runner::exit_code(status);
+        Unfinished {
+            timed_out: watchdog.take_timed_out(worker),
+            crashed: watchdog
+                .take_unfinished(worker)
+                .into_iter()
+                .map(|test_id| CrashedTest { test_id, exit_code })
+                .collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.timed_out.is_empty() && self.crashed.is_empty()
+    }
+
+    fn contains(&self, test_id: &TestId) -> bool {
+        self.timed_out.iter().any(|test| &test.test_id == test_id)
+            || self.crashed.iter().any(|test| &test.test_id == test_id)
+    }
+
+    fn extend(&mut self, other: Unfinished) {
+        self.timed_out.extend(other.timed_out);
+        self.crashed.extend(other.crashed);
+    }
+
+    /// Write an error event for each test
+    ///
+    /// Tests that timed out get how long they ran and their limit attached,
+    /// as "timed-out", and tests that crashed the exit status of their
+    /// process, as "exit-status". Both get the end of its stderr attached, as
+    /// "stderr".
+    fn write(&self, stderr_tail: &[u8], writer: &mut dyn std::io::Write) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let timed_out = self
+            .timed_out
+            .iter()
+            .map(|test| (&test.test_id, "timed-out", test.timeout(), test.to_result()));
+        let crashed = self.crashed.iter().map(|test| {
+            (
+                &test.test_id,
+                "exit-status",
+                test.exit_status(),
+                test.to_result(),
+            )
+        });
+
+        let mut run = TestRun::new(String::new());
+        for (test_id, name, reason, result) in timed_out.chain(crashed) {
+            subunit_stream::write_attachment(&mut *writer, test_id, name, reason.as_bytes())?;
+            if !stderr_tail.is_empty() {
+                subunit_stream::write_attachment(&mut *writer, test_id, "stderr", stderr_tail)?;
+            }
+            run.add_result(result);
+        }
+        subunit_stream::write_stream(&run, writer)
+    }
+
+    /// Record the tests as errors in `run`, appending the errors to `stream`
+    fn record(
+        &self,
+        stderr_tail: &[u8],
+        run: &mut TestRun,
+        stream: &mut dyn std::io::Write,
+    ) -> Result<()> {
+        self.write(stderr_tail, stream)?;
+        for test in &self.timed_out {
+            run.add_result(test.to_result());
+        }
+        for test in &self.crashed {
+            run.add_result(test.to_result());
+        }
+        Ok(())
+    }
+}
+
+/// Add the results parsed from a watched process's output to `run`, with
+/// the tests it left unfinished recorded as errors
+fn add_results(run: &mut TestRun, parsed: TestRun, unfinished: &Unfinished) {
+    for mut result in parsed.results.into_values() {
+        if unfinished.contains(&result.test_id) {
+            result.status = TestStatus::Error;
+        }
+        run.add_result(result);
+    }
+}
+
+/// Exit code of a run stopped by a signal, to which the signal number is added
+const INTERRUPTED_EXIT_CODE_BASE: i32 = 128;
+
+/// Why `--until-failure` stopped looping, given the exit code of an iteration
+fn iteration_stopped(exit_code: i32, iteration: usize) -> String {
+    if exit_code > INTERRUPTED_EXIT_CODE_BASE {
+        format!("\nInterrupted on iteration {}", iteration)
+    } else {
+        format!("\nTests failed on iteration {}", iteration)
+    }
+}
+
+/// How often a parallel run checks whether a worker's process is done
+const WORKER_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A test process running a batch of tests for a worker in a parallel run
+struct WorkerProcess {
+    /// The batch of tests it was given
+    tests: Vec<TestId>,
+    /// Keeps the `$IDFILE` around until the process is done
+    _id_file: Option<tempfile::NamedTempFile>,
+    parse_thread: std::thread::JoinHandle<Result<TestRun>>,
+    tee_thread: std::thread::JoinHandle<std::io::Result<std::fs::File>>,
+    stderr_thread: std::thread::JoinHandle<std::io::Result<Vec<u8>>>,
+    /// Reads back the raw output the tee thread buffers
+    reader: std::fs::File,
+}
+
+/// Number of the scheduled tests that have no result in `run`
+fn count_not_run(run: &TestRun, scheduled: &[TestId]) -> usize {
+    scheduled
+        .iter()
+        .filter(|test_id| !run.results.contains_key(*test_id))
+        .count()
+}
+
 /// Command to run tests and load results into the repository.
 ///
 /// Executes tests using the configured test command, displays progress,
 /// and stores the results in the repository.
 pub struct RunCommand {
     base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
     failing_only: bool,
     force_init: bool,
     partial: bool,
@@ -271,6 +454,14 @@ pub struct RunCommand {
     all_output: bool,
     test_filters: Option<Vec<String>>,
     test_args: Option<Vec<String>>,
+    retry: usize,
+    flaky_policy: FlakyPolicy,
+    max_failures: Option<usize>,
+    test_timeout: Option<TestTimeout>,
+    run_timeout: Option<Duration>,
+    reschedule_timeouts: bool,
+    dynamic: bool,
+    batch_size: Option<usize>,
 }
 
 impl RunCommand {
@@ -281,6 +472,7 @@ impl RunCommand {
     pub fn new(base_path: Option<String>) -> Self {
         RunCommand {
             base_path,
+            repository_factory: None,
             failing_only: false,
             force_init: false,
             partial: false,
@@ -292,6 +484,14 @@ impl RunCommand {
             all_output: false,
             test_filters: None,
             test_args: None,
+            retry: 0,
+            flaky_policy: FlakyPolicy::Pass,
+            max_failures: None,
+            test_timeout: None,
+            run_timeout: None,
+            reschedule_timeouts: false,
+            dynamic: false,
+            batch_size: None,
         }
     }
 
@@ -301,18 +501,9 @@ impl RunCommand {
     /// * `base_path` - Optional base directory path for the repository
     pub fn with_failing_only(base_path: Option<String>) -> Self {
         RunCommand {
-            base_path,
             failing_only: true,
-            force_init: false,
             partial: true, // --failing implies partial mode
-            load_list: None,
-            concurrency: None,
-            until_failure: false,
-            isolated: false,
-            subunit: false,
-            all_output: false,
-            test_filters: None,
-            test_args: None,
+            ..Self::new(base_path)
         }
     }
 
@@ -323,18 +514,10 @@ impl RunCommand {
     /// * `failing_only` - Whether to only run previously failing tests
     pub fn with_force_init(base_path: Option<String>, failing_only: bool) -> Self {
         RunCommand {
-            base_path,
             failing_only,
             force_init: true,
             partial: failing_only, // --failing implies partial mode
-            load_list: None,
-            concurrency: None,
-            until_failure: false,
-            isolated: false,
-            subunit: false,
-            all_output: false,
-            test_filters: None,
-            test_args: None,
+            ..Self::new(base_path)
         }
     }
 
@@ -352,18 +535,10 @@ impl RunCommand {
         force_init: bool,
     ) -> Self {
         RunCommand {
-            base_path,
             failing_only,
             force_init,
             partial,
-            load_list: None,
-            concurrency: None,
-            until_failure: false,
-            isolated: false,
-            subunit: false,
-            all_output: false,
-            test_filters: None,
-            test_args: None,
+            ..Self::new(base_path)
         }
     }
 
@@ -398,7 +573,6 @@ impl RunCommand {
         test_args: Option<Vec<String>>,
     ) -> Self {
         RunCommand {
-            base_path,
             failing_only,
             force_init,
             partial,
@@ -410,6 +584,537 @@ impl RunCommand {
             all_output,
             test_filters,
             test_args,
+            ..Self::new(base_path)
+        }
+    }
+
+    /// Only run the tests listed in a file.
+    ///
+    /// # Arguments
+    /// * `load_list` - Path to a file with one test ID per line, or `None`
+    ///   to run every test
+    pub fn with_load_list(mut self, load_list: Option<String>) -> Self {
+        self.load_list = load_list;
+        self
+    }
+
+    /// Run the tests across parallel workers.
+    ///
+    /// # Arguments
+    /// * `concurrency` - Number of workers, 0 for one per CPU, or `None` to
+    ///   use the repository or test command setting
+    pub fn with_concurrency(mut self, concurrency: Option<usize>) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Repeat the run until a test fails.
+    pub fn with_until_failure(mut self, until_failure: bool) -> Self {
+        self.until_failure = until_failure;
+        self
+    }
+
+    /// Run each test in a process of its own.
+    pub fn with_isolated(mut self, isolated: bool) -> Self {
+        self.isolated = isolated;
+        self
+    }
+
+    /// Retry failing tests after the run, up to `retry` more times each.
+    ///
+    /// # Arguments
+    /// * `retry` - Maximum number of times to re-run a failing test
+    /// * `flaky_policy` - Whether a test that passes when retried counts as passing
+    pub fn with_retry(mut self, retry: usize, flaky_policy: FlakyPolicy) -> Self {
+        self.retry = retry;
+        self.flaky_policy = flaky_policy;
+        self
+    }
+
+    /// Stop the run once `max_failures` tests have failed.
+    ///
+    /// # Arguments
+    /// * `max_failures` - Number of failures after which to terminate the
+    ///   test processes, or `None` to always run every test
+    pub fn with_max_failures(mut self, max_failures: Option<usize>) -> Self {
+        self.max_failures = max_failures;
+        self
+    }
+
+    /// Terminate tests, or the whole run, that go on for too long.
+    ///
+    /// A test that times out is recorded as an error, and the tests its
+    /// process never got to are missing from the run unless rescheduled.
+    ///
+    /// # Arguments
+    /// * `test_timeout` - Limit for each test, or `None` for no limit
+    /// * `run_timeout` - Limit for the whole run, or `None` for no limit
+    /// * `reschedule` - Whether to run the tests a timed-out process never
+    ///   got to in a new process
+    pub fn with_timeouts(
+        mut self,
+        test_timeout: Option<TestTimeout>,
+        run_timeout: Option<Duration>,
+        reschedule: bool,
+    ) -> Self {
+        self.test_timeout = test_timeout;
+        self.run_timeout = run_timeout;
+        self.reschedule_timeouts = reschedule;
+        self
+    }
+
+    /// Hand tests to parallel workers in batches as they become free,
+    /// instead of partitioning them between the workers up front.
+    ///
+    /// Each worker runs one test process per batch, on its own instance.
+    /// Tests matched into a group by `group_regex` share a batch.
+    ///
+    /// # Arguments
+    /// * `dynamic` - Whether to schedule tests dynamically
+    /// * `batch_size` - Maximum number of tests in a batch, or `None` to give
+    ///   each worker about [`crate::partition::BATCHES_PER_WORKER`] batches
+    pub fn with_dynamic_schedule(mut self, dynamic: bool, batch_size: Option<usize>) -> Self {
+        self.dynamic = dynamic;
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Start the watchdog enforcing the timeouts on a run of `tests`
+    fn start_watchdog(
+        &self,
+        repo: &dyn crate::repository::Repository,
+        tests: &[TestId],
+        stop: &StopHandle,
+    ) -> Result<Watchdog> {
+        let times = match self.test_timeout {
+            Some(TestTimeout::Scaled(_)) => repo.get_test_times_for_ids(tests)?,
+            _ => Default::default(),
+        };
+        Ok(Watchdog::start(
+            self.test_timeout,
+            times,
+            self.run_timeout,
+            stop.clone(),
+        ))
+    }
+
+    /// The `sh -c` command for a test process, with its output piped
+    ///
+    /// The process gets a process group of its own, so that stopping it, or
+    /// forwarding a signal testr received, also reaches the tests it started.
+    fn test_process(&self, cmd_str: &str, test_cmd: &TestCommand) -> std::process::Command {
+        use std::process::Stdio;
+
+        let mut command = std::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg(cmd_str)
+            .current_dir(test_cmd.base_dir())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        crate::test_runner::set_process_group(&mut command);
+        command
+    }
+
+    /// Run a test command in a new process for a worker, under the watchdog
+    ///
+    /// Returns the process's output followed by an error for each test it
+    /// left unfinished, those tests, and how the process exited.
+    fn run_watched(
+        &self,
+        test_cmd: &TestCommand,
+        watchdog: &Watchdog,
+        worker: usize,
+        cmd_str: &str,
+    ) -> Result<(Vec<u8>, Unfinished, std::process::ExitStatus)> {
+        let mut child = self.test_process(cmd_str, test_cmd).spawn().map_err(|e| {
+            Error::CommandExecution(format!("Failed to execute test command: {}", e))
+        })?;
+        watchdog.watch(worker, child.id());
+
+        let stdout = child.stdout.take().expect("stdout was piped");
+        let stderr = child.stderr.take().expect("stderr was piped");
+        let mut children = ProcessReapGuard::new(watchdog.stop_handle());
+        children.insert(worker, child);
+        let (tx, rx) = std::sync::mpsc::sync_channel(100);
+        let tee_thread = crate::test_runner::spawn_stdout_tee(stdout, Vec::new(), tx);
+        let stderr_thread =
+            crate::test_runner::spawn_stderr_forwarder(stderr, ProgressBar::hidden());
+
+        // Parse as it arrives so that the watchdog knows which test is running
+        subunit_stream::parse_stream_with_progress(
+            crate::test_runner::ChannelReader::new(rx),
+            String::new(),
+            |test_id, status| watchdog.progress(worker, test_id, status),
+            |_| {},
+            subunit_stream::OutputFilter::FailuresOnly,
+        )?;
+
+        let status = children.wait(worker).map_err(|e| {
+            Error::CommandExecution(format!("Failed to wait for test command: {}", e))
+        })?;
+        watchdog.unwatch(worker);
+        let mut output = tee_thread
+            .join()
+            .map_err(|_| Error::CommandExecution("Tee thread panicked".to_string()))??;
+        let stderr_tail = stderr_thread
+            .join()
+            .map_err(|_| Error::CommandExecution("Stderr thread panicked".to_string()))??;
+
+        let unfinished = Unfinished::take(watchdog, worker, status);
+        unfinished.write(&stderr_tail, &mut output)?;
+        Ok((output, unfinished, status))
+    }
+
+    /// Run the scheduled tests a worker's process never got to in a new
+    /// process, if it was terminated for a test that timed out
+    ///
+    /// Only done with `--reschedule-timeouts`, and repeated until no test
+    /// times out or the run is stopped. `unfinished` is what the first
+    /// process left unfinished, already recorded. Everything is appended to
+    /// `stream` and `run`. Returns every test left unfinished.
+    #[allow(clippy::too_many_arguments)]
+    fn reschedule_timeouts(
+        &self,
+        test_cmd: &TestCommand,
+        watchdog: &Watchdog,
+        stop: &StopHandle,
+        worker: usize,
+        instance_id: Option<&str>,
+        scheduled: &[TestId],
+        mut unfinished: Unfinished,
+        run: &mut TestRun,
+        stream: &mut dyn std::io::Write,
+        metadata: &mut RunMetadata,
+    ) -> Result<Unfinished> {
+        let mut timed_out = !unfinished.timed_out.is_empty();
+        while timed_out && self.reschedule_timeouts && !stop.is_stopped() {
+            let remaining: Vec<TestId> = scheduled
+                .iter()
+                .filter(|test_id| !run.results.contains_key(*test_id))
+                .cloned()
+                .collect();
+            if remaining.is_empty() {
+                break;
+            }
+
+            let (cmd_str, _temp_file) = test_cmd.build_command_full(
+                Some(&remaining),
+                false,
+                instance_id,
+                self.test_args.as_deref(),
+            )?;
+            let (output, more, status) = self.run_watched(test_cmd, watchdog, worker, &cmd_str)?;
+            metadata
+                .exit_codes
+                .push(crate::test_runner::exit_code(status));
+            stream.write_all(&output)?;
+            let rerun = subunit_stream::parse_stream(&output[..], run.id.clone())?;
+            add_results(run, rerun, &more);
+
+            timed_out = !more.timed_out.is_empty();
+            unfinished.extend(more);
+        }
+        Ok(unfinished)
+    }
+
+    /// Report the tests that never finished, and why a run ended before
+    /// running every test if it did
+    fn report_incomplete(
+        &self,
+        ui: &mut dyn UI,
+        run: &TestRun,
+        watchdog: &Watchdog,
+        stop: &StopHandle,
+        unfinished: &Unfinished,
+        not_run: usize,
+    ) -> Result<()> {
+        if !unfinished.timed_out.is_empty() {
+            ui.output(&format!(
+                "{} test(s) timed out:",
+                unfinished.timed_out.len()
+            ))?;
+            for test in &unfinished.timed_out {
+                ui.output(&format!(
+                    "  {} ({:.1}s, limit {:.1}s)",
+                    test.test_id,
+                    test.elapsed.as_secs_f64(),
+                    test.limit.as_secs_f64()
+                ))?;
+            }
+        }
+        if !unfinished.crashed.is_empty() {
+            ui.output(&format!("{} test(s) crashed:", unfinished.crashed.len()))?;
+            for test in &unfinished.crashed {
+                ui.output(&format!("  {} ({})", test.test_id, test.exit_status()))?;
+            }
+        }
+
+        let reason = if watchdog.run_timed_out() {
+            let limit = self.run_timeout.unwrap_or_default();
+            format!("Run timed out after {:.1}s; ", limit.as_secs_f64())
+        } else if stop.interrupted().is_some() {
+            "Interrupted; ".to_string()
+        } else if stop.is_stopped() {
+            format!("Stopped after {} failure(s); ", run.count_failures())
+        } else if not_run > 0 {
+            String::new()
+        } else {
+            return Ok(());
+        };
+        ui.output(&format!("{}{} test(s) were not run", reason, not_run))
+    }
+
+    /// Choose which test output to show: `--all-output` wins, then repo.conf
+    fn output_filter(
+        &self,
+        repo: &dyn crate::repository::Repository,
+    ) -> Result<subunit_stream::OutputFilter> {
+        if self.all_output {
+            return Ok(subunit_stream::OutputFilter::All);
+        }
+        Ok(repo
+            .get_config()?
+            .output
+            .unwrap_or(subunit_stream::OutputFilter::FailuresOnly))
+    }
+
+    /// Drop tests carrying a configured `filter_tags` tag from an explicit test list
+    ///
+    /// Tags are only known once a test has run, so the most recent run in the
+    /// repository is used to find them.
+    fn remove_filtered_tests(
+        &self,
+        repo: &dyn crate::repository::Repository,
+        test_cmd: &TestCommand,
+        test_ids: Vec<crate::repository::TestId>,
+    ) -> Result<Vec<crate::repository::TestId>> {
+        let filter_tags = test_cmd.get_filter_tags();
+        if filter_tags.is_empty() || repo.count()? == 0 {
+            return Ok(test_ids);
+        }
+
+        let latest = repo.get_latest_run()?;
+        let tagged = latest.tagged_test_ids(&filter_tags).into_iter().collect();
+        Ok(crate::partition::remove_filtered_tests(&test_ids, &tagged))
+    }
+
+    /// Start describing a run: when it began, its mode and the checkout it ran in
+    fn begin_metadata(&self, test_cmd: &TestCommand, concurrency: usize) -> RunMetadata {
+        let mut metadata = RunMetadata {
+            partial: Some(self.partial),
+            start_time: Some(chrono::Utc::now()),
+            concurrency: Some(concurrency),
+            ..Default::default()
+        };
+        metadata.record_environment(test_cmd.base_dir());
+        metadata
+    }
+
+    /// Store the description of a finished run alongside its results
+    fn record_metadata(
+        &self,
+        repo: &mut Box<dyn crate::repository::Repository>,
+        run_id: &str,
+        mut metadata: RunMetadata,
+    ) -> Result<()> {
+        metadata.end_time = Some(chrono::Utc::now());
+        repo.set_run_metadata(run_id, &metadata)
+    }
+
+    /// Re-run the tests that failed in `run`, up to `self.retry` times
+    ///
+    /// Each attempt runs the tests that are still failing, grouped by the
+    /// worker that ran them so that they get that worker's instance again, or
+    /// one process per test in isolated mode. Every attempt is appended to the
+    /// stored stream with its events tagged `retry-N`, and its results replace
+    /// the earlier ones in `run`.
+    #[allow(clippy::too_many_arguments)]
+    fn retry_failures(
+        &self,
+        ui: &mut dyn UI,
+        test_cmd: &TestCommand,
+        watchdog: &Watchdog,
+        run: &mut TestRun,
+        raw_writer: &mut dyn std::io::Write,
+        instance_ids: &[String],
+        isolated: bool,
+        metadata: &mut RunMetadata,
+    ) -> Result<Retries> {
+        use std::collections::{BTreeMap, HashMap};
+
+        let mut retries = Retries::default();
+        let mut first_failures: HashMap<TestId, TestResult> = HashMap::new();
+
+        for attempt in 1..=self.retry {
+            let failing: Vec<TestResult> = run
+                .results
+                .values()
+                .filter(|result| result.status.is_failure())
+                .cloned()
+                .collect();
+            if failing.is_empty() || watchdog.run_timed_out() {
+                break;
+            }
+            ui.output(&format!(
+                "Retrying {} failing test(s) (attempt {} of {})",
+                failing.len(),
+                attempt,
+                self.retry
+            ))?;
+            retries.attempts = attempt;
+
+            // Tests from the same worker are retried together, as that worker
+            let mut groups: BTreeMap<Option<usize>, Vec<TestId>> = BTreeMap::new();
+            for result in failing {
+                let worker = result
+                    .tags
+                    .iter()
+                    .find_map(|tag| tag.strip_prefix("worker-")?.parse().ok());
+                groups
+                    .entry(worker)
+                    .or_default()
+                    .push(result.test_id.clone());
+                first_failures
+                    .entry(result.test_id.clone())
+                    .or_insert(result);
+            }
+            let groups: Vec<(Option<usize>, Vec<TestId>)> = if isolated {
+                groups
+                    .into_iter()
+                    .flat_map(|(worker, ids)| ids.into_iter().map(move |id| (worker, vec![id])))
+                    .collect()
+            } else {
+                groups.into_iter().collect()
+            };
+
+            for (worker, mut test_ids) in groups {
+                test_ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+                let instance_id = worker
+                    .and_then(|worker| instance_ids.get(worker))
+                    .map(|id| id.as_str());
+                let (cmd_str, _temp_file) = test_cmd.build_command_full(
+                    Some(&test_ids),
+                    false,
+                    instance_id,
+                    self.test_args.as_deref(),
+                )?;
+                let (mut stream, unfinished, status) =
+                    self.run_watched(test_cmd, watchdog, worker.unwrap_or(0), &cmd_str)?;
+                metadata
+                    .exit_codes
+                    .push(crate::test_runner::exit_code(status));
+
+                if let Some(worker) = worker {
+                    let mut tagged = Vec::new();
+                    let worker_tag = format!("worker-{}", worker);
+                    subunit_stream::tag_stream(&stream[..], &mut tagged, &worker_tag)?;
+                    stream = tagged;
+                }
+                let mut tagged = Vec::new();
+                subunit_stream::tag_stream(&stream[..], &mut tagged, &retry_tag(attempt))?;
+                raw_writer.write_all(&tagged)?;
+
+                let attempt_run = subunit_stream::parse_stream(&tagged[..], run.id.clone())?;
+                add_results(run, attempt_run, &unfinished);
+                retries.unfinished.extend(unfinished);
+            }
+        }
+
+        retries.flaky = first_failures
+            .into_values()
+            .filter(|first| {
+                run.results.get(&first.test_id).is_some_and(|last| {
+                    matches!(
+                        last.status,
+                        TestStatus::Success | TestStatus::ExpectedFailure
+                    )
+                })
+            })
+            .collect();
+        retries
+            .flaky
+            .sort_by(|a, b| a.test_id.as_str().cmp(b.test_id.as_str()));
+        Ok(retries)
+    }
+
+    /// Report the tests that passed when retried, keeping them among the
+    /// failing tests if the flaky policy says they still fail
+    fn record_retries(
+        &self,
+        ui: &mut dyn UI,
+        repo: &mut Box<dyn crate::repository::Repository>,
+        retries: &Retries,
+    ) -> Result<()> {
+        if retries.flaky.is_empty() {
+            return Ok(());
+        }
+        if self.flaky_policy == FlakyPolicy::Fail {
+            repo.add_failing_tests(&retries.flaky)?;
+        }
+
+        ui.output(&format!(
+            "{} test(s) passed on retry (flaky):",
+            retries.flaky.len()
+        ))?;
+        for result in &retries.flaky {
+            ui.output(&format!("  {}", result.test_id))?;
+        }
+        Ok(())
+    }
+
+    /// Point out the failed tests that the stored runs show to be flaky
+    fn report_known_flaky(
+        &self,
+        ui: &mut dyn UI,
+        repo: &dyn crate::repository::Repository,
+        run: &TestRun,
+    ) -> Result<()> {
+        let failed: Vec<TestId> = run.get_failing_tests().into_iter().cloned().collect();
+        if failed.is_empty() {
+            return Ok(());
+        }
+        let flaky = find_flaky_tests(repo, &repo.list_run_ids()?, &TestSelector::AnyOf(failed))?;
+        if flaky.is_empty() {
+            return Ok(());
+        }
+
+        ui.output(&format!(
+            "{} failed test(s) are known to be flaky:",
+            flaky.len()
+        ))?;
+        for test in &flaky {
+            ui.output(&format!(
+                "  {} (score {:.2}, last flipped in run {})",
+                test.test_id,
+                test.score(),
+                test.last_flip
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Exit code for a finished run
+    ///
+    /// Test processes exit non-zero when their tests fail, so once failing
+    /// tests have been retried it is the results that decide. A run stopped
+    /// by a signal exits with 128 plus the signal number, as a shell does.
+    fn exit_code(
+        &self,
+        run: &TestRun,
+        process_failed: bool,
+        retries: &Retries,
+        stop: &StopHandle,
+    ) -> i32 {
+        if let Some(signal) = stop.interrupted() {
+            return INTERRUPTED_EXIT_CODE_BASE + signal;
+        }
+        let flaky_failed = self.flaky_policy == FlakyPolicy::Fail && !retries.flaky.is_empty();
+        let process_failed = process_failed && retries.attempts == 0;
+        if run.count_failures() > 0 || flaky_failed || process_failed {
+            1
+        } else {
+            0
         }
     }
 
@@ -431,22 +1136,28 @@ impl RunCommand {
 
         // Begin the test run and get a writer for streaming raw bytes
         let (run_id, raw_writer) = repo.begin_test_run_raw()?;
-
-        // Spawn test command with piped stdout
-        let mut child = Command::new("sh")
+        let mut metadata = self.begin_metadata(test_cmd, 1);
+
+        // Spawn test command with piped stdout, in its own process group so
+        // that an interrupt can be passed on to it
+        let stop = StopHandle::new();
+        let _interrupts = InterruptHandler::install(&stop);
+        let mut command = Command::new("sh");
+        command
             .arg("-c")
             .arg(&cmd_str)
-            .current_dir(Path::new(self.base_path.as_deref().unwrap_or(".")))
-            .stdout(Stdio::piped())
-            .spawn()
-            .map_err(|e| {
-                crate::error::Error::CommandExecution(format!(
-                    "Failed to execute test command: {}",
-                    e
-                ))
-            })?;
+            .current_dir(test_cmd.base_dir())
+            .stdout(Stdio::piped());
+        crate::test_runner::set_process_group(&mut command);
+        let mut child = command.spawn().map_err(|e| {
+            crate::error::Error::CommandExecution(format!("Failed to execute test command: {}", e))
+        })?;
+        stop.add_process(child.id());
 
         let mut stdout = child.stdout.take().expect("stdout was piped");
+        let pid = child.id();
+        let mut children = ProcessReapGuard::new(&stop);
+        children.insert(0, child);
 
         // Create a tee writer that writes to both file and UI
         struct TeeWriter<W1: Write, W2: Write> {
@@ -492,20 +1203,37 @@ impl RunCommand {
 
         std::io::copy(&mut stdout, &mut tee).map_err(crate::error::Error::Io)?;
         tee.flush().map_err(crate::error::Error::Io)?;
+        // The run is only stored once its writer is committed
+        tee.writer1.commit()?;
 
         // Wait for process to complete
-        let status = child.wait().map_err(|e| {
+        let status = children.wait(0).map_err(|e| {
             crate::error::Error::CommandExecution(format!("Failed to wait for test command: {}", e))
         })?;
+        stop.remove_process(pid);
 
         // Parse the stored stream to update failing tests
         let test_run = repo.get_test_run(&run_id)?;
 
-        crate::commands::utils::update_repository_failing_tests(repo, &test_run, self.partial)?;
+        if stop.interrupted().is_some() {
+            metadata.partial = Some(true);
+            metadata.interrupted = Some(true);
+        }
+        crate::commands::utils::update_repository_failing_tests(
+            repo,
+            &test_run,
+            metadata.partial == Some(true),
+        )?;
         crate::commands::utils::update_test_times_from_run(repo, &test_run)?;
 
+        metadata.command = Some(cmd_str);
+        metadata.exit_codes = vec![crate::test_runner::exit_code(status)];
+        self.record_metadata(repo, &run_id, metadata)?;
+
         // Return exit code based on test command exit code
-        if status.success() {
+        if let Some(signal) = stop.interrupted() {
+            Ok(INTERRUPTED_EXIT_CODE_BASE + signal)
+        } else if status.success() {
             Ok(0)
         } else {
             Ok(1)
@@ -520,14 +1248,14 @@ impl RunCommand {
         test_cmd: &TestCommand,
         test_ids: Option<&[crate::repository::TestId]>,
     ) -> Result<i32> {
-        use std::process::{Command, Stdio};
-
-        // Get test count for progress bar
-        let test_count = if let Some(ids) = test_ids {
-            ids.len()
+        // Get the tests to run, for the progress bar and to know which never
+        // ran if the run is stopped early
+        let scheduled = if let Some(ids) = test_ids {
+            ids.to_vec()
         } else {
-            test_cmd.list_tests()?.len()
+            test_cmd.list_tests()?
         };
+        let test_count = scheduled.len();
 
         // Build command with test IDs if provided
         let (cmd_str, _temp_file) =
@@ -535,6 +1263,7 @@ impl RunCommand {
 
         // Begin the test run and get a writer for streaming raw bytes
         let (run_id, raw_writer) = repo.begin_test_run_raw()?;
+        let mut metadata = self.begin_metadata(test_cmd, 1);
 
         // Create progress bar with dynamic width
         let term_width = console::Term::stdout().size().1 as usize;
@@ -557,24 +1286,20 @@ impl RunCommand {
         );
 
         // Spawn test command with both stdout and stderr piped
-        let mut child = Command::new("sh")
-            .arg("-c")
-            .arg(&cmd_str)
-            .current_dir(Path::new(self.base_path.as_deref().unwrap_or(".")))
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| {
-                progress_bar.finish_and_clear();
-                crate::error::Error::CommandExecution(format!(
-                    "Failed to execute test command: {}",
-                    e
-                ))
-            })?;
+        let stop = StopHandle::new();
+        let _interrupts = InterruptHandler::install(&stop);
+        let watchdog = self.start_watchdog(&**repo, &scheduled, &stop)?;
+        let mut child = self.test_process(&cmd_str, test_cmd).spawn().map_err(|e| {
+            progress_bar.finish_and_clear();
+            crate::error::Error::CommandExecution(format!("Failed to execute test command: {}", e))
+        })?;
+        watchdog.watch(0, child.id());
 
         // Take stdout and stderr for streaming
         let stdout = child.stdout.take().expect("stdout was piped");
         let stderr = child.stderr.take().expect("stderr was piped");
+        let mut children = ProcessReapGuard::new(&stop);
+        children.insert(0, child);
 
         // Tee the stream: capture raw bytes for storage AND parse for progress display
         let (tx, rx) = std::sync::mpsc::sync_channel(100);
@@ -593,11 +1318,10 @@ impl RunCommand {
         // Create a reader from the channel
         let channel_reader = crate::test_runner::ChannelReader::new(rx);
 
-        let output_filter = if self.all_output {
-            subunit_stream::OutputFilter::All
-        } else {
-            subunit_stream::OutputFilter::FailuresOnly
-        };
+        let output_filter = self.output_filter(&**repo)?;
+        let max_failures = self.max_failures;
+        let stop_clone = stop.clone();
+        let watchdog_clone = watchdog.clone();
 
         let parse_thread = std::thread::spawn(move || {
             let mut failures = 0;
@@ -608,6 +1332,7 @@ impl RunCommand {
                 channel_reader,
                 run_id_clone,
                 |test_id, status| {
+                    watchdog_clone.progress(0, test_id, status);
                     let indicator = status.indicator();
                     if !indicator.is_empty() {
                         progress_bar_clone.inc(1);
@@ -619,6 +1344,9 @@ impl RunCommand {
                                 | subunit_stream::ProgressStatus::UnexpectedSuccess
                         ) {
                             failures += 1;
+                            if max_failures.is_some_and(|max| failures >= max) {
+                                stop_clone.stop();
+                            }
                         }
 
                         // Update progress bar color based on failure rate
@@ -651,21 +1379,22 @@ impl RunCommand {
         });
 
         // Wait for process to complete
-        let status = child.wait().map_err(|e| {
+        let status = children.wait(0).map_err(|e| {
             progress_bar.finish_and_clear();
             crate::error::Error::CommandExecution(format!("Failed to wait for test command: {}", e))
         })?;
+        watchdog.unwatch(0);
 
         let command_failed = !status.success();
 
         // Get results from parse thread
-        let test_run = parse_thread.join().map_err(|_| {
+        let mut test_run = parse_thread.join().map_err(|_| {
             progress_bar.finish_and_clear();
             crate::error::Error::CommandExecution("Parse thread panicked".to_string())
         })??;
 
         // Wait for tee threads to finish writing raw bytes
-        tee_thread
+        let mut raw_writer = tee_thread
             .join()
             .map_err(|_| {
                 progress_bar.finish_and_clear();
@@ -676,7 +1405,7 @@ impl RunCommand {
                 crate::error::Error::Io(e)
             })?;
 
-        stderr_thread
+        let stderr_tail = stderr_thread
             .join()
             .map_err(|_| {
                 progress_bar.finish_and_clear();
@@ -689,19 +1418,76 @@ impl RunCommand {
 
         progress_bar.finish_and_clear();
 
+        metadata.command = Some(cmd_str);
+        metadata.exit_codes = vec![crate::test_runner::exit_code(status)];
+
+        // Record the tests the process never finished as errors
+        let unfinished = Unfinished::take(&watchdog, 0, status);
+        unfinished.record(&stderr_tail, &mut test_run, &mut raw_writer)?;
+        let mut unfinished = self.reschedule_timeouts(
+            test_cmd,
+            &watchdog,
+            &stop,
+            0,
+            None,
+            &scheduled,
+            unfinished,
+            &mut test_run,
+            &mut raw_writer,
+            &mut metadata,
+        )?;
+
+        // A run cut short by a stop, a timeout or a crash is missing the
+        // tests it never reached, which must keep their failing state rather
+        // than count as passed. A command that exits non-zero because tests
+        // failed has still run them all.
+        let stopped = stop.is_stopped();
+        let crashed = metadata.exit_codes.iter().any(|code| *code < 0);
+        let not_run = if stopped || crashed || !unfinished.is_empty() {
+            count_not_run(&test_run, &scheduled)
+        } else {
+            0
+        };
+        let mut retries = if stopped {
+            Retries::default()
+        } else {
+            self.retry_failures(
+                ui,
+                test_cmd,
+                &watchdog,
+                &mut test_run,
+                &mut raw_writer,
+                &[],
+                false,
+                &mut metadata,
+            )?
+        };
+        unfinished.extend(std::mem::take(&mut retries.unfinished));
+        // An interrupted run only ever updates the failing tests
+        if not_run > 0 || stop.interrupted().is_some() {
+            metadata.partial = Some(true);
+        }
+        metadata.interrupted = stop.interrupted().map(|_| true);
+        // The run is only stored once its writer is committed
+        raw_writer.commit()?;
+
         // Update failing tests and test times
-        crate::commands::utils::update_repository_failing_tests(repo, &test_run, self.partial)?;
+        crate::commands::utils::update_repository_failing_tests(
+            repo,
+            &test_run,
+            metadata.partial == Some(true),
+        )?;
         crate::commands::utils::update_test_times_from_run(repo, &test_run)?;
+        self.record_metadata(repo, &run_id, metadata)?;
 
-        // Display summary
-        crate::commands::utils::display_test_summary(ui, &run_id, &test_run)?;
+        // Display summary, hiding tagged tests unless they failed
+        let visible_run = test_run.hide_filtered_tags(&test_cmd.get_filter_tags());
+        crate::commands::utils::display_test_summary(ui, &run_id, &visible_run)?;
+        self.record_retries(ui, repo, &retries)?;
+        self.report_known_flaky(ui, repo.as_ref(), &visible_run)?;
+        self.report_incomplete(ui, &test_run, &watchdog, &stop, &unfinished, not_run)?;
 
-        // Return exit code based on results
-        if test_run.count_failures() > 0 || command_failed {
-            Ok(1)
-        } else {
-            Ok(0)
-        }
+        Ok(self.exit_code(&test_run, command_failed, &retries, &stop))
     }
 
     /// Run tests in parallel across multiple workers
@@ -713,26 +1499,19 @@ impl RunCommand {
         test_ids: Option<&[crate::repository::TestId]>,
         concurrency: usize,
     ) -> Result<i32> {
-        use std::collections::HashMap;
-        use std::process::{Command, Stdio};
+        use std::collections::{BTreeMap, HashMap, VecDeque};
+        use std::io::Write;
         use std::sync::atomic::{AtomicUsize, Ordering};
         use std::sync::Arc;
 
-        let output_filter = if self.all_output {
-            subunit_stream::OutputFilter::All
-        } else {
-            subunit_stream::OutputFilter::FailuresOnly
-        };
-
-        // Get the base run ID - each worker will write to run_id-{worker_id}
-        let base_run_id = repo.get_next_run_id()?;
+        let output_filter = self.output_filter(&**repo)?;
 
         // Get the list of tests to run
         let all_tests = if let Some(ids) = test_ids {
             ids.to_vec()
         } else {
             // Need to list all tests
-            test_cmd.list_tests()?
+            self.remove_filtered_tests(&**repo, test_cmd, test_cmd.list_tests()?)?
         };
 
         if all_tests.is_empty() {
@@ -740,21 +1519,40 @@ impl RunCommand {
             return Ok(0);
         }
 
-        // Get historical test durations for these specific tests
-        let durations = repo.get_test_times_for_ids(&all_tests)?;
-
         // Get group_regex from config if present
         let group_regex = test_cmd.config().group_regex.as_deref();
 
-        // Partition tests across workers
-        let partitions = crate::partition::partition_tests_with_grouping(
+        // Estimate how long these specific tests take from their history
+        let estimator = repo.get_config()?.time_estimator.unwrap_or_default();
+        let durations = crate::partition::estimate_durations(
             &all_tests,
-            &durations,
-            concurrency,
+            &repo.get_test_timings_for_ids(&all_tests)?,
+            estimator,
             group_regex,
         )
         .map_err(|e| crate::error::Error::Config(format!("Invalid group_regex pattern: {}", e)))?;
 
+        // Partition tests across workers, giving each worker one batch, or
+        // split them into smaller batches that go to whichever worker is free
+        let batches = if self.dynamic {
+            let batch_size = self.batch_size.unwrap_or_else(|| {
+                crate::partition::default_batch_size(all_tests.len(), concurrency)
+            });
+            crate::partition::batch_tests(&all_tests, &durations, batch_size, group_regex)
+        } else {
+            crate::partition::partition_tests_with_grouping(
+                &all_tests,
+                &durations,
+                concurrency,
+                group_regex,
+            )
+        };
+        let mut batches: VecDeque<Vec<TestId>> = batches
+            .map_err(|e| {
+                crate::error::Error::Config(format!("Invalid group_regex pattern: {}", e))
+            })?
+            .into();
+
         // Create multi-progress for tracking all workers
         let term_width = console::Term::stdout().size().1 as usize;
         let fixed_width = 25; // For "[HH:MM:SS] " + " pos/len "
@@ -772,8 +1570,10 @@ impl RunCommand {
                 .progress_chars("█▓▒░  "),
         );
 
-        // Shared failure counter across all workers
+        // Shared failure counter across all workers, which stop together
         let total_failures = Arc::new(AtomicUsize::new(0));
+        let stop = StopHandle::new();
+        let _interrupts = InterruptHandler::install(&stop);
 
         // Provision instances if configured
         let instance_ids = test_cmd.provision_instances(concurrency)?;
@@ -785,76 +1585,71 @@ impl RunCommand {
         let dispose_guard = InstanceDisposeGuard {
             test_cmd,
             instance_ids: &instance_ids,
+            disposed: false,
         };
 
-        // Spawn worker processes with progress bars and streaming parsers
-        let mut workers = Vec::new();
-        let mut parse_threads = Vec::new();
-
-        for (worker_id, partition) in partitions.iter().enumerate() {
-            if partition.is_empty() {
-                continue;
-            }
-
-            // Create a progress bar for this worker
-            // Template: "Worker N: [bar] pos/len msg"
-            // Fixed: "Worker N: " (10-12) + " " + "999/999" (7) + " " = ~20 chars
-            let worker_fixed = 22;
-            let worker_bar_width =
-                ((term_width.saturating_sub(worker_fixed + 30)) / concurrency.min(4)).clamp(15, 40);
-            // Calculate max message for worker
-            let worker_max_msg = term_width
-                .saturating_sub(worker_bar_width + worker_fixed)
-                .max(20);
-
-            let worker_bar = multi_progress.add(ProgressBar::new(partition.len() as u64));
-            worker_bar.set_style(
-                ProgressStyle::default_bar()
-                    .template(&format!(
-                        "Worker {}: [{{bar:{}.green/blue}}] {{pos}}/{{len}} {{msg}}",
-                        worker_id, worker_bar_width
-                    ))
-                    .unwrap()
-                    .progress_chars("█▓▒░  "),
-            );
-
-            // Build command for this partition with instance ID
+        // All workers are stored as a single run; each worker's raw output is
+        // buffered and multiplexed into it (tagged worker-N) once the worker is done
+        let (run_id, mut raw_writer) = repo.begin_test_run_raw()?;
+        let mut metadata = self.begin_metadata(test_cmd, concurrency);
+        let watchdog = self.start_watchdog(&**repo, &all_tests, &stop)?;
+
+        // Create a progress bar for each worker
+        // Template: "Worker N: [bar] pos/len msg"
+        // Fixed: "Worker N: " (10-12) + " " + "999/999" (7) + " " = ~20 chars
+        let worker_fixed = 22;
+        let worker_bar_width =
+            ((term_width.saturating_sub(worker_fixed + 30)) / concurrency.min(4)).clamp(15, 40);
+        // Calculate max message for worker
+        let worker_max_msg = term_width
+            .saturating_sub(worker_bar_width + worker_fixed)
+            .max(20);
+
+        // Start a test process for a worker, with a streaming parser, to
+        // run a batch of tests; the worker's bar grows by the batch
+        let spawn_batch = |worker_id: usize,
+                           tests: Vec<TestId>,
+                           worker_bar: &ProgressBar,
+                           worker_failures: &Arc<AtomicUsize>,
+                           metadata: &mut RunMetadata,
+                           children: &mut ProcessReapGuard|
+         -> Result<WorkerProcess> {
+            worker_bar.inc_length(tests.len() as u64);
+
+            // Build command for this batch with the worker's instance ID
             let instance_id = instance_ids.get(worker_id).map(|s| s.as_str());
-            let (cmd_str, _temp_file) = test_cmd.build_command_full(
-                Some(partition),
+            let (cmd_str, id_file) = test_cmd.build_command_full(
+                Some(&tests),
                 false,
                 instance_id,
                 self.test_args.as_deref(),
             )?;
+            metadata.command.get_or_insert_with(|| cmd_str.clone());
 
             // Spawn the worker process with both stdout and stderr piped
-            let mut child = Command::new("sh")
-                .arg("-c")
-                .arg(&cmd_str)
-                .current_dir(Path::new(self.base_path.as_deref().unwrap_or(".")))
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-                .map_err(|e| {
-                    crate::error::Error::CommandExecution(format!(
-                        "Failed to spawn worker {}: {}",
-                        worker_id, e
-                    ))
-                })?;
+            let mut child = self.test_process(&cmd_str, test_cmd).spawn().map_err(|e| {
+                crate::error::Error::CommandExecution(format!(
+                    "Failed to spawn worker {}: {}",
+                    worker_id, e
+                ))
+            })?;
+            watchdog.watch(worker_id, child.id());
 
             // Take stdout and stderr for streaming
             let stdout = child.stdout.take().expect("stdout was piped");
             let stderr = child.stderr.take().expect("stderr was piped");
+            children.insert(worker_id, child);
 
-            // Get a writer for this worker's raw output
-            let worker_run_id = format!("{}-{}", base_run_id, worker_id);
-            let (_, raw_writer) = repo.begin_test_run_raw()?;
+            // Buffer this process's raw output until it can be added to the combined run
+            let worker_run_id = format!("{}-{}", run_id, worker_id);
+            let worker_buffer = tempfile::tempfile()?;
+            let reader = worker_buffer.try_clone()?;
 
             // Tee the stream: capture raw bytes for storage AND parse for progress display
             let (tx, rx) = std::sync::mpsc::sync_channel(100);
 
             // Thread for stdout
-            let tee_thread = crate::test_runner::spawn_stdout_tee(stdout, raw_writer, tx);
+            let tee_thread = crate::test_runner::spawn_stdout_tee(stdout, worker_buffer, tx);
 
             // Thread for stderr - write directly to stderr (not to parser or storage)
             let stderr_thread =
@@ -866,18 +1661,21 @@ impl RunCommand {
             // Spawn thread to parse output in real-time
             let worker_bar_clone = worker_bar.clone();
             let overall_bar_clone = overall_bar.clone();
-            let worker_run_id_clone = worker_run_id.clone();
             let total_failures_clone = Arc::clone(&total_failures);
+            let worker_failures = Arc::clone(worker_failures);
+            let stop_clone = stop.clone();
+            let max_failures = self.max_failures;
+            let watchdog_clone = watchdog.clone();
 
             let output_filter_clone = output_filter;
             let parse_thread = std::thread::spawn(move || {
-                let mut failures = 0;
                 let worker_bar_for_bytes = worker_bar_clone.clone();
                 // Parse stdout stream for real-time progress
                 subunit_stream::parse_stream_with_progress(
                     channel_reader,
-                    worker_run_id_clone,
+                    worker_run_id,
                     |test_id, status| {
+                        watchdog_clone.progress(worker_id, test_id, status);
                         let indicator = status.indicator();
                         if !indicator.is_empty() {
                             worker_bar_clone.inc(1);
@@ -889,9 +1687,12 @@ impl RunCommand {
                                 subunit_stream::ProgressStatus::Failed
                                     | subunit_stream::ProgressStatus::UnexpectedSuccess
                             ) {
-                                failures += 1;
+                                worker_failures.fetch_add(1, Ordering::Relaxed);
                                 let total =
                                     total_failures_clone.fetch_add(1, Ordering::Relaxed) + 1;
+                                if max_failures.is_some_and(|max| total >= max) {
+                                    stop_clone.stop();
+                                }
 
                                 // Update progress bar color based on failure rate
                                 let completed = overall_bar_clone.position();
@@ -908,6 +1709,7 @@ impl RunCommand {
                                 overall_bar_clone.set_message(msg);
                             }
 
+                            let failures = worker_failures.load(Ordering::Relaxed);
                             let fail_msg = format_failure_msg(failures, true);
                             let fail_len = if failures > 0 {
                                 9 + failures.to_string().len()
@@ -927,25 +1729,80 @@ impl RunCommand {
                 )
             });
 
-            parse_threads.push((
-                worker_id,
-                worker_bar,
+            Ok(WorkerProcess {
+                tests,
+                _id_file: id_file,
                 parse_thread,
                 tee_thread,
                 stderr_thread,
-            ));
-            workers.push((worker_id, child, _temp_file));
+                reader,
+            })
+        };
+
+        // Give each worker its first batch; the workers are stopped and
+        // waited for if the run ends early with an error
+        let mut children = ProcessReapGuard::new(&stop);
+        let mut running: BTreeMap<usize, WorkerProcess> = BTreeMap::new();
+        let mut worker_bars = HashMap::new();
+        for worker_id in 0..concurrency {
+            let Some(batch) = batches.pop_front() else {
+                break;
+            };
+            if batch.is_empty() {
+                continue;
+            }
+
+            let worker_bar = multi_progress.add(ProgressBar::new(0));
+            worker_bar.set_style(
+                ProgressStyle::default_bar()
+                    .template(&format!(
+                        "Worker {}: [{{bar:{}.green/blue}}] {{pos}}/{{len}} {{msg}}",
+                        worker_id, worker_bar_width
+                    ))
+                    .unwrap()
+                    .progress_chars("█▓▒░  "),
+            );
+            let worker_failures = Arc::new(AtomicUsize::new(0));
+            let process = spawn_batch(
+                worker_id,
+                batch,
+                &worker_bar,
+                &worker_failures,
+                &mut metadata,
+                &mut children,
+            )?;
+            running.insert(worker_id, process);
+            worker_bars.insert(worker_id, (worker_bar, worker_failures));
         }
 
         // Collect results from parse threads and wait for workers to complete
-        // IMPORTANT: We must collect from parse threads FIRST (while workers are still running)
-        // to avoid deadlock. If we wait for workers first, the pipe buffer can fill up and
+        // IMPORTANT: We must collect from a worker's parse thread FIRST (while it is still running)
+        // to avoid deadlock. If we wait for the worker first, the pipe buffer can fill up and
         // the worker process will block trying to write, while we're blocked waiting for it to finish.
+        // The other workers' output keeps being consumed by their own threads meanwhile.
         let mut all_results = HashMap::new();
         let mut any_failed = false;
+        let mut unfinished = Unfinished::default();
+
+        while !running.is_empty() {
+            // Take whichever process has finished its output first
+            let finished = running
+                .iter()
+                .find(|(_, process)| process.parse_thread.is_finished())
+                .map(|(worker_id, _)| *worker_id);
+            let Some(worker_id) = finished else {
+                std::thread::sleep(WORKER_POLL_INTERVAL);
+                continue;
+            };
+            let WorkerProcess {
+                tests,
+                _id_file,
+                parse_thread,
+                tee_thread,
+                stderr_thread,
+                reader: mut worker_reader,
+            } = running.remove(&worker_id).expect("worker is running");
 
-        // First, collect results from ALL parse threads (this will also consume stdout, preventing deadlock)
-        for (worker_id, worker_bar, parse_thread, tee_thread, stderr_thread) in parse_threads {
             let worker_run = parse_thread.join().map_err(|_| {
                 crate::error::Error::CommandExecution(format!(
                     "Parse thread {} panicked",
@@ -964,7 +1821,7 @@ impl RunCommand {
                 })?
                 .map_err(crate::error::Error::Io)?;
 
-            stderr_thread
+            let stderr_tail = stderr_thread
                 .join()
                 .map_err(|_| {
                     crate::error::Error::CommandExecution(format!(
@@ -974,11 +1831,51 @@ impl RunCommand {
                 })?
                 .map_err(crate::error::Error::Io)?;
 
-            worker_bar.finish_with_message("done");
+            let status = children.wait(worker_id).map_err(|e| {
+                crate::error::Error::CommandExecution(format!(
+                    "Failed to wait for worker {}: {}",
+                    worker_id, e
+                ))
+            })?;
+            watchdog.unwatch(worker_id);
+            if !status.success() {
+                any_failed = true;
+            }
+            metadata
+                .exit_codes
+                .push(crate::test_runner::exit_code(status));
 
-            // Add worker tag to all results
-            let worker_tag = format!("worker-{}", worker_id);
+            // Record the tests this process never finished as errors, running
+            // the rest of its batch again if it timed out and was asked to
             let mut worker_run = worker_run;
+            {
+                use std::io::Seek;
+                worker_reader.seek(std::io::SeekFrom::End(0))?;
+                let worker_unfinished = Unfinished::take(&watchdog, worker_id, status);
+                worker_unfinished.record(&stderr_tail, &mut worker_run, &mut worker_reader)?;
+                unfinished.extend(self.reschedule_timeouts(
+                    test_cmd,
+                    &watchdog,
+                    &stop,
+                    worker_id,
+                    instance_ids.get(worker_id).map(|s| s.as_str()),
+                    &tests,
+                    worker_unfinished,
+                    &mut worker_run,
+                    &mut worker_reader,
+                    &mut metadata,
+                )?);
+            }
+
+            // Multiplex this worker's raw stream into the combined run
+            let worker_tag = format!("worker-{}", worker_id);
+            {
+                use std::io::Seek;
+                worker_reader.seek(std::io::SeekFrom::Start(0))?;
+                subunit_stream::tag_stream(&mut worker_reader, &mut raw_writer, &worker_tag)?;
+            }
+
+            // Add worker tag to all results
             for (_, result) in worker_run.results.iter_mut() {
                 if !result.tags.contains(&worker_tag) {
                     result.tags.push(worker_tag.clone());
@@ -989,19 +1886,22 @@ impl RunCommand {
             for (test_id, result) in worker_run.results {
                 all_results.insert(test_id, result);
             }
-        }
 
-        // Now wait for all worker processes to complete
-        for (worker_id, mut child, _temp_file) in workers {
-            let status = child.wait().map_err(|e| {
-                crate::error::Error::CommandExecution(format!(
-                    "Failed to wait for worker {}: {}",
-                    worker_id, e
-                ))
-            })?;
-
-            if !status.success() {
-                any_failed = true;
+            // Hand the worker the next batch, if there is one
+            let (worker_bar, worker_failures) = &worker_bars[&worker_id];
+            match batches.pop_front().filter(|_| !stop.is_stopped()) {
+                Some(batch) => {
+                    let process = spawn_batch(
+                        worker_id,
+                        batch,
+                        worker_bar,
+                        worker_failures,
+                        &mut metadata,
+                        &mut children,
+                    )?;
+                    running.insert(worker_id, process);
+                }
+                None => worker_bar.finish_with_message("done"),
             }
         }
 
@@ -1009,34 +1909,73 @@ impl RunCommand {
         overall_bar.finish_and_clear();
 
         // Create combined test run
-        let run_id_for_display = base_run_id.to_string();
-        let mut combined_run = crate::repository::TestRun::new(run_id_for_display.clone());
+        let mut combined_run = crate::repository::TestRun::new(run_id.clone());
         combined_run.timestamp = chrono::Utc::now();
 
         for (_, result) in all_results {
             combined_run.add_result(result);
         }
 
+        // A run cut short by a stop, a timeout or a crash is missing the
+        // tests it never reached, which must keep their failing state rather
+        // than count as passed. A command that exits non-zero because tests
+        // failed has still run them all.
+        let stopped = stop.is_stopped();
+        let crashed = metadata.exit_codes.iter().any(|code| *code < 0);
+        let not_run = if stopped || crashed || !unfinished.is_empty() {
+            count_not_run(&combined_run, &all_tests)
+        } else {
+            0
+        };
+
+        // Retry on the instances the tests failed on, before they are disposed
+        let mut retries = if stopped {
+            Retries::default()
+        } else {
+            self.retry_failures(
+                ui,
+                test_cmd,
+                &watchdog,
+                &mut combined_run,
+                &mut raw_writer,
+                &instance_ids,
+                false,
+                &mut metadata,
+            )?
+        };
+        unfinished.extend(std::mem::take(&mut retries.unfinished));
+        // An interrupted run only ever updates the failing tests
+        if not_run > 0 || stop.interrupted().is_some() {
+            metadata.partial = Some(true);
+        }
+        metadata.interrupted = stop.interrupted().map(|_| true);
+
+        // Make sure the combined stream is on disk before it is re-read
+        raw_writer.commit()?;
+
         // Update failing tests and test times
-        crate::commands::utils::update_repository_failing_tests(repo, &combined_run, self.partial)?;
+        crate::commands::utils::update_repository_failing_tests(
+            repo,
+            &combined_run,
+            metadata.partial == Some(true),
+        )?;
         crate::commands::utils::update_test_times_from_run(repo, &combined_run)?;
+        self.record_metadata(repo, &run_id, metadata)?;
 
-        // Dispose instances (done explicitly before drop to handle errors)
-        drop(dispose_guard);
-        test_cmd.dispose_instances(&instance_ids)?;
+        // Dispose instances (done explicitly rather than on drop to handle errors)
+        dispose_guard.dispose()?;
         if test_cmd.config().instance_provision.is_some() {
             ui.output("Disposed instances")?;
         }
 
-        // Display summary
-        crate::commands::utils::display_test_summary(ui, &run_id_for_display, &combined_run)?;
+        // Display summary, hiding tagged tests unless they failed
+        let visible_run = combined_run.hide_filtered_tags(&test_cmd.get_filter_tags());
+        crate::commands::utils::display_test_summary(ui, &run_id, &visible_run)?;
+        self.record_retries(ui, repo, &retries)?;
+        self.report_known_flaky(ui, repo.as_ref(), &visible_run)?;
+        self.report_incomplete(ui, &combined_run, &watchdog, &stop, &unfinished, not_run)?;
 
-        // Return exit code based on results
-        if combined_run.count_failures() > 0 || any_failed {
-            Ok(1)
-        } else {
-            Ok(0)
-        }
+        Ok(self.exit_code(&combined_run, any_failed, &retries, &stop))
     }
 
     /// Run each test in complete isolation (one test per process)
@@ -1047,21 +1986,28 @@ impl RunCommand {
         test_cmd: &TestCommand,
         test_ids: &[crate::repository::TestId],
     ) -> Result<i32> {
-        use std::collections::HashMap;
-        use std::process::{Command, Stdio};
+        use std::io::Write;
 
-        // Get the base run ID - each isolated test will write to its own file
-        let base_run_id = repo.get_next_run_id()?;
+        // Every isolated process is appended to the same stored run
+        let (run_id, mut raw_writer) = repo.begin_test_run_raw()?;
+        let mut metadata = self.begin_metadata(test_cmd, 1);
+        let stop = StopHandle::new();
+        let _interrupts = InterruptHandler::install(&stop);
+        let watchdog = self.start_watchdog(&**repo, test_ids, &stop)?;
 
         ui.output(&format!(
             "Running {} tests in isolated mode (one test per process)",
             test_ids.len()
         ))?;
 
-        let mut all_results = HashMap::new();
+        let mut combined_run = crate::repository::TestRun::new(run_id.clone());
         let mut any_failed = false;
+        let mut unfinished = Unfinished::default();
 
         for (idx, test_id) in test_ids.iter().enumerate() {
+            if stop.is_stopped() {
+                break;
+            }
             ui.output(&format!("  [{}/{}] {}", idx + 1, test_ids.len(), test_id))?;
 
             // Build command for this single test
@@ -1071,74 +2017,98 @@ impl RunCommand {
                 None,
                 self.test_args.as_deref(),
             )?;
+            metadata.command.get_or_insert_with(|| cmd_str.clone());
 
             // Spawn process for this test
-            let output = Command::new("sh")
-                .arg("-c")
-                .arg(&cmd_str)
-                .current_dir(Path::new(self.base_path.as_deref().unwrap_or(".")))
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .output()
-                .map_err(|e| {
-                    crate::error::Error::CommandExecution(format!(
-                        "Failed to execute test {}: {}",
-                        test_id, e
-                    ))
-                })?;
+            let (output, test_unfinished, status) =
+                self.run_watched(test_cmd, &watchdog, 0, &cmd_str)?;
 
-            if !output.status.success() {
+            if !status.success() {
                 any_failed = true;
             }
-
-            // Parse test results
-            let test_run_id = format!("{}-{}", base_run_id, idx);
-            let test_run = subunit_stream::parse_stream(output.stdout.as_slice(), test_run_id)?;
-
-            // Collect results
-            for (test_id, result) in test_run.results {
-                all_results.insert(test_id, result);
+            metadata
+                .exit_codes
+                .push(crate::test_runner::exit_code(status));
+
+            // Store the raw output, including attachments, as part of the run
+            raw_writer.write_all(&output)?;
+
+            // Parse test results and collect them
+            let test_run_id = format!("{}-{}", run_id, idx);
+            let test_run = subunit_stream::parse_stream(output.as_slice(), test_run_id)?;
+            add_results(&mut combined_run, test_run, &test_unfinished);
+            unfinished.extend(test_unfinished);
+
+            if self
+                .max_failures
+                .is_some_and(|max| combined_run.count_failures() >= max)
+            {
+                stop.stop();
             }
         }
-
-        // Create combined test run
-        let run_id_for_display = base_run_id.to_string();
-        let mut combined_run = crate::repository::TestRun::new(run_id_for_display.clone());
         combined_run.timestamp = chrono::Utc::now();
 
-        for (_, result) in all_results {
-            combined_run.add_result(result);
+        // A run cut short by a stop, a timeout or a crash is missing the
+        // tests it never reached, which must keep their failing state rather
+        // than count as passed. A command that exits non-zero because tests
+        // failed has still run them all.
+        let stopped = stop.is_stopped();
+        let crashed = metadata.exit_codes.iter().any(|code| *code < 0);
+        let not_run = if stopped || crashed || !unfinished.is_empty() {
+            count_not_run(&combined_run, test_ids)
+        } else {
+            0
+        };
+        let mut retries = if stopped {
+            Retries::default()
+        } else {
+            self.retry_failures(
+                ui,
+                test_cmd,
+                &watchdog,
+                &mut combined_run,
+                &mut raw_writer,
+                &[],
+                true,
+                &mut metadata,
+            )?
+        };
+        unfinished.extend(std::mem::take(&mut retries.unfinished));
+        // An interrupted run only ever updates the failing tests
+        if not_run > 0 || stop.interrupted().is_some() {
+            metadata.partial = Some(true);
         }
+        metadata.interrupted = stop.interrupted().map(|_| true);
+
+        raw_writer.commit()?;
 
         // Update failing tests and test times
-        crate::commands::utils::update_repository_failing_tests(repo, &combined_run, self.partial)?;
+        crate::commands::utils::update_repository_failing_tests(
+            repo,
+            &combined_run,
+            metadata.partial == Some(true),
+        )?;
         crate::commands::utils::update_test_times_from_run(repo, &combined_run)?;
+        self.record_metadata(repo, &run_id, metadata)?;
 
-        // Display summary
-        crate::commands::utils::display_test_summary(ui, &run_id_for_display, &combined_run)?;
+        // Display summary, hiding tagged tests unless they failed
+        let visible_run = combined_run.hide_filtered_tags(&test_cmd.get_filter_tags());
+        crate::commands::utils::display_test_summary(ui, &run_id, &visible_run)?;
+        self.record_retries(ui, repo, &retries)?;
+        self.report_known_flaky(ui, repo.as_ref(), &visible_run)?;
+        self.report_incomplete(ui, &combined_run, &watchdog, &stop, &unfinished, not_run)?;
 
-        // Return exit code based on results
-        if combined_run.count_failures() > 0 || any_failed {
-            Ok(1)
-        } else {
-            Ok(0)
-        }
+        Ok(self.exit_code(&combined_run, any_failed, &retries, &stop))
     }
-}
 
-impl Command for RunCommand {
-    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+    /// Select and run tests, storing the results in the repository
+    fn run_tests(
+        &self,
+        ui: &mut dyn UI,
+        repo: &mut Box<dyn crate::repository::Repository>,
+    ) -> Result<i32> {
         let base = Path::new(self.base_path.as_deref().unwrap_or("."));
 
-        // Open repository
-        let mut repo = if self.force_init {
-            // Try to open, if it fails, initialize
-            open_repository(self.base_path.as_deref())
-                .or_else(|_| init_repository(self.base_path.as_deref()))?
-        } else {
-            open_repository(self.base_path.as_deref())?
-        };
-
         // Load test command configuration
         let test_cmd = TestCommand::from_directory(base)?;
 
@@ -1212,14 +2182,21 @@ impl Command for RunCommand {
             test_ids = Some(filtered_ids);
         }
 
+        // Tagged pseudo-tests are run implicitly, never scheduled explicitly
+        if let Some(ids) = test_ids {
+            test_ids = Some(self.remove_filtered_tests(&**repo, &test_cmd, ids)?);
+        }
+
         // If subunit mode is requested, run and output raw subunit stream
         if self.subunit {
             let run_id = repo.get_next_run_id()?.to_string();
-            return self.run_subunit(ui, &mut repo, &test_cmd, test_ids.as_deref(), run_id);
+            return self.run_subunit(ui, repo, &test_cmd, test_ids.as_deref(), run_id);
         }
 
         // Determine concurrency level
-        // Priority: 1) explicit --parallel flag, 2) test_run_concurrency callout, 3) default to 1
+        // Priority: 1) explicit --parallel flag, 2) repo.conf, 3) test_run_concurrency
+        // callout, 4) default to 1
+        let repo_concurrency = repo.get_config()?.concurrency;
         let concurrency = if let Some(explicit_concurrency) = self.concurrency {
             if explicit_concurrency == 0 {
                 // --parallel was given without a value, detect CPU count
@@ -1232,6 +2209,8 @@ impl Command for RunCommand {
             } else {
                 explicit_concurrency
             }
+        } else if let Some(repo_concurrency) = repo_concurrency {
+            repo_concurrency
         } else if let Some(callout_concurrency) = test_cmd.get_concurrency()? {
             ui.output(&format!(
                 "Using concurrency from test_run_concurrency: {}",
@@ -1248,7 +2227,7 @@ impl Command for RunCommand {
                 ids
             } else {
                 // Need to list all tests
-                test_cmd.list_tests()?
+                self.remove_filtered_tests(&**repo, &test_cmd, test_cmd.list_tests()?)?
             };
 
             if all_tests.is_empty() {
@@ -1261,17 +2240,17 @@ impl Command for RunCommand {
                 let mut iteration = 1;
                 loop {
                     ui.output(&format!("\n=== Iteration {} ===", iteration))?;
-                    let exit_code = self.run_isolated(ui, &mut repo, &test_cmd, &all_tests)?;
+                    let exit_code = self.run_isolated(ui, repo, &test_cmd, &all_tests)?;
 
                     if exit_code != 0 {
-                        ui.output(&format!("\nTests failed on iteration {}", iteration))?;
+                        ui.output(&iteration_stopped(exit_code, iteration))?;
                         return Ok(exit_code);
                     }
 
                     iteration += 1;
                 }
             } else {
-                self.run_isolated(ui, &mut repo, &test_cmd, &all_tests)
+                self.run_isolated(ui, repo, &test_cmd, &all_tests)
             }
         } else if self.until_failure {
             // Run tests in a loop until failure (non-isolated)
@@ -1280,14 +2259,14 @@ impl Command for RunCommand {
                 ui.output(&format!("\n=== Iteration {} ===", iteration))?;
 
                 let exit_code = if concurrency > 1 {
-                    self.run_parallel(ui, &mut repo, &test_cmd, test_ids.as_deref(), concurrency)?
+                    self.run_parallel(ui, repo, &test_cmd, test_ids.as_deref(), concurrency)?
                 } else {
-                    self.run_serial(ui, &mut repo, &test_cmd, test_ids.as_deref())?
+                    self.run_serial(ui, repo, &test_cmd, test_ids.as_deref())?
                 };
 
                 // Stop if tests failed
                 if exit_code != 0 {
-                    ui.output(&format!("\nTests failed on iteration {}", iteration))?;
+                    ui.output(&iteration_stopped(exit_code, iteration))?;
                     return Ok(exit_code);
                 }
 
@@ -1297,14 +2276,49 @@ impl Command for RunCommand {
             // Single run (non-isolated, non-looping)
             if concurrency > 1 {
                 // Parallel execution
-                self.run_parallel(ui, &mut repo, &test_cmd, test_ids.as_deref(), concurrency)
+                self.run_parallel(ui, repo, &test_cmd, test_ids.as_deref(), concurrency)
             } else {
                 // Serial execution
-                self.run_serial(ui, &mut repo, &test_cmd, test_ids.as_deref())
+                self.run_serial(ui, repo, &test_cmd, test_ids.as_deref())
             }
         }
     }
 
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+impl Command for RunCommand {
+    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+        // Open repository
+        let mut repo = if self.force_init {
+            // Try to open, if it fails, initialize
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())
+                .or_else(|_| {
+                    init_repository_with(
+                        self.repository_factory.as_ref(),
+                        self.base_path.as_deref(),
+                    )
+                })?
+        } else {
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?
+        };
+
+        let exit_code = self.run_tests(ui, &mut repo)?;
+        crate::commands::utils::auto_prune(ui, &mut repo)?;
+        Ok(exit_code)
+    }
+
     fn name(&self) -> &str {
         "run"
     }
@@ -1321,12 +2335,71 @@ impl Command for RunCommand {
 struct InstanceDisposeGuard<'a> {
     test_cmd: &'a TestCommand,
     instance_ids: &'a [String],
+    disposed: bool,
+}
+
+impl<'a> InstanceDisposeGuard<'a> {
+    /// Dispose the instances now, reporting any error
+    fn dispose(mut self) -> Result<()> {
+        self.disposed = true;
+        self.test_cmd.dispose_instances(self.instance_ids)
+    }
 }
 
 impl<'a> Drop for InstanceDisposeGuard<'a> {
     fn drop(&mut self) {
         // Best effort cleanup - ignore errors during drop
-        let _ = self.test_cmd.dispose_instances(self.instance_ids);
+        if !self.disposed {
+            let _ = self.test_cmd.dispose_instances(self.instance_ids);
+        }
+    }
+}
+
+/// RAII guard to ensure test processes are waited for
+///
+/// Processes are waited for through the guard. Any it still holds when
+/// dropped, because the run returned early with an error, are stopped
+/// through the run's [`StopHandle`] and reaped rather than left running.
+struct ProcessReapGuard<'a> {
+    stop: &'a StopHandle,
+    children: std::collections::BTreeMap<usize, std::process::Child>,
+}
+
+impl<'a> ProcessReapGuard<'a> {
+    fn new(stop: &'a StopHandle) -> Self {
+        ProcessReapGuard {
+            stop,
+            children: std::collections::BTreeMap::new(),
+        }
+    }
+
+    /// Hold a worker's process until it is waited for
+    fn insert(&mut self, worker: usize, child: std::process::Child) {
+        self.children.insert(worker, child);
+    }
+
+    /// Wait for a worker's process, which the guard then lets go of
+    fn wait(&mut self, worker: usize) -> std::io::Result<std::process::ExitStatus> {
+        let child = self
+            .children
+            .get_mut(&worker)
+            .expect("worker has a process");
+        let status = child.wait()?;
+        self.children.remove(&worker);
+        Ok(status)
+    }
+}
+
+impl<'a> Drop for ProcessReapGuard<'a> {
+    fn drop(&mut self) {
+        if self.children.is_empty() {
+            return;
+        }
+        self.stop.stop();
+        for child in self.children.values_mut() {
+            // Best effort cleanup - ignore errors during drop
+            let _ = child.wait();
+        }
     }
 }
 
@@ -1398,4 +2471,32 @@ test_command=echo "test1"
         let cmd = RunCommand::new(None);
         assert_eq!(cmd.name(), "run");
     }
+
+    #[test]
+    fn test_flaky_policy_from_str() {
+        assert_eq!("pass".parse::<FlakyPolicy>().unwrap(), FlakyPolicy::Pass);
+        assert_eq!("fail".parse::<FlakyPolicy>().unwrap(), FlakyPolicy::Fail);
+        assert!("maybe".parse::<FlakyPolicy>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_reap_guard() {
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg("sleep 60; true");
+        crate::test_runner::set_process_group(&mut command);
+
+        let stop = StopHandle::new();
+        let child = command.spawn().unwrap();
+        let pid = child.id();
+        stop.add_process(pid);
+        let mut children = ProcessReapGuard::new(&stop);
+        children.insert(0, child);
+
+        // A run that returns early stops its processes and reaps them
+        drop(children);
+        assert!(stop.is_stopped());
+        // Safety: signal 0 only checks that the process exists
+        assert_eq!(unsafe { libc::kill(pid as libc::pid_t, 0) }, -1);
+    }
 }
@@ -1,9 +1,11 @@
 //! Show the slowest tests
 
-use crate::commands::utils::open_repository;
+use crate::commands::utils::{load_selected_runs, open_repository_with};
 use crate::commands::Command;
 use crate::error::Result;
+use crate::repository::RepositoryFactory;
 use crate::ui::UI;
+use std::sync::Arc;
 
 /// Command to show the slowest tests from the last run.
 ///
@@ -11,7 +13,9 @@ use crate::ui::UI;
 /// with timing information and percentage of total time.
 pub struct SlowestCommand {
     base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
     count: usize,
+    run: Option<String>,
 }
 
 impl SlowestCommand {
@@ -22,7 +26,9 @@ impl SlowestCommand {
     pub fn new(base_path: Option<String>) -> Self {
         SlowestCommand {
             base_path,
+            repository_factory: None,
             count: 10, // Default to top 10
+            run: None,
         }
     }
 
@@ -32,14 +38,44 @@ impl SlowestCommand {
     /// * `base_path` - Optional base directory path for the repository
     /// * `count` - Number of slowest tests to show
     pub fn with_count(base_path: Option<String>, count: usize) -> Self {
-        SlowestCommand { base_path, count }
+        SlowestCommand {
+            base_path,
+            repository_factory: None,
+            count,
+            run: None,
+        }
+    }
+
+    /// Use the timings from the runs named by a run spec instead of the
+    /// latest run.
+    ///
+    /// # Arguments
+    /// * `run` - Run spec naming a run or a range of runs (see
+    ///   [`crate::repository::run_spec`])
+    pub fn for_run(mut self, run: Option<String>) -> Self {
+        self.run = run;
+        self
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
     }
 }
 
 impl Command for SlowestCommand {
     fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
-        let repo = open_repository(self.base_path.as_deref())?;
-        let test_run = repo.get_latest_run()?;
+        let repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+        let test_run = load_selected_runs(repo.as_ref(), self.run.as_deref())?;
 
         // Collect tests with durations
         let mut tests_with_duration: Vec<_> = test_run
@@ -87,7 +123,7 @@ impl Command for SlowestCommand {
     }
 
     fn help(&self) -> &str {
-        "Show the slowest tests from the last run"
+        "Show the slowest tests from the last run, or other runs"
     }
 }
 
@@ -182,4 +218,25 @@ mod tests {
         assert!(ui.output[1].contains("slow_test"));
         assert!(ui.output[2].contains("medium_test"));
     }
+
+    #[test]
+    fn test_slowest_command_for_run() {
+        let temp = TempDir::new().unwrap();
+
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+        for (id, millis) in [("0", 3000), ("1", 10)] {
+            let mut test_run = TestRun::new(id.to_string());
+            test_run.add_result(
+                TestResult::success("test1").with_duration(Duration::from_millis(millis)),
+            );
+            repo.insert_test_run(test_run).unwrap();
+        }
+
+        let mut ui = TestUI::new();
+        let cmd = SlowestCommand::new(Some(temp.path().to_string_lossy().to_string()))
+            .for_run(Some("-2".to_string()));
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(ui.output[1], "  3.000s (100.0%) - test1");
+    }
 }
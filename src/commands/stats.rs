@@ -1,8 +1,12 @@
 //! Show repository statistics
 
+use crate::commands::utils::open_repository_with;
 use crate::commands::Command;
 use crate::error::Result;
+use crate::repository::run_spec::resolve_runs;
+use crate::repository::RepositoryFactory;
 use crate::ui::UI;
+use std::sync::Arc;
 
 /// Command to display repository statistics.
 ///
@@ -10,6 +14,8 @@ use crate::ui::UI;
 /// latest run details, and aggregate test counts.
 pub struct StatsCommand {
     base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    run: Option<String>,
 }
 
 impl StatsCommand {
@@ -18,37 +24,87 @@ impl StatsCommand {
     /// # Arguments
     /// * `base_path` - Optional base directory path for the repository
     pub fn new(base_path: Option<String>) -> Self {
-        StatsCommand { base_path }
+        StatsCommand {
+            base_path,
+            repository_factory: None,
+            run: None,
+        }
+    }
+
+    /// Only count the runs named by a run spec.
+    ///
+    /// # Arguments
+    /// * `run` - Run spec naming a run or a range of runs (see
+    ///   [`crate::repository::run_spec`])
+    pub fn for_run(mut self, run: Option<String>) -> Self {
+        self.run = run;
+        self
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
     }
 }
 
 impl Command for StatsCommand {
     fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
-        let repo = super::utils::open_repository(self.base_path.as_deref())?;
+        let repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
 
-        let run_count = repo.count()?;
-        let run_ids = repo.list_run_ids()?;
+        let filter_tags = super::utils::load_filter_tags(self.base_path.as_deref())?;
+
+        let (run_count, run_ids) = match &self.run {
+            Some(spec) => {
+                let run_ids = resolve_runs(repo.as_ref(), spec)?;
+                (run_ids.len(), run_ids)
+            }
+            None => (repo.count()?, repo.list_run_ids()?),
+        };
 
         ui.output("Repository Statistics:")?;
         ui.output(&format!("  Total test runs: {}", run_count))?;
 
-        if !run_ids.is_empty() {
-            let latest_run = repo.get_latest_run()?;
+        if let Some(last_id) = run_ids.last() {
+            let latest_run = match self.run {
+                Some(_) => repo.get_test_run(last_id)?,
+                None => repo.get_latest_run()?,
+            };
+            // Tests tagged with filter_tags are only counted when they fail
             ui.output(&format!("  Latest run: {}", latest_run.id))?;
             ui.output(&format!(
                 "  Tests in latest run: {}",
-                latest_run.total_tests()
+                latest_run.hide_filtered_tags(&filter_tags).total_tests()
             ))?;
             ui.output(&format!(
                 "  Failures in latest run: {}",
                 latest_run.count_failures()
             ))?;
 
+            let metadata = repo.get_run_metadata(&latest_run.id)?;
+            if let Some(start_time) = metadata.start_time {
+                ui.output(&format!("  Latest run started: {}", start_time))?;
+            }
+            if let Some(duration) = metadata.duration() {
+                ui.output(&format!(
+                    "  Latest run wall time: {:.3}s",
+                    duration.as_secs_f64()
+                ))?;
+            }
+
             // Calculate total tests across all runs
             let mut total_tests = 0;
             for run_id in &run_ids {
                 if let Ok(run) = repo.get_test_run(run_id) {
-                    total_tests += run.total_tests();
+                    total_tests += run.hide_filtered_tags(&filter_tags).total_tests();
                 }
             }
             ui.output(&format!("  Total tests executed: {}", total_tests))?;
@@ -69,7 +125,7 @@ impl Command for StatsCommand {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::repository::{TestId, TestResult, TestRun, TestStatus};
+    use crate::repository::{RunMetadata, TestId, TestResult, TestRun, TestStatus};
     use crate::ui::test_ui::TestUI;
     use tempfile::TempDir;
 
@@ -127,4 +183,56 @@ mod tests {
         assert_eq!(ui.output[4], "  Failures in latest run: 0");
         assert_eq!(ui.output[5], "  Total tests executed: 2");
     }
+
+    #[test]
+    fn test_stats_command_shows_latest_run_times() {
+        let temp = TempDir::new().unwrap();
+
+        let mut repo =
+            super::super::utils::init_repository(Some(&temp.path().to_string_lossy())).unwrap();
+        let run_id = repo.insert_test_run(TestRun::new("0".to_string())).unwrap();
+        repo.set_run_metadata(
+            &run_id,
+            &RunMetadata {
+                start_time: chrono::DateTime::from_timestamp(1000000000, 0),
+                end_time: chrono::DateTime::from_timestamp(1000000090, 0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut ui = TestUI::new();
+        let cmd = StatsCommand::new(Some(temp.path().to_string_lossy().to_string()));
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(
+            ui.output[5],
+            "  Latest run started: 2001-09-09 01:46:40 UTC"
+        );
+        assert_eq!(ui.output[6], "  Latest run wall time: 90.000s");
+        assert_eq!(ui.output[7], "  Total tests executed: 0");
+    }
+
+    #[test]
+    fn test_stats_command_for_runs() {
+        let temp = TempDir::new().unwrap();
+
+        let mut repo =
+            super::super::utils::init_repository(Some(&temp.path().to_string_lossy())).unwrap();
+        for i in 0..4 {
+            let mut test_run = TestRun::new(i.to_string());
+            for j in 0..=i {
+                test_run.add_result(TestResult::success(format!("test{}", j)));
+            }
+            repo.insert_test_run(test_run).unwrap();
+        }
+
+        let mut ui = TestUI::new();
+        let cmd = StatsCommand::new(Some(temp.path().to_string_lossy().to_string()))
+            .for_run(Some("1..-2".to_string()));
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(ui.output[1], "  Total test runs: 2");
+        assert_eq!(ui.output[2], "  Latest run: 2");
+        assert_eq!(ui.output[3], "  Tests in latest run: 3");
+        assert_eq!(ui.output[5], "  Total tests executed: 5");
+    }
 }
@@ -0,0 +1,205 @@
+//! Dump the test times database
+
+use crate::commands::utils::open_repository_with;
+use crate::commands::Command;
+use crate::error::{Error, Result};
+use crate::repository::RepositoryFactory;
+use crate::ui::UI;
+use regex::Regex;
+use std::sync::Arc;
+
+/// Order in which the times command lists tests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimesSort {
+    /// Alphabetically by test ID
+    Name,
+    /// Slowest test first
+    Time,
+}
+
+/// Command to dump the recorded duration of every test in the repository.
+///
+/// Unlike `slowest`, which only looks at the last run, this reads the times
+/// database that is used to partition tests for parallel runs.
+pub struct TimesCommand {
+    base_path: Option<String>,
+    repository_factory: Option<Arc<dyn RepositoryFactory + Send + Sync>>,
+    filters: Vec<String>,
+    sort: TimesSort,
+}
+
+impl TimesCommand {
+    /// Creates a times command listing every test by name.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    pub fn new(base_path: Option<String>) -> Self {
+        TimesCommand {
+            base_path,
+            repository_factory: None,
+            filters: Vec::new(),
+            sort: TimesSort::Name,
+        }
+    }
+
+    /// Creates a times command with filters and a sort order.
+    ///
+    /// # Arguments
+    /// * `base_path` - Optional base directory path for the repository
+    /// * `filters` - Regexes; only tests matching any of them are listed
+    /// * `sort` - Order in which to list tests
+    pub fn with_options(base_path: Option<String>, filters: Vec<String>, sort: TimesSort) -> Self {
+        TimesCommand {
+            base_path,
+            repository_factory: None,
+            filters,
+            sort,
+        }
+    }
+
+    /// Open the repository through a factory instead of from disk.
+    ///
+    /// # Arguments
+    /// * `factory` - Factory holding the repository, such as a
+    ///   [`MemoryRepositoryFactory`](crate::repository::memory::MemoryRepositoryFactory)
+    pub fn with_repository_factory(
+        mut self,
+        factory: Arc<dyn RepositoryFactory + Send + Sync>,
+    ) -> Self {
+        self.repository_factory = Some(factory);
+        self
+    }
+}
+
+impl Command for TimesCommand {
+    fn execute(&self, ui: &mut dyn UI) -> Result<i32> {
+        let repo =
+            open_repository_with(self.repository_factory.as_ref(), self.base_path.as_deref())?;
+
+        let filters = self
+            .filters
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    Error::Config(format!("Invalid test filter regex '{}': {}", pattern, e))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let times = repo.get_test_times()?;
+        if times.is_empty() {
+            ui.output("No timing information available")?;
+            return Ok(0);
+        }
+
+        let mut entries: Vec<_> = times
+            .into_iter()
+            .filter(|(test_id, _)| {
+                filters.is_empty() || filters.iter().any(|re| re.is_match(test_id.as_str()))
+            })
+            .collect();
+
+        match self.sort {
+            TimesSort::Name => entries.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str())),
+            TimesSort::Time => {
+                entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.as_str().cmp(b.0.as_str())))
+            }
+        }
+
+        for (test_id, duration) in entries {
+            ui.output(&format!("{:.6} {}", duration.as_secs_f64(), test_id))?;
+        }
+
+        Ok(0)
+    }
+
+    fn name(&self) -> &str {
+        "times"
+    }
+
+    fn help(&self) -> &str {
+        "Show the recorded duration of every test"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::TestId;
+    use crate::ui::test_ui::TestUI;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn setup_repo() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        let mut repo =
+            super::super::utils::init_repository(Some(&temp.path().to_string_lossy())).unwrap();
+
+        let mut times = HashMap::new();
+        times.insert(TestId::new("pkg.test_b"), Duration::from_secs_f64(0.5));
+        times.insert(TestId::new("pkg.test_a"), Duration::from_secs_f64(2.0));
+        times.insert(TestId::new("other.test_c"), Duration::from_secs_f64(1.0));
+        repo.update_test_times(&times).unwrap();
+
+        temp
+    }
+
+    #[test]
+    fn test_times_command_empty() {
+        let temp = TempDir::new().unwrap();
+        super::super::utils::init_repository(Some(&temp.path().to_string_lossy())).unwrap();
+
+        let mut ui = TestUI::new();
+        let cmd = TimesCommand::new(Some(temp.path().to_string_lossy().to_string()));
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(ui.output, vec!["No timing information available"]);
+    }
+
+    #[test]
+    fn test_times_command_sorted_by_name() {
+        let temp = setup_repo();
+
+        let mut ui = TestUI::new();
+        let cmd = TimesCommand::new(Some(temp.path().to_string_lossy().to_string()));
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(
+            ui.output,
+            vec![
+                "1.000000 other.test_c",
+                "2.000000 pkg.test_a",
+                "0.500000 pkg.test_b",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_times_command_filtered_by_time() {
+        let temp = setup_repo();
+
+        let mut ui = TestUI::new();
+        let cmd = TimesCommand::with_options(
+            Some(temp.path().to_string_lossy().to_string()),
+            vec!["^pkg\\.".to_string()],
+            TimesSort::Time,
+        );
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 0);
+        assert_eq!(
+            ui.output,
+            vec!["2.000000 pkg.test_a", "0.500000 pkg.test_b"]
+        );
+    }
+
+    #[test]
+    fn test_times_command_invalid_filter() {
+        let temp = setup_repo();
+
+        let mut ui = TestUI::new();
+        let cmd = TimesCommand::with_options(
+            Some(temp.path().to_string_lossy().to_string()),
+            vec!["(".to_string()],
+            TimesSort::Name,
+        );
+        assert!(cmd.execute(&mut ui).is_err());
+    }
+}
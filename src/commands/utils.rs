@@ -1,25 +1,95 @@
 //! Utility functions for command implementation
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::repository::file::FileRepositoryFactory;
-use crate::repository::{Repository, RepositoryFactory, TestRun};
+use crate::repository::run_spec::resolve_runs;
+use crate::repository::{find_root, Repository, RepositoryFactory, TestRun};
+use crate::testcommand::TestCommand;
 use crate::ui::UI;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-/// Open a repository at the given path (or current directory if None)
+/// Find the project root for the given path (or current directory if None)
+///
+/// See [`find_root`] for how the root is found.
+pub fn project_root(base_path: Option<&str>) -> PathBuf {
+    find_root(base_path.map(Path::new).unwrap_or_else(|| Path::new(".")))
+}
+
+/// Open the repository in the project root containing the given path (or
+/// current directory if None)
 pub fn open_repository(base_path: Option<&str>) -> Result<Box<dyn Repository>> {
-    let base = base_path.map(Path::new).unwrap_or_else(|| Path::new("."));
+    crate::repository::open_repository(&project_root(base_path))
+}
 
+/// Initialize a repository in the project root containing the given path (or
+/// current directory if None)
+pub fn init_repository(base_path: Option<&str>) -> Result<Box<dyn Repository>> {
     let factory = FileRepositoryFactory;
-    factory.open(base)
+    factory.initialise(&project_root(base_path))
 }
 
-/// Initialize a repository at the given path (or current directory if None)
-pub fn init_repository(base_path: Option<&str>) -> Result<Box<dyn Repository>> {
-    let base = base_path.map(Path::new).unwrap_or_else(|| Path::new("."));
+/// Open a command's repository through the factory it was given, if any,
+/// rather than from disk
+pub fn open_repository_with(
+    factory: Option<&Arc<dyn RepositoryFactory + Send + Sync>>,
+    base_path: Option<&str>,
+) -> Result<Box<dyn Repository>> {
+    match factory {
+        Some(factory) => factory.open(&project_root(base_path)),
+        None => open_repository(base_path),
+    }
+}
 
-    let factory = FileRepositoryFactory;
-    factory.initialise(base)
+/// Initialize a command's repository through the factory it was given, if
+/// any, rather than on disk
+pub fn init_repository_with(
+    factory: Option<&Arc<dyn RepositoryFactory + Send + Sync>>,
+    base_path: Option<&str>,
+) -> Result<Box<dyn Repository>> {
+    match factory {
+        Some(factory) => factory.initialise(&project_root(base_path)),
+        None => init_repository(base_path),
+    }
+}
+
+/// Load the `filter_tags` configured in .testr.conf, if there is one
+///
+/// Commands that only read the repository do not need a configuration file,
+/// so a missing .testr.conf yields an empty list rather than an error.
+pub fn load_filter_tags(base_path: Option<&str>) -> Result<Vec<String>> {
+    let root = project_root(base_path);
+
+    if !root.join(".testr.conf").exists() {
+        return Ok(Vec::new());
+    }
+    Ok(TestCommand::from_directory(&root)?.get_filter_tags())
+}
+
+/// Load the runs selected by a run spec as a single run, or the latest run
+/// if there is no spec
+///
+/// When several runs are selected, each test keeps its result from the most
+/// recent of them that ran it.
+pub fn load_selected_runs(repo: &dyn Repository, run_spec: Option<&str>) -> Result<TestRun> {
+    let Some(spec) = run_spec else {
+        return repo.get_latest_run();
+    };
+
+    let run_ids = resolve_runs(repo, spec)?;
+    match run_ids.as_slice() {
+        [] => Err(Error::TestRunNotFound(spec.to_string())),
+        [run_id] => repo.get_test_run(run_id),
+        [first, .., last] => {
+            let mut combined = TestRun::new(format!("{}..{}", first, last));
+            for run_id in &run_ids {
+                let run = repo.get_test_run(run_id)?;
+                combined.timestamp = run.timestamp;
+                combined.results.extend(run.results);
+            }
+            Ok(combined)
+        }
+    }
 }
 
 /// Extract test durations from a test run and update the repository's times database
@@ -57,6 +127,59 @@ pub fn update_repository_failing_tests(
     Ok(())
 }
 
+/// Delete runs that fall outside the given retention limits
+///
+/// A run is deleted when it is not among the `keep_runs` most recent runs or
+/// was stored more than `keep_days` days ago. The most recent run is always
+/// kept. Returns the IDs of the deleted runs, or with `dry_run` the IDs of
+/// the runs that would be deleted.
+pub fn prune_runs(
+    repo: &mut Box<dyn Repository>,
+    keep_runs: Option<usize>,
+    keep_days: Option<u64>,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let run_ids = repo.list_run_ids()?;
+    let total = run_ids.len();
+    let cutoff = keep_days.map(|days| chrono::Utc::now() - chrono::Duration::days(days as i64));
+
+    // The most recent run is never a candidate
+    let candidates = total.saturating_sub(1);
+
+    let mut pruned = Vec::new();
+    for (index, run_id) in run_ids.into_iter().enumerate().take(candidates) {
+        let too_many = keep_runs.is_some_and(|keep| index + keep < total);
+        let too_old = match cutoff {
+            Some(cutoff) => repo.get_run_time(&run_id)? < cutoff,
+            None => false,
+        };
+
+        if too_many || too_old {
+            if !dry_run {
+                repo.delete_run(&run_id)?;
+            }
+            pruned.push(run_id);
+        }
+    }
+
+    Ok(pruned)
+}
+
+/// Prune runs using the repo.conf retention limits if auto_prune is enabled
+pub fn auto_prune(ui: &mut dyn UI, repo: &mut Box<dyn Repository>) -> Result<()> {
+    let config = repo.get_config()?;
+    if config.auto_prune != Some(true) || (config.keep_runs.is_none() && config.keep_days.is_none())
+    {
+        return Ok(());
+    }
+
+    let pruned = prune_runs(repo, config.keep_runs, config.keep_days, false)?;
+    if !pruned.is_empty() {
+        ui.output(&format!("Pruned {} old run(s)", pruned.len()))?;
+    }
+    Ok(())
+}
+
 /// Display a test run summary
 pub fn display_test_summary(ui: &mut dyn UI, run_id: &str, test_run: &TestRun) -> Result<()> {
     let total = test_run.total_tests();
@@ -71,6 +194,25 @@ pub fn display_test_summary(ui: &mut dyn UI, run_id: &str, test_run: &TestRun) -
     Ok(())
 }
 
+/// Quote a string for inclusion in JSON output
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -103,4 +245,96 @@ mod tests {
         let result = open_repository(Some(&path));
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_open_repository_from_subdirectory() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().to_string_lossy().to_string();
+        let mut repo = init_repository(Some(&path)).unwrap();
+        repo.insert_test_run(TestRun::new("0".to_string())).unwrap();
+
+        let subdir = temp.path().join("src").join("module");
+        std::fs::create_dir_all(&subdir).unwrap();
+        let subdir = subdir.to_string_lossy().to_string();
+
+        assert_eq!(project_root(Some(&subdir)), temp.path());
+        let repo = open_repository(Some(&subdir)).unwrap();
+        assert_eq!(repo.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_prune_runs() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().to_string_lossy().to_string();
+        let mut repo = init_repository(Some(&path)).unwrap();
+
+        for i in 0..5 {
+            repo.insert_test_run(TestRun::new(i.to_string())).unwrap();
+        }
+
+        // Dry runs report without deleting
+        let pruned = prune_runs(&mut repo, Some(3), None, true).unwrap();
+        assert_eq!(pruned, vec!["0", "1"]);
+        assert_eq!(repo.count().unwrap(), 5);
+
+        let pruned = prune_runs(&mut repo, Some(3), None, false).unwrap();
+        assert_eq!(pruned, vec!["0", "1"]);
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["2", "3", "4"]);
+
+        // Every run is older than zero days, but the latest one is kept
+        let pruned = prune_runs(&mut repo, None, Some(0), false).unwrap();
+        assert_eq!(pruned, vec!["2", "3"]);
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["4"]);
+    }
+
+    #[test]
+    fn test_auto_prune_requires_opt_in() {
+        use crate::ui::test_ui::TestUI;
+
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().to_string_lossy().to_string();
+        let mut repo = init_repository(Some(&path)).unwrap();
+        for i in 0..3 {
+            repo.insert_test_run(TestRun::new(i.to_string())).unwrap();
+        }
+
+        let mut config = repo.get_config().unwrap();
+        config.keep_runs = Some(1);
+        repo.set_config(&config).unwrap();
+
+        let mut ui = TestUI::new();
+        auto_prune(&mut ui, &mut repo).unwrap();
+        assert_eq!(repo.count().unwrap(), 3);
+
+        config.auto_prune = Some(true);
+        repo.set_config(&config).unwrap();
+        auto_prune(&mut ui, &mut repo).unwrap();
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["2"]);
+        assert_eq!(ui.output, vec!["Pruned 2 old run(s)"]);
+    }
+
+    #[test]
+    fn test_load_filter_tags() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().to_string_lossy().to_string();
+
+        // No configuration file means no filtering
+        assert!(load_filter_tags(Some(&path)).unwrap().is_empty());
+
+        std::fs::write(
+            temp.path().join(".testr.conf"),
+            "[DEFAULT]\ntest_command=true\nfilter_tags=zope:layer\n",
+        )
+        .unwrap();
+        assert_eq!(load_filter_tags(Some(&path)).unwrap(), vec!["zope:layer"]);
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("test.a"), "\"test.a\"");
+        assert_eq!(
+            json_string("say \"hi\"\\\n\u{1}"),
+            "\"say \\\"hi\\\"\\\\\\n\\u0001\""
+        );
+    }
 }
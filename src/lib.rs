@@ -15,7 +15,7 @@
 //!
 //! The library is organized into several key modules:
 //!
-//! - [`repository`]: Core repository trait and file-based implementation for storing test results
+//! - [`repository`]: Core repository trait and file-based, SQLite-backed and in-memory implementations
 //! - [`commands`]: All user-facing commands (init, run, load, last, failing, stats, slowest, list-tests)
 //! - [`subunit_stream`]: Subunit v2 protocol parsing and generation
 //! - [`config`]: .testr.conf configuration file parsing
@@ -4,6 +4,7 @@ use clap::{Parser, Subcommand};
 use std::io::Write;
 use testrepository::commands::*;
 use testrepository::error::Result;
+use testrepository::test_runner::{parse_seconds, TestTimeout};
 use testrepository::ui::UI;
 
 // Explicit imports for commands not covered by wildcard
@@ -15,7 +16,7 @@ use testrepository::commands::AnalyzeIsolationCommand;
 #[command(version)]
 #[command(disable_help_subcommand = true)]
 struct Cli {
-    /// Repository path (defaults to current directory)
+    /// Directory to start looking for the repository in (defaults to current directory)
     #[arg(short = 'C', long, global = true)]
     directory: Option<String>,
 
@@ -26,7 +27,16 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize a new test repository
-    Init,
+    Init {
+        /// Kind of repository to create
+        #[arg(
+            long = "type",
+            value_name = "TYPE",
+            default_value = "file",
+            value_parser = ["file", "sql"]
+        )]
+        repo_type: String,
+    },
 
     /// Show help information for commands
     Help {
@@ -57,6 +67,10 @@ enum Commands {
         /// Don't show test output/tracebacks for failed tests
         #[arg(long)]
         no_output: bool,
+
+        /// Run to show, e.g. 17, -2, last~1 or a bookmark (defaults to the latest run)
+        #[arg(value_name = "RUN", allow_hyphen_values = true)]
+        run: Option<String>,
     },
 
     /// Show failing tests from the last run
@@ -68,10 +82,38 @@ enum Commands {
         /// Show output as a subunit stream
         #[arg(long)]
         subunit: bool,
+
+        /// Runs to show the failures of, e.g. -1 or 10..15 (defaults to the
+        /// repository's current failing tests)
+        #[arg(value_name = "RUN", allow_hyphen_values = true)]
+        run: Option<String>,
+    },
+
+    /// Compare two test runs
+    Diff {
+        /// Old run (defaults to the run before the latest)
+        #[arg(value_name = "A", allow_hyphen_values = true)]
+        old_run: Option<String>,
+
+        /// New run (defaults to the latest run)
+        #[arg(value_name = "B", allow_hyphen_values = true)]
+        new_run: Option<String>,
+
+        /// Report tests that got slower by more than this percentage
+        #[arg(long, value_name = "PERCENT", default_value_t = diff::DEFAULT_THRESHOLD)]
+        threshold: f64,
+
+        /// Output the differences as JSON
+        #[arg(long)]
+        json: bool,
     },
 
     /// Show repository statistics
-    Stats,
+    Stats {
+        /// Only count these runs, e.g. 10..15 or good..
+        #[arg(value_name = "RUN", allow_hyphen_values = true)]
+        run: Option<String>,
+    },
 
     /// Show the slowest tests from the last run
     #[command(name = "slowest")]
@@ -83,6 +125,35 @@ enum Commands {
         /// Show all tests (not just top N)
         #[arg(long)]
         all: bool,
+
+        /// Runs to take timings from, e.g. -2 or 10..15 (defaults to the latest run)
+        #[arg(value_name = "RUN", allow_hyphen_values = true)]
+        run: Option<String>,
+    },
+
+    /// Show the recorded duration of every test
+    Times {
+        /// Sort by duration (slowest first) instead of by test ID
+        #[arg(long)]
+        by_time: bool,
+
+        /// Only show tests matching any of these regexes
+        #[arg(value_name = "TESTFILTER")]
+        testfilters: Vec<String>,
+    },
+
+    /// Show tests whose outcome flips between runs of the same code
+    Flaky {
+        /// Only scan these runs, e.g. -20.. (defaults to every stored run)
+        #[arg(value_name = "RUN", allow_hyphen_values = true)]
+        run: Option<String>,
+    },
+
+    /// Show how tests behaved across stored runs
+    History {
+        /// Test ID, or regex matching the tests to show
+        #[arg(value_name = "TEST")]
+        pattern: String,
     },
 
     /// List all available tests
@@ -96,6 +167,67 @@ enum Commands {
         test: String,
     },
 
+    /// Check the repository for damage
+    Check {
+        /// Repair the problems found
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Rebuild the failing tests and times database from stored runs
+    Rebuild {
+        /// First run to replay
+        #[arg(long, value_name = "RUN")]
+        from: Option<u64>,
+
+        /// Last run to replay
+        #[arg(long, value_name = "RUN")]
+        to: Option<u64>,
+    },
+
+    /// Delete old test runs from the repository
+    Prune {
+        /// Keep this many of the most recent runs
+        #[arg(long, value_name = "N")]
+        keep: Option<usize>,
+
+        /// Keep runs stored within this many days
+        #[arg(long, value_name = "DAYS")]
+        max_age: Option<u64>,
+
+        /// Show which runs would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// List, set or delete names for test runs
+    Bookmark {
+        /// Bookmark to set or delete (lists all bookmarks if omitted)
+        name: Option<String>,
+
+        /// Run to point the bookmark at (defaults to the latest run)
+        #[arg(value_name = "RUN", allow_hyphen_values = true)]
+        run: Option<String>,
+
+        /// Delete the bookmark
+        #[arg(long, short = 'd', requires = "name", conflicts_with = "run")]
+        delete: bool,
+    },
+
+    /// Show or change repository settings (repo.conf)
+    #[command(name = "repo-config")]
+    RepoConfig {
+        /// Setting to show or change (shows all settings if omitted)
+        key: Option<String>,
+
+        /// New value for the setting
+        value: Option<String>,
+
+        /// Remove the setting
+        #[arg(long, requires = "key", conflicts_with = "value")]
+        unset: bool,
+    },
+
     /// Run tests and load results
     Run {
         /// Run only the tests that failed in the last run
@@ -134,6 +266,52 @@ enum Commands {
         #[arg(long)]
         all_output: bool,
 
+        /// Re-run failing tests up to N more times, reporting those that then pass as flaky
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 0,
+            conflicts_with = "subunit"
+        )]
+        retry: usize,
+
+        /// Whether a test that passes on retry counts as passing ("pass") or still failing ("fail")
+        #[arg(
+            long,
+            value_name = "POLICY",
+            default_value = "pass",
+            value_parser = ["pass", "fail"]
+        )]
+        flaky_policy: String,
+
+        /// Stop the run, terminating all test processes, after the first failure
+        #[arg(long, conflicts_with_all = ["subunit", "max_failures"])]
+        fail_fast: bool,
+
+        /// Stop the run, terminating all test processes, after N failures (0 for no limit)
+        #[arg(long, value_name = "N", conflicts_with = "subunit")]
+        max_failures: Option<usize>,
+
+        /// Terminate a test that runs longer than this: seconds, or a multiple of its recorded time such as 5x
+        #[arg(long, value_name = "TIMEOUT", conflicts_with = "subunit")]
+        test_timeout: Option<String>,
+
+        /// Terminate the whole run after this many seconds
+        #[arg(long, value_name = "SECONDS", conflicts_with = "subunit")]
+        run_timeout: Option<String>,
+
+        /// Run the tests a timed-out test process never got to in a new process
+        #[arg(long, requires = "test_timeout")]
+        reschedule_timeouts: bool,
+
+        /// Hand tests to parallel workers in batches as they become free instead of partitioning them up front
+        #[arg(long)]
+        dynamic: bool,
+
+        /// Maximum number of tests in a batch with --dynamic (defaults to about four batches per worker)
+        #[arg(long, value_name = "N", requires = "dynamic")]
+        batch_size: Option<usize>,
+
         /// Test ID filters (regex patterns to filter which tests to run)
         #[arg(value_name = "TESTFILTER")]
         testfilters: Vec<String>,
@@ -170,10 +348,10 @@ fn main() {
     let mut ui = CliUI;
 
     let result = match cli.command {
-        Commands::Init => {
-            let cmd = InitCommand::new(cli.directory);
+        Commands::Init { repo_type } => repo_type.parse().and_then(|repo_type| {
+            let cmd = InitCommand::with_type(cli.directory, repo_type);
             cmd.execute(&mut ui)
-        }
+        }),
         Commands::Help { command } => {
             let cmd = HelpCommand::new(command);
             cmd.execute(&mut ui)
@@ -189,7 +367,11 @@ fn main() {
             let cmd = LoadCommand::with_partial(cli.directory, partial, force_init);
             cmd.execute(&mut ui)
         }
-        Commands::Last { subunit, no_output } => {
+        Commands::Last {
+            subunit,
+            no_output,
+            run,
+        } => {
             let cmd = if subunit {
                 LastCommand::with_subunit(cli.directory)
             } else if no_output {
@@ -197,9 +379,9 @@ fn main() {
             } else {
                 LastCommand::new(cli.directory)
             };
-            cmd.execute(&mut ui)
+            cmd.for_run(run).execute(&mut ui)
         }
-        Commands::Failing { list, subunit } => {
+        Commands::Failing { list, subunit, run } => {
             let cmd = if subunit {
                 FailingCommand::with_subunit(cli.directory)
             } else if list {
@@ -207,15 +389,44 @@ fn main() {
             } else {
                 FailingCommand::new(cli.directory)
             };
+            cmd.for_run(run).execute(&mut ui)
+        }
+        Commands::Diff {
+            old_run,
+            new_run,
+            threshold,
+            json,
+        } => {
+            let cmd = DiffCommand::with_options(cli.directory, old_run, new_run, threshold, json);
             cmd.execute(&mut ui)
         }
-        Commands::Stats => {
-            let cmd = StatsCommand::new(cli.directory);
+        Commands::Stats { run } => {
+            let cmd = StatsCommand::new(cli.directory).for_run(run);
             cmd.execute(&mut ui)
         }
-        Commands::Slowest { count, all } => {
+        Commands::Slowest { count, all, run } => {
             let display_count = if all { usize::MAX } else { count };
-            let cmd = SlowestCommand::with_count(cli.directory, display_count);
+            let cmd = SlowestCommand::with_count(cli.directory, display_count).for_run(run);
+            cmd.execute(&mut ui)
+        }
+        Commands::Times {
+            by_time,
+            testfilters,
+        } => {
+            let sort = if by_time {
+                TimesSort::Time
+            } else {
+                TimesSort::Name
+            };
+            let cmd = TimesCommand::with_options(cli.directory, testfilters, sort);
+            cmd.execute(&mut ui)
+        }
+        Commands::Flaky { run } => {
+            let cmd = FlakyCommand::new(cli.directory).for_run(run);
+            cmd.execute(&mut ui)
+        }
+        Commands::History { pattern } => {
+            let cmd = HistoryCommand::new(cli.directory, pattern);
             cmd.execute(&mut ui)
         }
         Commands::ListTests => {
@@ -226,6 +437,41 @@ fn main() {
             let cmd = AnalyzeIsolationCommand::new(cli.directory, test);
             cmd.execute(&mut ui)
         }
+        Commands::Check { repair } => {
+            let cmd = CheckCommand::with_repair(cli.directory, repair);
+            cmd.execute(&mut ui)
+        }
+        Commands::Rebuild { from, to } => {
+            let cmd = RebuildCommand::with_range(cli.directory, from, to);
+            cmd.execute(&mut ui)
+        }
+        Commands::Prune {
+            keep,
+            max_age,
+            dry_run,
+        } => {
+            let cmd = PruneCommand::with_options(cli.directory, keep, max_age, dry_run);
+            cmd.execute(&mut ui)
+        }
+        Commands::Bookmark { name, run, delete } => {
+            let cmd = match name {
+                Some(name) if delete => BookmarkCommand::with_delete(cli.directory, name),
+                Some(name) => BookmarkCommand::with_set(cli.directory, name, run),
+                None => BookmarkCommand::new(cli.directory),
+            };
+            cmd.execute(&mut ui)
+        }
+        Commands::RepoConfig { key, value, unset } => {
+            let cmd = match (key, value) {
+                (Some(key), _) if unset => RepoConfigCommand::with_set(cli.directory, key, None),
+                (Some(key), Some(value)) => {
+                    RepoConfigCommand::with_set(cli.directory, key, Some(value))
+                }
+                (Some(key), None) => RepoConfigCommand::with_get(cli.directory, key),
+                (None, _) => RepoConfigCommand::new(cli.directory),
+            };
+            cmd.execute(&mut ui)
+        }
         Commands::Run {
             failing,
             force_init,
@@ -236,9 +482,23 @@ fn main() {
             isolated,
             subunit,
             all_output,
+            retry,
+            flaky_policy,
+            fail_fast,
+            max_failures,
+            test_timeout,
+            run_timeout,
+            reschedule_timeouts,
+            dynamic,
+            batch_size,
             testfilters,
             testargs,
-        } => {
+        } => flaky_policy.parse().and_then(|flaky_policy| {
+            let test_timeout = test_timeout
+                .as_deref()
+                .map(str::parse::<TestTimeout>)
+                .transpose()?;
+            let run_timeout = run_timeout.as_deref().map(parse_seconds).transpose()?;
             let cmd = RunCommand::with_all_options(
                 cli.directory,
                 partial,
@@ -260,9 +520,13 @@ fn main() {
                 } else {
                     Some(testargs)
                 },
-            );
+            )
+            .with_retry(retry, flaky_policy)
+            .with_max_failures(max_failures.or(fail_fast.then_some(1)).filter(|&n| n > 0))
+            .with_timeouts(test_timeout, run_timeout, reschedule_timeouts)
+            .with_dynamic_schedule(dynamic, batch_size);
             cmd.execute(&mut ui)
-        }
+        }),
     };
 
     match result {
@@ -1,13 +1,30 @@
 //! Test partitioning for parallel execution
 //!
 //! This module provides functions to partition tests across multiple workers
-//! based on their historical durations to balance the load.
+//! based on their historical durations to balance the load, or to split them
+//! into batches that are handed to whichever worker is free next. The
+//! durations used are estimated from each test's recent durations by
+//! [`estimate_durations`].
 
 use crate::grouping::group_tests;
-use crate::repository::TestId;
-use std::collections::HashMap;
+use crate::repository::{TestId, TestTiming, TimeEstimator};
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 
+/// Remove tests that must not be scheduled explicitly
+///
+/// `tagged` holds the IDs of tests known to carry one of the configured
+/// `filter_tags`. Such tests are usually pseudo-tests (layer setup and the
+/// like) that the test runner executes implicitly, so scheduling them is
+/// pointless.
+pub fn remove_filtered_tests(test_ids: &[TestId], tagged: &HashSet<&TestId>) -> Vec<TestId> {
+    test_ids
+        .iter()
+        .filter(|id| !tagged.contains(id))
+        .cloned()
+        .collect()
+}
+
 /// Partition tests into groups for parallel execution
 ///
 /// Tests are partitioned to balance the expected runtime across workers.
@@ -151,10 +168,162 @@ pub fn partition_tests_with_grouping(
     Ok(partitions.into_iter().map(|(ids, _)| ids).collect())
 }
 
+/// The median of some durations, or `None` if there are none
+fn median(mut durations: Vec<Duration>) -> Option<Duration> {
+    durations.sort();
+    let mid = durations.len() / 2;
+    match durations.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(durations[mid]),
+        _ => Some((durations[mid - 1] + durations[mid]) / 2),
+    }
+}
+
+/// Estimate how long each test will take
+///
+/// Tests with recorded durations are estimated by `estimator`. Any other
+/// test is expected to take the median estimate of the tests in its group
+/// when a group_regex is provided and some of them have estimates, or else
+/// the median estimate of all tests, rather than no time at all. If no test
+/// has recorded durations the result is empty.
+///
+/// # Arguments
+///
+/// * `test_ids` - List of test IDs to estimate
+/// * `timings` - Map of test IDs to their recent durations
+/// * `estimator` - How to turn recent durations into an estimate
+/// * `group_regex` - Optional regex pattern to group tests by
+///
+/// # Returns
+///
+/// Map of test IDs to their estimated durations
+pub fn estimate_durations(
+    test_ids: &[TestId],
+    timings: &HashMap<TestId, TestTiming>,
+    estimator: TimeEstimator,
+    group_regex: Option<&str>,
+) -> Result<HashMap<TestId, Duration>, regex::Error> {
+    let mut estimates: HashMap<TestId, Duration> = test_ids
+        .iter()
+        .filter_map(|id| Some((id.clone(), estimator.estimate(timings.get(id)?)?)))
+        .collect();
+    let Some(suite_median) = median(estimates.values().copied().collect()) else {
+        return Ok(estimates);
+    };
+
+    let groups: Vec<Vec<TestId>> = match group_regex {
+        Some(regex) => group_tests(test_ids, regex)?.into_values().collect(),
+        None => vec![test_ids.to_vec()],
+    };
+    for group in groups {
+        let known = group.iter().filter_map(|id| estimates.get(id)).copied();
+        let group_median = median(known.collect()).unwrap_or(suite_median);
+        for test_id in group {
+            estimates.entry(test_id).or_insert(group_median);
+        }
+    }
+    Ok(estimates)
+}
+
+/// Number of batches each worker gets on average when no batch size is given
+pub const BATCHES_PER_WORKER: usize = 4;
+
+/// The batch size that gives each of `concurrency` workers about
+/// [`BATCHES_PER_WORKER`] batches of `test_count` tests
+pub fn default_batch_size(test_count: usize, concurrency: usize) -> usize {
+    test_count
+        .div_ceil(concurrency.max(1) * BATCHES_PER_WORKER)
+        .max(1)
+}
+
+/// Split tests into batches for dynamic scheduling
+///
+/// Batches are handed out in order to whichever worker is free, so the
+/// groups with the longest known durations come first and those without any
+/// come last, in the order they were listed. When a group_regex is provided
+/// a group is never split across batches. Consecutive groups are packed into
+/// batches of up to `batch_size` tests; a larger group is a batch of its own.
+///
+/// # Arguments
+///
+/// * `test_ids` - List of test IDs to split
+/// * `durations` - Map of test IDs to their historical durations
+/// * `batch_size` - Maximum number of tests in a batch
+/// * `group_regex` - Optional regex pattern to group tests by
+///
+/// # Returns
+///
+/// Vector of test ID vectors, in the order they should be run
+pub fn batch_tests(
+    test_ids: &[TestId],
+    durations: &HashMap<TestId, Duration>,
+    batch_size: usize,
+    group_regex: Option<&str>,
+) -> Result<Vec<Vec<TestId>>, regex::Error> {
+    let mut groups: Vec<Vec<TestId>> = match group_regex {
+        Some(regex) => {
+            let position: HashMap<&TestId, usize> = test_ids
+                .iter()
+                .enumerate()
+                .map(|(i, test_id)| (test_id, i))
+                .collect();
+            let mut groups: Vec<Vec<TestId>> =
+                group_tests(test_ids, regex)?.into_values().collect();
+            groups.sort_by_key(|group| group.iter().map(|id| position[id]).min());
+            groups
+        }
+        None => test_ids.iter().map(|id| vec![id.clone()]).collect(),
+    };
+
+    // Longest known first; the sort is stable, so unknown groups keep their order
+    let known = |group: &Vec<TestId>| -> Option<Duration> {
+        group
+            .iter()
+            .filter_map(|id| durations.get(id))
+            .copied()
+            .reduce(|a, b| a + b)
+    };
+    groups.sort_by(|a, b| match (known(a), known(b)) {
+        (Some(a), Some(b)) => b.cmp(&a),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+
+    let mut batches = Vec::new();
+    let mut batch: Vec<TestId> = Vec::new();
+    for group in groups {
+        if !batch.is_empty() && batch.len() + group.len() > batch_size {
+            batches.push(std::mem::take(&mut batch));
+        }
+        batch.extend(group);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_remove_filtered_tests() {
+        let tests = vec![
+            TestId::new("layer.setUp"),
+            TestId::new("test1"),
+            TestId::new("test2"),
+        ];
+        let layer = TestId::new("layer.setUp");
+        let tagged: HashSet<&TestId> = [&layer].into_iter().collect();
+
+        let result = remove_filtered_tests(&tests, &tagged);
+        assert_eq!(result, vec![TestId::new("test1"), TestId::new("test2")]);
+
+        assert_eq!(remove_filtered_tests(&tests, &HashSet::new()), tests);
+    }
+
     #[test]
     fn test_partition_empty() {
         let result = partition_tests(&[], &HashMap::new(), 4);
@@ -337,6 +506,75 @@ mod tests {
         assert_eq!(total_duration.as_millis(), 10200);
     }
 
+    #[test]
+    fn test_batch_tests() {
+        let tests: Vec<TestId> = ["a.1", "b.1", "a.2", "c.1", "d.1"]
+            .into_iter()
+            .map(TestId::new)
+            .collect();
+        let mut durations = HashMap::new();
+        durations.insert(TestId::new("c.1"), Duration::from_secs(1));
+        durations.insert(TestId::new("d.1"), Duration::from_secs(5));
+
+        // Longest first, then the unknown tests in listed order
+        let batches = batch_tests(&tests, &durations, 2, None).unwrap();
+        let ids = |batch: &[TestId]| batch.iter().map(|id| id.as_str()).collect::<Vec<_>>();
+        assert_eq!(batches.len(), 3);
+        assert_eq!(ids(&batches[0]), vec!["d.1", "c.1"]);
+        assert_eq!(ids(&batches[1]), vec!["a.1", "b.1"]);
+        assert_eq!(ids(&batches[2]), vec!["a.2"]);
+
+        // Groups stay together, even when larger than a batch
+        let batches = batch_tests(&tests, &durations, 1, Some(r"^([^.]+)\.")).unwrap();
+        assert_eq!(batches.len(), 4);
+        assert_eq!(ids(&batches[2]), vec!["a.1", "a.2"]);
+        assert_eq!(ids(&batches[3]), vec!["b.1"]);
+
+        assert!(batch_tests(&[], &durations, 2, None).unwrap().is_empty());
+        assert_eq!(default_batch_size(100, 4), 7);
+        assert_eq!(default_batch_size(3, 4), 1);
+    }
+
+    #[test]
+    fn test_estimate_durations() {
+        let tests: Vec<TestId> = ["a.1", "a.2", "a.3", "b.1", "b.2", "c.1"]
+            .into_iter()
+            .map(TestId::new)
+            .collect();
+        let secs = |values: &[u64]| {
+            TestTiming::new(values.iter().map(|&s| Duration::from_secs(s)).collect())
+        };
+        let mut timings = HashMap::new();
+        timings.insert(TestId::new("a.1"), secs(&[2, 4]));
+        timings.insert(TestId::new("a.2"), secs(&[6, 2]));
+        timings.insert(TestId::new("b.1"), secs(&[10, 10, 30]));
+
+        // Unknown tests take their group's median, or the suite's
+        let estimates =
+            estimate_durations(&tests, &timings, TimeEstimator::Mean, Some(r"^([^.]+)\.")).unwrap();
+        let estimate = |id: &str| estimates[&TestId::new(id)].as_secs();
+        assert_eq!(estimates.len(), 6);
+        assert_eq!(estimate("a.1"), 3);
+        assert_eq!(estimate("a.2"), 4);
+        assert_eq!(estimate("a.3"), 3);
+        assert_eq!(estimate("b.2"), 16);
+        assert_eq!(estimate("c.1"), 4);
+
+        let estimates = estimate_durations(&tests, &timings, TimeEstimator::Last, None).unwrap();
+        assert_eq!(estimates[&TestId::new("b.1")].as_secs(), 30);
+        assert_eq!(estimates[&TestId::new("c.1")].as_secs(), 4);
+
+        let estimates = estimate_durations(&tests, &timings, TimeEstimator::P90, None).unwrap();
+        assert_eq!(estimates[&TestId::new("a.2")].as_secs(), 6);
+
+        // Without any recorded durations there is nothing to go on
+        assert!(
+            estimate_durations(&tests, &HashMap::new(), TimeEstimator::Mean, None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn test_partition_with_grouping_invalid_regex() {
         let tests = vec![TestId::new("test1")];
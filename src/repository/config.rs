@@ -0,0 +1,257 @@
+//! Per-repository settings (.testrepository/repo.conf)
+//!
+//! repo.conf holds settings that belong to one checkout rather than to the
+//! project, so they can be changed without editing the shared .testr.conf.
+//! It uses the same INI layout as .testr.conf, with a `[DEFAULT]` section.
+
+use crate::error::{Error, Result};
+use crate::repository::TimeEstimator;
+use crate::subunit_stream::OutputFilter;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Known repository settings and their descriptions, in display order
+pub const SETTINGS: &[(&str, &str)] = &[
+    (
+        "keep_runs",
+        "Number of most recent runs to keep when pruning",
+    ),
+    (
+        "keep_days",
+        "Maximum age in days of runs to keep when pruning",
+    ),
+    (
+        "auto_prune",
+        "Prune runs after every 'testr run' and 'testr load': 'true' or 'false'",
+    ),
+    (
+        "concurrency",
+        "Number of workers for 'testr run' when --parallel is not given",
+    ),
+    (
+        "output",
+        "Test output to show during 'testr run': 'failures' or 'all'",
+    ),
+    (
+        "time_estimator",
+        "How to estimate test times for partitioning: 'mean', 'p90' or 'last'",
+    ),
+];
+
+/// Settings loaded from repo.conf
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepoConfig {
+    /// Number of most recent runs to keep when pruning
+    pub keep_runs: Option<usize>,
+
+    /// Maximum age in days of runs to keep when pruning
+    pub keep_days: Option<u64>,
+
+    /// Whether to prune runs automatically after run and load
+    pub auto_prune: Option<bool>,
+
+    /// Default concurrency for test runs
+    pub concurrency: Option<usize>,
+
+    /// Default output filter for test runs
+    pub output: Option<OutputFilter>,
+
+    /// How test times are estimated when partitioning tests
+    pub time_estimator: Option<TimeEstimator>,
+}
+
+impl RepoConfig {
+    /// Load settings from a repo.conf file, returning defaults if it is missing
+    pub fn load_from_file(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(RepoConfig::default());
+        }
+
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Error::Config(format!("Failed to read repo.conf: {}", e)))?;
+
+        Self::parse(&contents)
+    }
+
+    /// Parse settings from a string
+    pub fn parse(contents: &str) -> Result<Self> {
+        let ini: HashMap<String, HashMap<String, String>> = serde_ini::from_str(contents)
+            .map_err(|e| Error::Config(format!("Failed to parse repo.conf: {}", e)))?;
+
+        let mut config = RepoConfig::default();
+        if let Some(default) = ini.get("DEFAULT") {
+            for (key, value) in default {
+                config.set(key, Some(value))?;
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Serialize the settings in repo.conf format
+    pub fn to_ini_string(&self) -> String {
+        let mut contents = String::from("[DEFAULT]\n");
+        for (key, _) in SETTINGS {
+            if let Some(value) = self.get(key).ok().flatten() {
+                contents.push_str(&format!("{}={}\n", key, value));
+            }
+        }
+        contents
+    }
+
+    /// Get a setting by name, or `None` if it is not set
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let value = match key {
+            "keep_runs" => self.keep_runs.map(|n| n.to_string()),
+            "keep_days" => self.keep_days.map(|n| n.to_string()),
+            "auto_prune" => self.auto_prune.map(|b| b.to_string()),
+            "concurrency" => self.concurrency.map(|n| n.to_string()),
+            "output" => self.output.map(|filter| match filter {
+                OutputFilter::FailuresOnly => "failures".to_string(),
+                OutputFilter::All => "all".to_string(),
+            }),
+            "time_estimator" => self.time_estimator.map(|estimator| estimator.to_string()),
+            _ => return Err(unknown_setting(key)),
+        };
+        Ok(value)
+    }
+
+    /// Set a setting by name; `None` or an empty value unsets it
+    pub fn set(&mut self, key: &str, value: Option<&str>) -> Result<()> {
+        let value = value.map(str::trim).filter(|v| !v.is_empty());
+
+        match key {
+            "keep_runs" => self.keep_runs = value.map(|v| parse_number(key, v)).transpose()?,
+            "keep_days" => self.keep_days = value.map(|v| parse_number(key, v)).transpose()?,
+            "auto_prune" => {
+                self.auto_prune = match value {
+                    None => None,
+                    Some("true") | Some("yes") | Some("1") => Some(true),
+                    Some("false") | Some("no") | Some("0") => Some(false),
+                    Some(other) => {
+                        return Err(Error::Config(format!(
+                            "Invalid value for auto_prune: '{}' (expected 'true' or 'false')",
+                            other
+                        )))
+                    }
+                }
+            }
+            "concurrency" => {
+                let concurrency = value.map(|v| parse_number(key, v)).transpose()?;
+                if concurrency == Some(0) {
+                    return Err(Error::Config("concurrency must be at least 1".to_string()));
+                }
+                self.concurrency = concurrency;
+            }
+            "output" => {
+                self.output = match value {
+                    None => None,
+                    Some("failures") => Some(OutputFilter::FailuresOnly),
+                    Some("all") => Some(OutputFilter::All),
+                    Some(other) => {
+                        return Err(Error::Config(format!(
+                            "Invalid value for output: '{}' (expected 'failures' or 'all')",
+                            other
+                        )))
+                    }
+                }
+            }
+            "time_estimator" => self.time_estimator = value.map(str::parse).transpose()?,
+            _ => return Err(unknown_setting(key)),
+        }
+
+        Ok(())
+    }
+}
+
+fn unknown_setting(key: &str) -> Error {
+    let known: Vec<&str> = SETTINGS.iter().map(|(name, _)| *name).collect();
+    Error::Config(format!(
+        "Unknown repository setting '{}' (known settings: {})",
+        key,
+        known.join(", ")
+    ))
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| {
+        Error::Config(format!(
+            "Invalid value for {}: '{}' (expected a non-negative integer)",
+            key, value
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repo_config() {
+        let config = RepoConfig::parse(
+            r#"
+[DEFAULT]
+keep_runs=50
+concurrency=4
+output=all
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.keep_runs, Some(50));
+        assert_eq!(config.keep_days, None);
+        assert_eq!(config.concurrency, Some(4));
+        assert_eq!(config.output, Some(OutputFilter::All));
+    }
+
+    #[test]
+    fn test_parse_repo_config_rejects_bad_values() {
+        assert!(RepoConfig::parse("[DEFAULT]\nkeep_runs=lots\n").is_err());
+        assert!(RepoConfig::parse("[DEFAULT]\nbogus=1\n").is_err());
+    }
+
+    #[test]
+    fn test_set_and_get() {
+        let mut config = RepoConfig::default();
+        config.set("keep_days", Some("30")).unwrap();
+        assert_eq!(config.get("keep_days").unwrap(), Some("30".to_string()));
+
+        config.set("keep_days", None).unwrap();
+        assert_eq!(config.get("keep_days").unwrap(), None);
+
+        config.set("auto_prune", Some("yes")).unwrap();
+        assert_eq!(config.get("auto_prune").unwrap(), Some("true".to_string()));
+
+        assert!(config.set("auto_prune", Some("maybe")).is_err());
+        assert!(config.set("concurrency", Some("0")).is_err());
+        assert!(config.set("output", Some("some")).is_err());
+        assert!(config.get("bogus").is_err());
+
+        config.set("time_estimator", Some("p90")).unwrap();
+        assert_eq!(config.time_estimator, Some(TimeEstimator::P90));
+        assert_eq!(
+            config.get("time_estimator").unwrap(),
+            Some("p90".to_string())
+        );
+        assert!(config.set("time_estimator", Some("median")).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut config = RepoConfig::default();
+        config.set("keep_runs", Some("10")).unwrap();
+        config.set("output", Some("failures")).unwrap();
+
+        let contents = config.to_ini_string();
+        assert_eq!(contents, "[DEFAULT]\nkeep_runs=10\noutput=failures\n");
+        assert_eq!(RepoConfig::parse(&contents).unwrap(), config);
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let config = RepoConfig::load_from_file(&temp.path().join("repo.conf")).unwrap();
+        assert_eq!(config, RepoConfig::default());
+    }
+}
@@ -4,20 +4,294 @@
 //! - format: version file (contains "1")
 //! - next-stream: counter for run IDs
 //! - 0, 1, 2, ...: individual test run files (subunit format)
+//! - 0.meta, 1.meta, ...: metadata for each run, such as whether it was partial (ignored by Python)
 //! - failing: synthetic run containing current failures
-//! - times.dbm: test timing database (NOT YET IMPLEMENTED - will use different format)
+//! - times.dbm: test timing database (Python dbm.sqlite3 format; legacy GDBM files are migrated).
+//!   Each test's most recent durations are kept in an extra History table,
+//!   which Python ignores
+//! - times.dbm.gdbm: a legacy GDBM times database kept as a backup after
+//!   migration
+//! - repo.conf: per-repository settings
+//! - lock: advisory lock held while the repository is being written
 
 use crate::error::{Error, Result};
-use crate::repository::{Repository, RepositoryFactory, TestId, TestResult, TestRun};
+use crate::repository::{
+    run_spec, CheckIssue, RepoConfig, Repository, RepositoryFactory, RunMetadata, RunStreamWriter,
+    TestId, TestResult, TestRun, TestTiming,
+};
 use crate::subunit_stream;
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use rusqlite::OptionalExtension;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 use subunit::serialize::Serializable;
 
 const REPOSITORY_FORMAT: &str = "1";
-const REPO_DIR: &str = ".testrepository";
+pub(super) const REPO_DIR: &str = ".testrepository";
+
+/// Find the numbered run files in a repository directory, whatever its run
+/// counter says
+pub(super) fn run_files(repo_path: &Path) -> Result<BTreeSet<u64>> {
+    let mut runs = BTreeSet::new();
+    for entry in fs::read_dir(repo_path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if let Ok(n) = name.parse::<u64>() {
+            if n.to_string() == name {
+                runs.insert(n);
+            }
+        }
+    }
+    Ok(runs)
+}
+
+/// Check a run counter against the runs that exist, including those still
+/// being written
+///
+/// A counter behind the runs would have the next run overwrite one, and one
+/// past them leaves a gap before the next run. Both are reported under
+/// `counter_name`, and the value the counter should have is returned so that
+/// it can be repaired. Gaps between the runs are reported too; they can't be
+/// repaired, since the runs are gone.
+pub(super) fn check_run_numbering(
+    counter_name: &str,
+    counter: Option<u64>,
+    runs: &BTreeSet<u64>,
+    repair: bool,
+    issues: &mut Vec<CheckIssue>,
+) -> Option<u64> {
+    let next_run = runs.last().map_or(0, |n| n + 1);
+    let message = match counter {
+        None => Some(format!("{} is missing or unreadable", counter_name)),
+        Some(c) if c < next_run => Some(format!(
+            "{} is {} but run {} exists",
+            counter_name,
+            c,
+            next_run - 1
+        )),
+        Some(c) if c > next_run && next_run == 0 => {
+            Some(format!("{} is {} but there are no runs", counter_name, c))
+        }
+        Some(c) if c > next_run => Some(format!(
+            "{} is {} but the highest run is {}",
+            counter_name,
+            c,
+            next_run - 1
+        )),
+        Some(_) => None,
+    };
+    if let Some(message) = &message {
+        issues.push(CheckIssue {
+            location: counter_name.to_string(),
+            message: message.clone(),
+            repaired: repair,
+        });
+    }
+
+    let mut previous: Option<u64> = None;
+    for &run in runs {
+        if let Some(previous) = previous.filter(|previous| previous + 1 < run) {
+            let location = if previous + 2 == run {
+                format!("run {}", previous + 1)
+            } else {
+                format!("runs {}-{}", previous + 1, run - 1)
+            };
+            issues.push(CheckIssue {
+                location,
+                message: format!("missing between run {} and run {}", previous, run),
+                repaired: false,
+            });
+        }
+        previous = Some(run);
+    }
+
+    message.map(|_| next_run)
+}
+
+/// Validate one stream file, recording any problems found in `issues`
+///
+/// A stream whose only problem is a torn final packet, as left behind by an
+/// interrupted write, is truncated before that packet when repairing.
+/// Returns true if the stream is still damaged.
+pub(super) fn check_stream(
+    path: &Path,
+    location: &str,
+    repair: bool,
+    issues: &mut Vec<CheckIssue>,
+) -> Result<bool> {
+    let data = fs::read(path)?;
+    let problems = subunit_stream::validate_stream(&data);
+    let torn_tail = match problems.as_slice() {
+        [problem] if problem.truncated => Some(problem.offset),
+        _ => None,
+    };
+    let repaired = repair && torn_tail.is_some();
+
+    for problem in &problems {
+        issues.push(CheckIssue {
+            location: location.to_string(),
+            message: format!("offset {}: {}", problem.offset, problem.message),
+            repaired,
+        });
+    }
+
+    if let (true, Some(offset)) = (repair, torn_tail) {
+        fs::OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(offset)?;
+    }
+
+    Ok(!problems.is_empty() && !repaired)
+}
+
+/// Writer for a new run's stream that moves it into place when committed.
+///
+/// Until then the stream is kept under a temporary name, so that readers
+/// never see a partly written run. A writer dropped without being committed
+/// removes its temporary file. The temporary file stays locked while it is
+/// being written, which is how `check` tells one left behind by a killed
+/// process from one still in use.
+pub(super) struct RunWriter {
+    file: File,
+    temp_path: PathBuf,
+    path: PathBuf,
+    committed: bool,
+    /// Gives the run's ID back once the writer is dropped without committing
+    release: Option<Box<dyn FnOnce() -> Result<()> + Send>>,
+}
+
+impl RunWriter {
+    /// Start writing the stream that will be stored at `path`
+    pub(super) fn create(path: PathBuf) -> Result<Self> {
+        let temp_path = path.with_extension("tmp");
+        let file = File::create(&temp_path)?;
+        file.lock()?;
+        Ok(RunWriter {
+            file,
+            temp_path,
+            path,
+            committed: false,
+            release: None,
+        })
+    }
+
+    /// Call `release` if the writer is dropped without being committed,
+    /// after its temporary file has been removed
+    pub(super) fn on_release(
+        mut self,
+        release: impl FnOnce() -> Result<()> + Send + 'static,
+    ) -> Self {
+        self.release = Some(Box::new(release));
+        self
+    }
+
+    /// Move the finished stream into place
+    pub(super) fn finish(&mut self) -> Result<()> {
+        self.file.flush()?;
+        fs::rename(&self.temp_path, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl RunStreamWriter for RunWriter {
+    fn commit(mut self: Box<Self>) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl Write for RunWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for RunWriter {
+    fn drop(&mut self) {
+        if !self.committed {
+            // Drop can't report errors; a temporary file that can't be
+            // removed, or a run ID that can't be given back, is reported by
+            // `check`
+            let _ = fs::remove_file(&self.temp_path);
+            if let Some(release) = self.release.take() {
+                let _ = release();
+            }
+        }
+    }
+}
+
+/// Find the temporary files of runs, with whether a writer still holds each
+/// one locked
+fn run_temp_files(dir: &Path) -> Result<Vec<(u64, PathBuf, bool)>> {
+    let mut temp_files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let run_id = name
+            .to_str()
+            .and_then(|name| name.strip_suffix(".tmp"))
+            .and_then(|run_id| run_id.parse::<u64>().ok());
+        let Some(run_id) = run_id else {
+            continue;
+        };
+        if entry.file_type()?.is_file() {
+            let locked = File::open(entry.path())?.try_lock().is_err();
+            temp_files.push((run_id, entry.path(), locked));
+        }
+    }
+    temp_files.sort();
+    Ok(temp_files)
+}
+
+/// Find the runs still being written, whose writers hold their temporary
+/// files locked
+pub(super) fn runs_in_progress(dir: &Path) -> Result<BTreeSet<u64>> {
+    Ok(run_temp_files(dir)?
+        .into_iter()
+        .filter_map(|(run_id, _, locked)| locked.then_some(run_id))
+        .collect())
+}
+
+/// Report the temporary files of runs whose writers were killed before
+/// committing them, removing them when repairing
+///
+/// A temporary file that is still locked belongs to a run in progress and
+/// is left alone.
+pub(super) fn check_stale_run_files(
+    dir: &Path,
+    repair: bool,
+    issues: &mut Vec<CheckIssue>,
+) -> Result<()> {
+    let stale = run_temp_files(dir)?
+        .into_iter()
+        .filter(|(_, _, locked)| !locked);
+
+    for (run_id, path, _) in stale {
+        if repair {
+            fs::remove_file(&path)?;
+        }
+        issues.push(CheckIssue {
+            location: format!("run {}", run_id),
+            message: format!("stale {}.tmp left by an interrupted run", run_id),
+            repaired: repair,
+        });
+    }
+    Ok(())
+}
 
 /// Factory for creating file-based repositories.
 ///
@@ -94,26 +368,94 @@ impl FileRepository {
     }
 
     fn write_next_stream(&self, value: u64) -> Result<()> {
+        // Readers don't take the lock, so never let them see a half-written counter
         let path = self.get_next_stream_path();
-        fs::write(&path, format!("{}\n", value))?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, format!("{}\n", value))?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    /// Give back the ID of a run whose writer was dropped, unless a later
+    /// run has been given an ID since
+    fn release_run_id(&self, run_id: u64) -> Result<()> {
+        let _lock = self.lock()?;
+        if self.read_next_stream()? == run_id + 1 {
+            self.write_next_stream(run_id)?;
+        }
         Ok(())
     }
 
-    fn increment_next_stream(&mut self) -> Result<u64> {
-        let current = self.read_next_stream()?;
-        let next = current + 1;
-        self.write_next_stream(next)?;
-        Ok(current)
+    /// Take the repository's advisory write lock, which is held until the
+    /// returned file is dropped
+    ///
+    /// Only writers lock; readers rely on files being replaced atomically. The
+    /// lock is not reentrant, so public methods take it and the private
+    /// helpers they call must not.
+    fn lock(&self) -> Result<File> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path.join("lock"))?;
+        file.lock()?;
+        Ok(file)
     }
 
     fn get_run_path(&self, run_id: &str) -> PathBuf {
         self.path.join(run_id)
     }
 
+    fn get_run_meta_path(&self, run_id: &str) -> PathBuf {
+        self.path.join(format!("{}.meta", run_id))
+    }
+
+    /// Read the metadata recorded for a run, which is empty if there is none
+    fn read_run_metadata(&self, run_id: &str) -> Result<RunMetadata> {
+        let path = self.get_run_meta_path(run_id);
+        if !path.exists() {
+            return Ok(RunMetadata::default());
+        }
+
+        RunMetadata::parse(&fs::read_to_string(&path)?)
+            .map_err(|e| Error::InvalidFormat(format!("metadata for run {}: {}", run_id, e)))
+    }
+
+    fn write_run_metadata(&self, run_id: &str, metadata: &RunMetadata) -> Result<()> {
+        let path = self.get_run_meta_path(run_id);
+        let temp_path = self.path.join(format!("{}.meta.tmp", run_id));
+        fs::write(&temp_path, metadata.to_ini_string())?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    /// Record whether a run was partial, so that a rebuild can replay it the same way
+    fn write_run_mode(&self, run_id: &str, partial: bool) -> Result<()> {
+        let mut metadata = self.read_run_metadata(run_id)?;
+        metadata.partial = Some(partial);
+        self.write_run_metadata(run_id, &metadata)
+    }
+
     fn get_failing_path(&self) -> PathBuf {
         self.path.join("failing")
     }
 
+    fn get_config_path(&self) -> PathBuf {
+        self.path.join("repo.conf")
+    }
+
+    fn get_bookmarks_path(&self) -> PathBuf {
+        self.path.join("bookmarks")
+    }
+
+    fn get_times_path(&self) -> PathBuf {
+        self.path.join("times.dbm")
+    }
+
+    fn get_legacy_times_path(&self) -> PathBuf {
+        self.path.join("times.dbm.gdbm")
+    }
+
     fn read_failing_run(&self) -> Result<HashMap<TestId, TestResult>> {
         let path = self.get_failing_path();
 
@@ -142,23 +484,26 @@ impl FileRepository {
         let run_path = self.get_run_path(run_id);
 
         // Read the raw test run and filter for failing tests
+        let temp_path = failing_path.with_extension("tmp");
         let reader = File::open(&run_path)?;
-        let writer = File::create(&failing_path)?;
+        let writer = File::create(&temp_path)?;
 
         subunit_stream::filter_failing_tests(reader, writer)?;
 
-        // Check if the failing file is empty and remove it if so
-        let metadata = std::fs::metadata(&failing_path)?;
-        if metadata.len() == 0 {
-            fs::remove_file(&failing_path)?;
+        // No failures means no failing file at all
+        if fs::metadata(&temp_path)?.len() == 0 {
+            fs::remove_file(&temp_path)?;
+            if failing_path.exists() {
+                fs::remove_file(&failing_path)?;
+            }
+        } else {
+            fs::rename(&temp_path, &failing_path)?;
         }
 
         Ok(())
     }
 
     fn update_failing_run_from_raw(&self, run_id: &str) -> Result<()> {
-        use std::io::Write;
-
         // Get existing failing tests
         let mut existing_failing = self.read_failing_run().unwrap_or_default();
 
@@ -239,26 +584,24 @@ impl FileRepository {
         &self,
         test_ids: &[TestId],
     ) -> Result<HashMap<TestId, Duration>> {
-        let times_path = self.path.join("times.dbm");
-
-        // If the database doesn't exist yet, return empty
-        if !times_path.exists() {
-            return Ok(HashMap::new());
-        }
-
-        // Try SQLite first (Python's dbm.sqlite3 format - most common on modern systems)
-        if let Ok(result) = self.read_times_sqlite(&times_path, test_ids) {
-            return Ok(result);
-        }
-
-        // Fall back to GDBM (older Python versions or explicit GDBM usage)
-        if let Ok(result) = self.read_times_gdbm(&times_path, test_ids) {
-            return Ok(result);
-        }
+        let times_path = self.get_times_path();
+
+        let mut result = if !times_path.exists() {
+            // If the database doesn't exist yet, start empty
+            HashMap::new()
+        } else if let Ok(result) = self.read_times_sqlite(&times_path, test_ids) {
+            // SQLite first (Python's dbm.sqlite3 format - most common on modern systems)
+            result
+        } else if let Ok(result) = self.read_times_gdbm(&times_path, test_ids) {
+            // Fall back to GDBM (older Python versions or explicit GDBM usage)
+            result
+        } else {
+            // If neither format works, return empty (with warning)
+            eprintln!("Warning: Could not read times database in SQLite or GDBM format, continuing without historical timing data");
+            HashMap::new()
+        };
 
-        // If neither format works, return empty (with warning)
-        eprintln!("Warning: Could not read times database in SQLite or GDBM format, continuing without historical timing data");
-        Ok(HashMap::new())
+        Ok(result)
     }
 
     /// Read test times from SQLite database (Python dbm.sqlite3 format)
@@ -288,6 +631,139 @@ impl FileRepository {
         Ok(result)
     }
 
+    fn get_test_timings_for_ids_impl(
+        &self,
+        test_ids: &[TestId],
+    ) -> Result<HashMap<TestId, TestTiming>> {
+        let times_path = self.get_times_path();
+        if !times_path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        if let Ok(result) = self.read_timings_sqlite(&times_path, test_ids) {
+            return Ok(result);
+        }
+
+        // Databases without a History table, including GDBM ones, only hold
+        // the most recent duration of each test
+        Ok(self
+            .get_test_times_for_ids_impl(test_ids)?
+            .into_iter()
+            .map(|(test_id, duration)| (test_id, TestTiming::new(vec![duration])))
+            .collect())
+    }
+
+    /// Read the recent durations of tests from a SQLite times database
+    fn read_timings_sqlite(
+        &self,
+        path: &Path,
+        test_ids: &[TestId],
+    ) -> Result<HashMap<TestId, TestTiming>> {
+        let conn = rusqlite::Connection::open(path)?;
+
+        let mut result = HashMap::new();
+        for test_id in test_ids {
+            if let Some(timing) = read_timing_sqlite(&conn, test_id)? {
+                result.insert(test_id.clone(), timing);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Read every entry from a SQLite times database (Python dbm.sqlite3 format)
+    fn read_all_times_sqlite(&self, path: &Path) -> Result<HashMap<TestId, Duration>> {
+        let conn = rusqlite::Connection::open(path)?;
+
+        // A database that has never been written to has no Dict table yet
+        let has_table: bool = conn.query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'Dict'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_table {
+            return Ok(HashMap::new());
+        }
+
+        let mut stmt = conn.prepare("SELECT key, value FROM Dict")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?;
+
+        let mut result = HashMap::new();
+        for row in rows {
+            let (key, value) = row?;
+            let (Ok(key), Ok(value)) = (String::from_utf8(key), String::from_utf8(value)) else {
+                continue;
+            };
+            if let Ok(seconds) = value.parse::<f64>() {
+                result.insert(TestId::new(key), Duration::from_secs_f64(seconds));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Check whether a times database is in SQLite format
+    ///
+    /// SQLite leaves a freshly opened database empty until the first write, so
+    /// an empty file counts as SQLite too.
+    fn is_sqlite_database(path: &Path) -> Result<bool> {
+        use std::io::Read;
+
+        let mut header = [0u8; 16];
+        let mut file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok(true);
+        }
+        match file.read_exact(&mut header) {
+            Ok(()) => Ok(&header == b"SQLite format 3\0"),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Check whether a times database is in GDBM format
+    ///
+    /// Every GDBM file starts with a magic number, whose value depends on the
+    /// format version and whose byte order on the machine that wrote it.
+    fn is_gdbm_database(path: &Path) -> Result<bool> {
+        use std::io::Read;
+
+        const MAGICS: [u32; 5] = [0x13579ace, 0x13579acd, 0x13579acf, 0x13579ad0, 0x13579ad1];
+        let mut header = [0u8; 4];
+        match File::open(path)?.read_exact(&mut header) {
+            Ok(()) => {
+                let magic = u32::from_ne_bytes(header);
+                Ok(MAGICS.contains(&magic) || MAGICS.contains(&magic.swap_bytes()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Convert a legacy GDBM times database to SQLite
+    ///
+    /// Only done by writers, which hold the lock. Every timing is copied, and
+    /// the original file is kept as times.dbm.gdbm. Returns the timings
+    /// copied.
+    fn migrate_times_from_gdbm(&self) -> Result<HashMap<TestId, Duration>> {
+        let times_path = self.get_times_path();
+        if !times_path.exists() || Self::is_sqlite_database(&times_path)? {
+            return Ok(HashMap::new());
+        }
+
+        let times = self.read_all_times_gdbm(&times_path)?;
+
+        fs::rename(&times_path, self.get_legacy_times_path())?;
+        self.write_times_sqlite(&times_path, &times)?;
+        Ok(times)
+    }
+
+    /// Read every entry from a GDBM times database
+    fn read_all_times_gdbm(&self, path: &Path) -> Result<HashMap<TestId, Duration>> {
+        self.read_times_gdbm(path, &gdbm_keys(path)?)
+    }
+
     /// Read test times from GDBM database (older Python versions)
     fn read_times_gdbm(
         &self,
@@ -310,38 +786,200 @@ impl FileRepository {
         Ok(result)
     }
 
+    /// Read every entry of the times database
+    ///
+    /// Readers don't migrate, so a GDBM database may not have been converted
+    /// yet.
+    fn read_times(&self) -> Result<HashMap<TestId, Duration>> {
+        let times_path = self.get_times_path();
+        if !times_path.exists() {
+            Ok(HashMap::new())
+        } else if Self::is_sqlite_database(&times_path)? {
+            self.read_all_times_sqlite(&times_path)
+        } else {
+            self.read_all_times_gdbm(&times_path)
+        }
+    }
+
     fn update_test_times_impl(&mut self, times: &HashMap<TestId, Duration>) -> Result<()> {
         if times.is_empty() {
             return Ok(());
         }
 
-        let times_path = self.path.join("times.dbm");
+        // Older repositories may still have a GDBM database, which SQLite cannot write to
+        self.migrate_times_from_gdbm()?;
+        self.write_times_sqlite(&self.get_times_path(), times)
+    }
 
+    /// Record durations in a SQLite times database
+    ///
+    /// Dict holds the latest duration of each test, as Python expects, and
+    /// History the recent durations.
+    fn write_times_sqlite(&self, path: &Path, times: &HashMap<TestId, Duration>) -> Result<()> {
         // Use SQLite to match Python's dbm.sqlite3 format
-        let conn = rusqlite::Connection::open(&times_path)?;
+        let mut conn = rusqlite::Connection::open(path)?;
+        let tx = conn.transaction()?;
 
         // Create the Dict table if it doesn't exist (Python dbm.sqlite3 format)
-        conn.execute(
+        tx.execute_batch(
             "CREATE TABLE IF NOT EXISTS Dict (
                 key BLOB PRIMARY KEY,
                 value BLOB
-            )",
-            [],
+            );
+            CREATE TABLE IF NOT EXISTS History (
+                key BLOB PRIMARY KEY,
+                value BLOB
+            );",
         )?;
 
-        // Update each test time using UPSERT (INSERT OR REPLACE)
-        let mut stmt = conn.prepare("INSERT OR REPLACE INTO Dict (key, value) VALUES (?, ?)")?;
-
         for (test_id, duration) in times {
+            let mut timing = read_timing_sqlite(&tx, test_id)?.unwrap_or_default();
+            timing.record(*duration);
+
+            // Update each test time using UPSERT (INSERT OR REPLACE)
             let key = test_id.as_str().as_bytes();
             let value = duration.as_secs_f64().to_string();
-            stmt.execute([key, value.as_bytes()])?;
+            tx.prepare_cached("INSERT OR REPLACE INTO Dict (key, value) VALUES (?, ?)")?
+                .execute([key, value.as_bytes()])?;
+            tx.prepare_cached("INSERT OR REPLACE INTO History (key, value) VALUES (?, ?)")?
+                .execute([key, timing.encode().as_bytes()])?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Recompute failing and the times database by replaying runs in order
+    fn replay_runs(&mut self, run_ids: &[String]) -> Result<()> {
+        // A GDBM database isn't damaged, only old, so it is migrated and its
+        // timings are kept for tests that no stored run has. One that is in
+        // neither format is damaged, and goes with the rest.
+        let times_path = self.get_times_path();
+        let kept_times = if times_path.exists() && Self::is_gdbm_database(&times_path)? {
+            self.migrate_times_from_gdbm()?
+        } else {
+            HashMap::new()
+        };
+        for path in [self.get_failing_path(), times_path] {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        self.update_test_times_impl(&kept_times)?;
+
+        for run_id in run_ids {
+            // Runs stored before modes were recorded are replayed as partial,
+            // which never drops a failure that no later run saw passing
+            if self.read_run_metadata(run_id)?.partial == Some(false) {
+                self.write_failing_run_from_raw(run_id)?;
+            } else {
+                self.update_failing_run_from_raw(run_id)?;
+            }
+
+            let run = self.get_test_run(run_id)?;
+            let times: HashMap<TestId, Duration> = run
+                .results
+                .values()
+                .filter_map(|result| result.duration.map(|d| (result.test_id.clone(), d)))
+                .collect();
+            self.update_test_times_impl(&times)?;
         }
 
         Ok(())
     }
 }
 
+/// Read a test's recent durations from a SQLite times database
+///
+/// Only Dict is updated by Python and by versions that kept no history, so a
+/// latest duration there that History doesn't end with is counted as well.
+fn read_timing_sqlite(conn: &rusqlite::Connection, test_id: &TestId) -> Result<Option<TestTiming>> {
+    let key = test_id.as_str().as_bytes();
+    let history: Option<Vec<u8>> = conn
+        .prepare_cached("SELECT value FROM History WHERE key = ?")?
+        .query_row([key], |row| row.get(0))
+        .optional()?;
+    let latest: Option<Vec<u8>> = conn
+        .prepare_cached("SELECT value FROM Dict WHERE key = ?")?
+        .query_row([key], |row| row.get(0))
+        .optional()?;
+    let latest = latest
+        .and_then(|value| String::from_utf8(value).ok()?.parse::<f64>().ok())
+        .map(Duration::from_secs_f64);
+
+    let mut timing = match history {
+        Some(history) => TestTiming::decode(&String::from_utf8_lossy(&history)),
+        None => TestTiming::default(),
+    };
+    if let Some(latest) = latest {
+        if timing.last() != Some(latest) {
+            timing.record(latest);
+        }
+    }
+    Ok((timing.count() > 0).then_some(timing))
+}
+
+/// A key or value handed out by libgdbm
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GdbmDatum {
+    dptr: *mut libc::c_char,
+    dsize: libc::c_int,
+}
+
+#[link(name = "gdbm")]
+extern "C" {
+    fn gdbm_open(
+        name: *const libc::c_char,
+        block_size: libc::c_int,
+        flags: libc::c_int,
+        mode: libc::c_int,
+        fatal_func: Option<extern "C" fn(*const libc::c_char)>,
+    ) -> *mut libc::c_void;
+    fn gdbm_firstkey(dbf: *mut libc::c_void) -> GdbmDatum;
+    fn gdbm_nextkey(dbf: *mut libc::c_void, key: GdbmDatum) -> GdbmDatum;
+    fn gdbm_close(dbf: *mut libc::c_void);
+}
+
+/// Every key in a GDBM database
+///
+/// The gdbm crate can only look keys up, so they are walked with libgdbm
+/// directly.
+fn gdbm_keys(path: &Path) -> Result<Vec<TestId>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    const GDBM_READER: libc::c_int = 0;
+    let name = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    // SAFETY: name is a valid C string, and libgdbm reports failure with null
+    let dbf = unsafe { gdbm_open(name.as_ptr(), 0, GDBM_READER, 0o644, None) };
+    if dbf.is_null() {
+        return Err(Error::Io(std::io::Error::other(format!(
+            "Failed to open GDBM: {}",
+            path.display()
+        ))));
+    }
+
+    let mut keys = Vec::new();
+    // SAFETY: dbf is open until gdbm_close, and each key is allocated by
+    // libgdbm with malloc, holds dsize bytes and is freed once the next key
+    // has been found from it
+    unsafe {
+        let mut key = gdbm_firstkey(dbf);
+        while !key.dptr.is_null() {
+            let bytes = std::slice::from_raw_parts(key.dptr as *const u8, key.dsize as usize);
+            if let Ok(test_id) = std::str::from_utf8(bytes) {
+                keys.push(TestId::new(test_id));
+            }
+            let next = gdbm_nextkey(dbf, key);
+            libc::free(key.dptr as *mut libc::c_void);
+            key = next;
+        }
+        gdbm_close(dbf);
+    }
+    Ok(keys)
+}
+
 impl Repository for FileRepository {
     fn get_test_run(&self, run_id: &str) -> Result<TestRun> {
         let path = self.get_run_path(run_id);
@@ -354,7 +992,7 @@ impl Repository for FileRepository {
 
         // Check file size - only use mmap for files larger than 4KB
         let metadata = file.metadata()?;
-        let test_run = if metadata.len() > 4096 {
+        let mut test_run = if metadata.len() > 4096 {
             // Safety: We're only reading from the file, not modifying it
             let mmap = unsafe { memmap2::Mmap::map(&file)? };
             subunit_stream::parse_stream_bytes(&mmap, run_id.to_string())
@@ -363,26 +1001,38 @@ impl Repository for FileRepository {
             subunit_stream::parse_stream(file, run_id.to_string())
         }?;
 
+        // Date the run by when it started, or failing that when it was stored;
+        // damaged metadata shouldn't stop the results being read
+        test_run.timestamp = match self.read_run_metadata(run_id) {
+            Ok(RunMetadata {
+                start_time: Some(start_time),
+                ..
+            }) => start_time,
+            _ => metadata.modified()?.into(),
+        };
+
         Ok(test_run)
     }
 
-    fn begin_test_run_raw(&mut self) -> Result<(String, Box<dyn std::io::Write + Send>)> {
-        let run_id = self.increment_next_stream()?;
+    fn begin_test_run_raw(&mut self) -> Result<(String, Box<dyn RunStreamWriter>)> {
+        // The temporary file is created under the lock, so that `check`
+        // always finds the run in progress behind the counter
+        let _lock = self.lock()?;
+        let run_id = self.read_next_stream()?;
         let run_id_str = run_id.to_string();
+        let writer = RunWriter::create(self.get_run_path(&run_id_str))?;
+        self.write_next_stream(run_id + 1)?;
 
-        let path = self.get_run_path(&run_id_str);
-        let file = File::create(&path)?;
-
-        Ok((run_id_str, Box::new(file)))
+        let repo = FileRepository {
+            path: self.path.clone(),
+        };
+        let writer = writer.on_release(move || repo.release_run_id(run_id));
+        Ok((run_id_str, Box::new(writer)))
     }
 
     fn get_latest_run(&self) -> Result<TestRun> {
-        let next_stream = self.read_next_stream()?;
-        if next_stream == 0 {
-            return Err(Error::NoTestRuns);
-        }
-
-        let run_id = (next_stream - 1).to_string();
+        // The newest run ID may be missing if runs were deleted
+        let run_id = self.list_run_ids()?.pop().ok_or(Error::NoTestRuns)?;
         self.get_test_run(&run_id)
     }
 
@@ -394,14 +1044,53 @@ impl Repository for FileRepository {
 
     fn update_failing_tests(&mut self, run: &TestRun) -> Result<()> {
         // For update mode (partial runs), merge with existing failing tests
+        let _lock = self.lock()?;
+        self.write_run_mode(&run.id, true)?;
         self.update_failing_run_from_raw(&run.id)
     }
 
     fn replace_failing_tests(&mut self, run: &TestRun) -> Result<()> {
         // For replace mode (full runs), completely replace the failing file
+        let _lock = self.lock()?;
+        self.write_run_mode(&run.id, false)?;
         self.write_failing_run_from_raw(&run.id)
     }
 
+    fn add_failing_tests(&mut self, results: &[TestResult]) -> Result<()> {
+        let _lock = self.lock()?;
+        let mut added = TestRun::new("failing".to_string());
+        for result in results {
+            added.add_result(result.clone());
+        }
+
+        let failing_path = self.get_failing_path();
+        let temp_path = failing_path.with_extension("tmp");
+        {
+            let mut writer = File::create(&temp_path)?;
+
+            // Keep the recorded events, with their attachments, of the other tests
+            if failing_path.exists() {
+                for item in subunit::io::sync::iter_stream(File::open(&failing_path)?) {
+                    if let Ok(subunit::types::stream::ScannedItem::Event(event)) = item {
+                        let replaced = event.test_id.as_ref().is_some_and(|test_id| {
+                            added.results.contains_key(&TestId::new(test_id))
+                        });
+                        if !replaced {
+                            event.serialize(&mut writer).map_err(|e| {
+                                Error::Subunit(format!("Failed to serialize: {}", e))
+                            })?;
+                        }
+                    }
+                }
+            }
+
+            subunit_stream::write_stream(&added, &mut writer)?;
+        }
+        fs::rename(&temp_path, &failing_path)?;
+
+        Ok(())
+    }
+
     fn get_failing_tests(&self) -> Result<Vec<TestId>> {
         let failing = self.read_failing_run()?;
         Ok(failing.keys().cloned().collect())
@@ -414,13 +1103,7 @@ impl Repository for FileRepository {
     }
 
     fn get_test_times(&self) -> Result<HashMap<TestId, Duration>> {
-        // TODO: The gdbm crate doesn't expose iteration methods (firstkey/nextkey).
-        // For now, return empty HashMap. This method isn't currently used in the CLI.
-        // When needed, we can either:
-        // 1. Add iteration support to the gdbm crate
-        // 2. Use a different approach (e.g., maintain a separate index)
-        // 3. Use get_test_times_for_ids() for specific lookups
-        Ok(HashMap::new())
+        self.read_times()
     }
 
     fn get_test_times_for_ids(&self, test_ids: &[TestId]) -> Result<HashMap<TestId, Duration>> {
@@ -428,7 +1111,12 @@ impl Repository for FileRepository {
         self.get_test_times_for_ids_impl(test_ids)
     }
 
+    fn get_test_timings_for_ids(&self, test_ids: &[TestId]) -> Result<HashMap<TestId, TestTiming>> {
+        self.get_test_timings_for_ids_impl(test_ids)
+    }
+
     fn update_test_times(&mut self, times: &HashMap<TestId, Duration>) -> Result<()> {
+        let _lock = self.lock()?;
         self.update_test_times_impl(times)
     }
 
@@ -454,6 +1142,157 @@ impl Repository for FileRepository {
     fn count(&self) -> Result<usize> {
         Ok(self.list_run_ids()?.len())
     }
+
+    fn delete_run(&mut self, run_id: &str) -> Result<()> {
+        // Checked under the lock, so that another pruner deleting the same
+        // run makes this one report it as not found
+        let _lock = self.lock()?;
+        let path = self.get_run_path(run_id);
+        if !path.is_file() || run_id.parse::<u64>().is_err() {
+            return Err(Error::TestRunNotFound(run_id.to_string()));
+        }
+        fs::remove_file(&path)?;
+        let meta_path = self.get_run_meta_path(run_id);
+        if meta_path.exists() {
+            fs::remove_file(&meta_path)?;
+        }
+        Ok(())
+    }
+
+    fn get_run_time(&self, run_id: &str) -> Result<DateTime<Utc>> {
+        let path = self.get_run_path(run_id);
+        if !path.is_file() {
+            return Err(Error::TestRunNotFound(run_id.to_string()));
+        }
+        match self.read_run_metadata(run_id) {
+            Ok(RunMetadata {
+                start_time: Some(start_time),
+                ..
+            }) => Ok(start_time),
+            _ => Ok(fs::metadata(&path)?.modified()?.into()),
+        }
+    }
+
+    fn get_run_metadata(&self, run_id: &str) -> Result<RunMetadata> {
+        if !self.get_run_path(run_id).is_file() {
+            return Err(Error::TestRunNotFound(run_id.to_string()));
+        }
+        self.read_run_metadata(run_id)
+    }
+
+    fn set_run_metadata(&mut self, run_id: &str, metadata: &RunMetadata) -> Result<()> {
+        if !self.get_run_path(run_id).is_file() {
+            return Err(Error::TestRunNotFound(run_id.to_string()));
+        }
+        let _lock = self.lock()?;
+        let mut metadata = metadata.clone();
+        if metadata.partial.is_none() {
+            metadata.partial = self.read_run_metadata(run_id)?.partial;
+        }
+        self.write_run_metadata(run_id, &metadata)
+    }
+
+    fn rebuild_failing_and_times(&mut self, run_ids: &[String]) -> Result<()> {
+        let _lock = self.lock()?;
+        self.replay_runs(run_ids)
+    }
+
+    fn check(&mut self, repair: bool) -> Result<Vec<CheckIssue>> {
+        let _lock = if repair { Some(self.lock()?) } else { None };
+        let mut issues = Vec::new();
+
+        // Runs in progress allocate their IDs under the lock, so with it
+        // held the counter can be set straight after the highest run
+        let stored = run_files(&self.path)?;
+        let mut runs = stored.clone();
+        runs.extend(runs_in_progress(&self.path)?);
+        let counter = self.read_next_stream().ok();
+        if let Some(next_run) =
+            check_run_numbering("next-stream", counter, &runs, repair, &mut issues)
+        {
+            if repair {
+                self.write_next_stream(next_run)?;
+            }
+        }
+
+        let counter_issues = issues.len();
+        let mut intact_runs = Vec::new();
+        for n in &stored {
+            let run_id = n.to_string();
+            let path = self.get_run_path(&run_id);
+            let location = format!("run {}", run_id);
+            if !check_stream(&path, &location, repair, &mut issues)? {
+                intact_runs.push(run_id);
+            }
+        }
+        let runs_damaged = issues.len() > counter_issues;
+        check_stale_run_files(&self.path, repair, &mut issues)?;
+
+        // failing and the times database are derived from the runs, so when
+        // either is damaged both are rebuilt from the runs that are intact
+        let mut derived_damaged = false;
+        let temp_path = self.get_failing_path().with_extension("tmp");
+        if temp_path.exists() {
+            if repair {
+                fs::remove_file(&temp_path)?;
+            }
+            issues.push(CheckIssue {
+                location: "failing".to_string(),
+                message: "stale failing.tmp left by an interrupted update".to_string(),
+                repaired: repair,
+            });
+            derived_damaged = true;
+        }
+
+        let failing_path = self.get_failing_path();
+        if failing_path.exists() {
+            let mut failing_issues = Vec::new();
+            if check_stream(&failing_path, "failing", false, &mut failing_issues)? {
+                derived_damaged = true;
+            }
+            issues.extend(failing_issues.into_iter().map(|issue| CheckIssue {
+                repaired: repair,
+                ..issue
+            }));
+        }
+
+        if let Err(e) = self.read_times() {
+            issues.push(CheckIssue {
+                location: "times.dbm".to_string(),
+                message: format!("unreadable: {}", e),
+                repaired: repair,
+            });
+            derived_damaged = true;
+        }
+
+        if repair && (runs_damaged || derived_damaged) {
+            self.replay_runs(&intact_runs)?;
+        }
+
+        Ok(issues)
+    }
+
+    fn get_config(&self) -> Result<RepoConfig> {
+        RepoConfig::load_from_file(&self.get_config_path())
+    }
+
+    fn set_config(&mut self, config: &RepoConfig) -> Result<()> {
+        let _lock = self.lock()?;
+        let path = self.get_config_path();
+        let temp_path = path.with_extension("conf.tmp");
+        fs::write(&temp_path, config.to_ini_string())?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    fn get_bookmarks(&self) -> Result<BTreeMap<String, String>> {
+        run_spec::load_bookmarks(&self.get_bookmarks_path())
+    }
+
+    fn set_bookmark(&mut self, name: &str, run_id: Option<&str>) -> Result<()> {
+        let _lock = self.lock()?;
+        run_spec::store_bookmark(&self.get_bookmarks_path(), name, run_id)
+    }
 }
 
 #[cfg(test)]
@@ -523,7 +1362,7 @@ mod tests {
 
         // Write the test run as subunit stream
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
 
         assert_eq!(run_id, "0");
         assert_eq!(repo.get_next_run_id().unwrap(), 1);
@@ -546,12 +1385,12 @@ mod tests {
         let run = TestRun::new("0".to_string());
         let (_, mut writer) = repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
 
         let run = TestRun::new("1".to_string());
         let (_, mut writer) = repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
 
         let ids = repo.list_run_ids().unwrap();
         assert_eq!(ids.len(), 2);
@@ -569,7 +1408,7 @@ mod tests {
         let run = TestRun::new("0".to_string());
         let (_, mut writer) = repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
 
         assert_eq!(repo.count().unwrap(), 1);
     }
@@ -599,7 +1438,7 @@ mod tests {
         // Stream and store first run
         let (_, mut writer) = repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run1, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
         repo.replace_failing_tests(&run1).unwrap();
 
         // Check failing tests after first run
@@ -616,7 +1455,7 @@ mod tests {
         // Stream and store second run
         let (_, mut writer) = repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run2, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
         repo.update_failing_tests(&run2).unwrap(); // Partial mode
 
         // Check failing tests after partial run
@@ -641,7 +1480,7 @@ mod tests {
 
         let (_, mut writer) = repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run1, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
         repo.replace_failing_tests(&run1).unwrap();
 
         let failing = repo.get_failing_tests().unwrap();
@@ -656,7 +1495,7 @@ mod tests {
 
         let (_, mut writer) = repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run2, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
         repo.replace_failing_tests(&run2).unwrap(); // Full mode
 
         // Check that failing tests were replaced, not updated
@@ -667,6 +1506,38 @@ mod tests {
         assert!(!failing.iter().any(|id| id.as_str() == "test2"));
     }
 
+    #[test]
+    fn test_add_failing_tests() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+
+        let mut run = TestRun::new("0".to_string());
+        run.add_result(TestResult::failure("test1", "Failed").with_details("Traceback"));
+        run.add_result(TestResult::success("test2"));
+        repo.insert_test_run(run).unwrap();
+
+        repo.add_failing_tests(&[TestResult::failure("test2", "Flaky")])
+            .unwrap();
+
+        let mut failing = repo.get_failing_tests().unwrap();
+        failing.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        assert_eq!(failing, vec![TestId::new("test1"), TestId::new("test2")]);
+
+        // The tests already failing keep their attachments
+        let failing_run = crate::subunit_stream::parse_stream(
+            repo.get_failing_tests_raw().unwrap(),
+            "failing".to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            failing_run.results[&TestId::new("test1")]
+                .details
+                .as_deref(),
+            Some("Traceback")
+        );
+    }
+
     #[test]
     fn test_times_database_write_and_read() {
         let temp = TempDir::new().unwrap();
@@ -691,7 +1562,7 @@ mod tests {
         // Insert the run (should write times to database)
         let (_, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
 
         // Update times
         use std::collections::HashMap;
@@ -724,6 +1595,133 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_all_test_times() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+
+        // No database yet
+        assert!(repo.get_test_times().unwrap().is_empty());
+
+        let mut times = HashMap::new();
+        times.insert(TestId::new("test1"), Duration::from_secs_f64(1.5));
+        times.insert(TestId::new("test2"), Duration::from_secs_f64(0.25));
+        repo.update_test_times(&times).unwrap();
+
+        assert_eq!(repo.get_test_times().unwrap(), times);
+    }
+
+    #[test]
+    fn test_times_history() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+        let test1 = TestId::new("test1");
+
+        for secs in [1.0, 3.0] {
+            let times = HashMap::from([(test1.clone(), Duration::from_secs_f64(secs))]);
+            repo.update_test_times(&times).unwrap();
+        }
+
+        // Python only reads the latest duration, stored under the test ID
+        let times_path = temp.path().join(".testrepository").join("times.dbm");
+        let conn = rusqlite::Connection::open(&times_path).unwrap();
+        let value: Vec<u8> = conn
+            .query_row(
+                "SELECT value FROM Dict WHERE key = ?",
+                [&b"test1"[..]],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(value, b"3");
+
+        let timing = &repo.get_test_timings_for_ids(&[test1.clone()]).unwrap()[&test1];
+        assert_eq!(timing.count(), 2);
+        assert_eq!(timing.mean(), Some(Duration::from_secs(2)));
+
+        // A duration written by Python, which doesn't update History, counts too
+        conn.execute(
+            "UPDATE Dict SET value = ? WHERE key = ?",
+            [&b"5"[..], &b"test1"[..]],
+        )
+        .unwrap();
+        let timing = &repo.get_test_timings_for_ids(&[test1.clone()]).unwrap()[&test1];
+        assert_eq!(
+            timing.samples(),
+            [1, 3, 5].map(Duration::from_secs).as_slice()
+        );
+    }
+
+    #[test]
+    fn test_is_sqlite_database() {
+        let temp = TempDir::new().unwrap();
+
+        let empty = temp.path().join("empty");
+        fs::write(&empty, b"").unwrap();
+        assert!(FileRepository::is_sqlite_database(&empty).unwrap());
+
+        let other = temp.path().join("other");
+        fs::write(&other, b"not a database at all").unwrap();
+        assert!(!FileRepository::is_sqlite_database(&other).unwrap());
+
+        let sqlite = temp.path().join("sqlite");
+        let conn = rusqlite::Connection::open(&sqlite).unwrap();
+        conn.execute("CREATE TABLE Dict (key BLOB PRIMARY KEY, value BLOB)", [])
+            .unwrap();
+        drop(conn);
+        assert!(FileRepository::is_sqlite_database(&sqlite).unwrap());
+    }
+
+    #[test]
+    fn test_gdbm_times_are_migrated() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+        let times_path = temp.path().join(".testrepository/times.dbm");
+        let db = gdbm::Gdbm::new(&times_path, 0, gdbm::Open::NEWDB, 0o644).unwrap();
+        db.store(b"test1", b"1.5", true).unwrap();
+        db.store(b"test2", b"2", true).unwrap();
+        drop(db);
+        assert!(FileRepository::is_gdbm_database(&times_path).unwrap());
+        assert!(!FileRepository::is_sqlite_database(&times_path).unwrap());
+
+        // Every test is read, whether or not any run has it
+        let expected = HashMap::from([
+            (TestId::new("test1"), Duration::from_secs_f64(1.5)),
+            (TestId::new("test2"), Duration::from_secs(2)),
+        ]);
+        assert_eq!(repo.get_test_times().unwrap(), expected);
+
+        // Replaying the runs migrates it, keeping every test's timing
+        fs::write(temp.path().join(".testrepository/failing"), [0xb3, 0x20]).unwrap();
+        let issues = repo.check(false).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].location, "failing");
+        assert!(repo.check(true).unwrap()[0].repaired);
+        assert!(FileRepository::is_sqlite_database(&times_path).unwrap());
+        assert!(temp.path().join(".testrepository/times.dbm.gdbm").exists());
+        assert_eq!(repo.get_test_times().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_reading_times_leaves_database_alone() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let repo = factory.initialise(temp.path()).unwrap();
+
+        // A database in neither format is reported, not converted or replaced
+        let times_path = temp.path().join(".testrepository/times.dbm");
+        fs::write(&times_path, b"neither SQLite nor GDBM").unwrap();
+        assert!(repo.get_test_times().is_err());
+        assert!(repo
+            .get_test_times_for_ids(&[TestId::new("test1")])
+            .unwrap()
+            .is_empty());
+        assert_eq!(fs::read(&times_path).unwrap(), b"neither SQLite nor GDBM");
+        assert!(!temp.path().join(".testrepository/times.dbm.gdbm").exists());
+    }
+
     #[test]
     fn test_times_database_updates_on_multiple_runs() {
         let temp = TempDir::new().unwrap();
@@ -745,7 +1743,7 @@ mod tests {
 
         let (_, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run1, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
 
         use std::collections::HashMap;
         let mut times = HashMap::new();
@@ -763,7 +1761,7 @@ mod tests {
 
         let (_, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run2, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
 
         let mut times = HashMap::new();
         for result in run2.results.values() {
@@ -798,7 +1796,7 @@ mod tests {
         // Write as raw stream
         let (_, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
 
         // Replace failing tests (full run mode)
         file_repo.replace_failing_tests(&run).unwrap();
@@ -833,7 +1831,7 @@ mod tests {
         // Write as raw stream and update failing tests
         let (_, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
         file_repo.replace_failing_tests(&run).unwrap();
 
         // Get failing tests
@@ -866,7 +1864,7 @@ mod tests {
 
         let (_, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run1, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
         file_repo.replace_failing_tests(&run1).unwrap();
 
         // Get the list of failing tests (this is what --failing would use)
@@ -905,7 +1903,7 @@ mod tests {
         // Write and read back
         let (run_id, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
 
         // Should successfully read small file
         let retrieved = file_repo.get_test_run(&run_id).unwrap();
@@ -934,7 +1932,7 @@ mod tests {
         // Write and read back
         let (run_id, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
 
         // Verify file is actually > 4KB
         let file_path = temp.path().join(".testrepository").join(&run_id);
@@ -962,7 +1960,7 @@ mod tests {
 
         let (_, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
         file_repo.replace_failing_tests(&run).unwrap();
 
         // Should successfully read small failing file
@@ -990,7 +1988,7 @@ mod tests {
 
         let (_, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
         file_repo.replace_failing_tests(&run).unwrap();
 
         // Verify failing file is > 4KB
@@ -1019,7 +2017,7 @@ mod tests {
 
         let (_, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run1, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
         file_repo.replace_failing_tests(&run1).unwrap();
 
         // Verify all 3 failing
@@ -1034,7 +2032,7 @@ mod tests {
 
         let (_, mut writer) = file_repo.begin_test_run_raw().unwrap();
         crate::subunit_stream::write_stream(&run2, &mut writer).unwrap();
-        drop(writer);
+        writer.commit().unwrap();
         file_repo.update_failing_tests(&run2).unwrap(); // Partial mode
 
         // After partial update:
@@ -1046,4 +2044,357 @@ mod tests {
         assert!(failing.contains(&TestId::new("test2")), "test2 should remain");
         assert!(failing.contains(&TestId::new("test3")), "test3 should remain");
     }
+
+    #[test]
+    fn test_repo_config_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+
+        // No repo.conf yet means default settings
+        assert_eq!(repo.get_config().unwrap(), RepoConfig::default());
+
+        let mut config = RepoConfig::default();
+        config.set("concurrency", Some("3")).unwrap();
+        repo.set_config(&config).unwrap();
+
+        assert!(temp.path().join(REPO_DIR).join("repo.conf").exists());
+        let repo = factory.open(temp.path()).unwrap();
+        assert_eq!(repo.get_config().unwrap().concurrency, Some(3));
+    }
+
+    #[test]
+    fn test_delete_run() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+
+        for i in 0..3 {
+            let mut run = TestRun::new(i.to_string());
+            run.add_result(TestResult::failure("test1", "Failed"));
+            repo.insert_test_run(run).unwrap();
+        }
+
+        repo.delete_run("2").unwrap();
+        repo.delete_run("0").unwrap();
+
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["1"]);
+        assert_eq!(repo.get_latest_run().unwrap().id, "1");
+        // IDs are not reused and the failing tests survive
+        assert_eq!(repo.get_next_run_id().unwrap(), 3);
+        assert_eq!(
+            repo.get_failing_tests().unwrap(),
+            vec![TestId::new("test1")]
+        );
+
+        assert!(matches!(
+            repo.delete_run("2"),
+            Err(Error::TestRunNotFound(_))
+        ));
+        assert!(matches!(
+            repo.delete_run("failing"),
+            Err(Error::TestRunNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_and_repair() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+        let repo_path = temp.path().join(".testrepository");
+
+        let mut run = TestRun::new("0".to_string());
+        run.add_result(
+            TestResult::failure("test1", "Failed").with_duration(Duration::from_secs(1)),
+        );
+        repo.insert_test_run(run).unwrap();
+        let mut run = TestRun::new("1".to_string());
+        run.add_result(TestResult::success("test2").with_duration(Duration::from_secs(2)));
+        repo.insert_test_run_partial(run, true).unwrap();
+        assert!(repo.check(false).unwrap().is_empty());
+
+        // Simulate a crash: a torn write, a stale counter and a leftover temp file
+        let intact_len = fs::metadata(repo_path.join("1")).unwrap().len();
+        let mut torn = fs::read(repo_path.join("1")).unwrap();
+        torn.extend_from_slice(&[0xb3, 0x20]);
+        fs::write(repo_path.join("1"), torn).unwrap();
+        fs::write(repo_path.join("next-stream"), "1\n").unwrap();
+        fs::write(repo_path.join("failing.tmp"), b"partial").unwrap();
+        fs::remove_file(repo_path.join("times.dbm")).unwrap();
+
+        let issues = repo.check(false).unwrap();
+        let locations: Vec<_> = issues.iter().map(|i| i.location.as_str()).collect();
+        assert_eq!(locations, vec!["next-stream", "run 1", "failing"]);
+        assert!(issues.iter().all(|i| !i.repaired));
+        assert!(issues[1]
+            .message
+            .starts_with(&format!("offset {}:", intact_len)));
+
+        let issues = repo.check(true).unwrap();
+        assert_eq!(issues.len(), 3);
+        assert!(issues.iter().all(|i| i.repaired));
+        assert!(repo.check(false).unwrap().is_empty());
+
+        assert_eq!(repo.get_next_run_id().unwrap(), 2);
+        assert_eq!(fs::metadata(repo_path.join("1")).unwrap().len(), intact_len);
+        assert!(!repo_path.join("failing.tmp").exists());
+        assert_eq!(
+            repo.get_failing_tests().unwrap(),
+            vec![TestId::new("test1")]
+        );
+        let times = repo.get_test_times().unwrap();
+        assert_eq!(
+            times.get(&TestId::new("test1")),
+            Some(&Duration::from_secs(1))
+        );
+        assert_eq!(
+            times.get(&TestId::new("test2")),
+            Some(&Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn test_uncommitted_run_is_discarded() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+        let repo_path = temp.path().join(".testrepository");
+
+        // A writer dropped part way through a run leaves nothing behind
+        let (run_id, mut writer) = repo.begin_test_run_raw().unwrap();
+        writer.write_all(b"partial output").unwrap();
+        drop(writer);
+        assert!(!repo_path.join(&run_id).exists());
+        assert!(!repo_path.join(format!("{}.tmp", run_id)).exists());
+        assert!(repo.list_run_ids().unwrap().is_empty());
+        // and gives its ID back to the next run
+        assert_eq!(repo.get_next_run_id().unwrap(), 0);
+
+        // A temporary file left by a killed process is stale, unless a
+        // writer still has it open
+        fs::write(repo_path.join("5.tmp"), b"partial output").unwrap();
+        let (run_id, writer) = repo.begin_test_run_raw().unwrap();
+        let issues = repo.check(false).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].location, "run 5");
+        assert_eq!(issues[0].message, "stale 5.tmp left by an interrupted run");
+
+        assert_eq!(repo.check(true).unwrap().len(), 1);
+        assert!(!repo_path.join("5.tmp").exists());
+        assert!(repo_path.join(format!("{}.tmp", run_id)).exists());
+        writer.commit().unwrap();
+        assert!(repo.check(false).unwrap().is_empty());
+        assert_eq!(repo.list_run_ids().unwrap(), vec![run_id]);
+    }
+
+    #[test]
+    fn test_check_run_numbering() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+        let repo_path = temp.path().join(".testrepository");
+
+        for i in 0..5 {
+            let mut run = TestRun::new(i.to_string());
+            run.add_result(TestResult::success("test1"));
+            repo.insert_test_run(run).unwrap();
+        }
+        fs::remove_file(repo_path.join("2")).unwrap();
+        fs::remove_file(repo_path.join("3")).unwrap();
+        fs::write(repo_path.join("next-stream"), "9\n").unwrap();
+
+        let issues = repo.check(false).unwrap();
+        let found: Vec<_> = issues
+            .iter()
+            .map(|i| (i.location.as_str(), i.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("next-stream", "next-stream is 9 but the highest run is 4"),
+                ("runs 2-3", "missing between run 1 and run 4"),
+            ]
+        );
+
+        // The counter is put back after the highest run, but lost runs
+        // can't be brought back
+        let issues = repo.check(true).unwrap();
+        assert!(issues[0].repaired);
+        assert!(!issues[1].repaired);
+        assert_eq!(repo.get_next_run_id().unwrap(), 5);
+
+        // A run in progress counts as the highest run
+        let (run_id, writer) = repo.begin_test_run_raw().unwrap();
+        assert_eq!(run_id, "5");
+        let issues = repo.check(true).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].location, "runs 2-3");
+        assert_eq!(repo.get_next_run_id().unwrap(), 6);
+        writer.commit().unwrap();
+    }
+
+    #[test]
+    fn test_check_ignores_non_ascii_output() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+
+        let mut run = TestRun::new("0".to_string());
+        run.add_result(TestResult::success("test1"));
+        let (run_id, mut writer) = repo.begin_test_run_raw().unwrap();
+        writer.write_all("Größe: 3 m³\n".as_bytes()).unwrap();
+        crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
+        writer.write_all("готово ó".as_bytes()).unwrap();
+        writer.commit().unwrap();
+
+        let path = temp.path().join(".testrepository").join(&run_id);
+        let len = fs::metadata(&path).unwrap().len();
+        assert!(repo.check(true).unwrap().is_empty());
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+    }
+
+    #[test]
+    fn test_rebuild_failing_respects_run_modes() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+
+        let runs = [
+            (vec![("a", false), ("b", false)], false),
+            (vec![("a", true)], true),
+            (vec![("c", false)], false),
+            (vec![("c", true), ("d", false)], true),
+        ];
+        for (i, (results, partial)) in runs.into_iter().enumerate() {
+            let mut run = TestRun::new(i.to_string());
+            for (test_id, passed) in results {
+                run.add_result(if passed {
+                    TestResult::success(test_id)
+                } else {
+                    TestResult::failure(test_id, "Failed")
+                });
+            }
+            repo.insert_test_run_partial(run, partial).unwrap();
+        }
+
+        let failing = |repo: &dyn Repository| {
+            let mut ids: Vec<_> = repo
+                .get_failing_tests()
+                .unwrap()
+                .into_iter()
+                .map(|id| id.as_str().to_string())
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(failing(&*repo), vec!["d"]);
+
+        let all = repo.list_run_ids().unwrap();
+        repo.rebuild_failing_and_times(&all[..2]).unwrap();
+        assert_eq!(failing(&*repo), vec!["b"]);
+
+        repo.rebuild_failing_and_times(&all).unwrap();
+        assert_eq!(failing(&*repo), vec!["d"]);
+
+        // A run without a recorded mode is replayed as partial
+        fs::remove_file(temp.path().join(".testrepository").join("2.meta")).unwrap();
+        repo.rebuild_failing_and_times(&all).unwrap();
+        assert_eq!(failing(&*repo), vec!["b", "d"]);
+
+        repo.delete_run("3").unwrap();
+        assert!(!temp.path().join(".testrepository").join("3.meta").exists());
+    }
+
+    #[test]
+    fn test_run_metadata() {
+        let temp = TempDir::new().unwrap();
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+        let run_id = repo
+            .insert_test_run_partial(TestRun::new("0".to_string()), true)
+            .unwrap();
+
+        assert_eq!(
+            repo.get_run_metadata(&run_id).unwrap(),
+            RunMetadata {
+                partial: Some(true),
+                ..Default::default()
+            }
+        );
+
+        let start_time = DateTime::from_timestamp(1000000000, 0).unwrap();
+        let metadata = RunMetadata {
+            start_time: Some(start_time),
+            end_time: DateTime::from_timestamp(1000000010, 0),
+            command: Some("python -m test".to_string()),
+            concurrency: Some(2),
+            exit_codes: vec![0, 1],
+            hostname: Some("build-1".to_string()),
+            ..Default::default()
+        };
+        repo.set_run_metadata(&run_id, &metadata).unwrap();
+
+        // The recorded mode survives, and the run is dated by its start
+        let stored = repo.get_run_metadata(&run_id).unwrap();
+        assert_eq!(stored.partial, Some(true));
+        assert_eq!(stored.command.as_deref(), Some("python -m test"));
+        assert_eq!(stored.exit_codes, vec![0, 1]);
+        assert_eq!(repo.get_test_run(&run_id).unwrap().timestamp, start_time);
+        assert_eq!(repo.get_run_time(&run_id).unwrap(), start_time);
+
+        assert!(matches!(
+            repo.get_run_metadata("1"),
+            Err(Error::TestRunNotFound(_))
+        ));
+        assert!(repo.set_run_metadata("1", &metadata).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let temp = TempDir::new().unwrap();
+        FileRepositoryFactory.initialise(temp.path()).unwrap();
+
+        let writers: Vec<_> = (0..4)
+            .map(|i| {
+                let base = temp.path().to_path_buf();
+                std::thread::spawn(move || {
+                    let mut repo = FileRepositoryFactory.open(&base).unwrap();
+                    let mut run_ids = Vec::new();
+                    for _ in 0..5 {
+                        let (run_id, mut writer) = repo.begin_test_run_raw().unwrap();
+                        let mut run = TestRun::new(run_id.clone());
+                        run.add_result(TestResult::failure(
+                            format!("test{}", i).as_str(),
+                            "Failed",
+                        ));
+                        crate::subunit_stream::write_stream(&run, &mut writer).unwrap();
+                        writer.commit().unwrap();
+                        repo.update_failing_tests(&run).unwrap();
+                        run_ids.push(run_id);
+                    }
+                    run_ids
+                })
+            })
+            .collect();
+
+        let mut run_ids: Vec<u64> = writers
+            .into_iter()
+            .flat_map(|writer| writer.join().unwrap())
+            .map(|run_id| run_id.parse().unwrap())
+            .collect();
+        run_ids.sort();
+        assert_eq!(run_ids, (0..20).collect::<Vec<_>>());
+
+        let repo = FileRepositoryFactory.open(temp.path()).unwrap();
+        assert_eq!(repo.count().unwrap(), 20);
+        let mut failing: Vec<_> = repo
+            .get_failing_tests()
+            .unwrap()
+            .into_iter()
+            .map(|id| id.as_str().to_string())
+            .collect();
+        failing.sort();
+        assert_eq!(failing, vec!["test0", "test1", "test2", "test3"]);
+    }
 }
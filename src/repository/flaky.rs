@@ -0,0 +1,243 @@
+//! Detection of flaky tests from stored runs
+//!
+//! A test is flaky when its outcome flips between passing and failing while
+//! the code under test stays the same. Two runs are taken to have tested the
+//! same code when they recorded the same git commit and neither had
+//! uncommitted changes; runs made outside a git checkout, or by versions that
+//! didn't record the commit, are never compared. Repeated runs of the same
+//! revision, such as the iterations of `testr run --until-failure`, are
+//! therefore compared with each other.
+//!
+//! A test that failed and then passed when `testr run --retry` re-ran it has
+//! flipped within a single run, whatever the revision. The results of retried
+//! tests are tagged with the attempt that produced them.
+
+use crate::error::Result;
+use crate::repository::{
+    HistoryEntry, Repository, RunMetadata, TestId, TestResult, TestSelector, TestStatus,
+};
+use std::collections::HashMap;
+
+/// Prefix of the tag carried by the result of a test that was retried
+pub const RETRY_TAG_PREFIX: &str = "retry-";
+
+/// The tag for the results of the given retry attempt, counting from 1
+pub fn retry_tag(attempt: usize) -> String {
+    format!("{}{}", RETRY_TAG_PREFIX, attempt)
+}
+
+/// The retry attempt that produced a result, or `None` for a first attempt
+pub fn retry_attempt(result: &TestResult) -> Option<usize> {
+    result
+        .tags
+        .iter()
+        .find_map(|tag| tag.strip_prefix(RETRY_TAG_PREFIX)?.parse().ok())
+}
+
+/// A test whose outcome changed between runs of the same code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlakyTest {
+    /// The test
+    pub test_id: TestId,
+    /// Number of times its outcome flipped between consecutive comparable runs
+    pub flips: usize,
+    /// Number of pairs of consecutive comparable runs it appeared in
+    pub comparisons: usize,
+    /// The run in which its outcome last flipped
+    pub last_flip: String,
+}
+
+impl FlakyTest {
+    /// How often the outcome flipped when it had a chance to, from 0 to 1
+    pub fn score(&self) -> f64 {
+        self.flips as f64 / self.comparisons as f64
+    }
+}
+
+/// The revision a run tested, if it is known to be a clean checkout
+fn revision(metadata: &RunMetadata) -> Option<String> {
+    match (&metadata.git_commit, metadata.git_dirty) {
+        (Some(commit), Some(false)) => Some(commit.clone()),
+        _ => None,
+    }
+}
+
+/// A test's outcome in the last run it passed or failed in
+struct LastOutcome {
+    revision: Option<String>,
+    passed: bool,
+}
+
+/// The entry for a test, created the first time it could have flipped
+fn flaky_entry<'a>(
+    flaky: &'a mut HashMap<TestId, FlakyTest>,
+    test_id: &TestId,
+) -> &'a mut FlakyTest {
+    flaky.entry(test_id.clone()).or_insert_with(|| FlakyTest {
+        test_id: test_id.clone(),
+        flips: 0,
+        comparisons: 0,
+        last_flip: String::new(),
+    })
+}
+
+/// Find the flaky tests among the selected tests in the given runs
+///
+/// Skipped tests are ignored. A test that passed on retry counts as having
+/// flipped in its run, in addition to any comparison with earlier runs. The
+/// result is sorted by score, highest first.
+pub fn find_flaky_tests(
+    repo: &dyn Repository,
+    run_ids: &[String],
+    tests: &TestSelector,
+) -> Result<Vec<FlakyTest>> {
+    let mut revisions = HashMap::new();
+    for run_id in run_ids {
+        revisions.insert(run_id.as_str(), revision(&repo.get_run_metadata(run_id)?));
+    }
+    let mut last: HashMap<TestId, LastOutcome> = HashMap::new();
+    let mut flaky: HashMap<TestId, FlakyTest> = HashMap::new();
+
+    // The history is in run order, so each result is compared with the
+    // test's result in the last run before it
+    for HistoryEntry { run_id, result, .. } in repo.get_test_history(tests)? {
+        let Some(revision) = revisions.get(run_id.as_str()) else {
+            continue;
+        };
+        let passed = match result.status {
+            TestStatus::Skip => continue,
+            status => !status.is_failure(),
+        };
+
+        if passed && retry_attempt(&result).is_some() {
+            let entry = flaky_entry(&mut flaky, &result.test_id);
+            entry.comparisons += 1;
+            entry.flips += 1;
+            entry.last_flip = run_id.clone();
+        }
+
+        let outcome = LastOutcome {
+            revision: revision.clone(),
+            passed,
+        };
+        let Some(previous) = last.insert(result.test_id.clone(), outcome) else {
+            continue;
+        };
+        if previous.revision.is_none() || previous.revision != *revision {
+            continue;
+        }
+
+        let entry = flaky_entry(&mut flaky, &result.test_id);
+        entry.comparisons += 1;
+        if previous.passed != passed {
+            entry.flips += 1;
+            entry.last_flip = run_id.clone();
+        }
+    }
+
+    let mut flaky: Vec<FlakyTest> = flaky.into_values().filter(|test| test.flips > 0).collect();
+    flaky.sort_by(|a, b| {
+        b.score()
+            .total_cmp(&a.score())
+            .then_with(|| a.test_id.as_str().cmp(b.test_id.as_str()))
+    });
+    Ok(flaky)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryRepositoryFactory;
+    use crate::repository::{RepositoryFactory, TestResult, TestRun};
+    use std::path::Path;
+
+    fn add_run(repo: &mut Box<dyn Repository>, commit: &str, results: Vec<TestResult>) -> String {
+        let mut run = TestRun::new(String::new());
+        for result in results {
+            run.add_result(result);
+        }
+        let run_id = repo.insert_test_run(run).unwrap();
+        repo.set_run_metadata(
+            &run_id,
+            &RunMetadata {
+                git_commit: Some(commit.to_string()),
+                git_dirty: Some(false),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        run_id
+    }
+
+    #[test]
+    fn test_find_flaky_tests() {
+        let factory = MemoryRepositoryFactory::new();
+        let mut repo = factory.initialise(Path::new("/repo")).unwrap();
+
+        let pass = TestResult::success;
+        let fail = |id| TestResult::failure(id, "boom");
+        add_run(
+            &mut repo,
+            "a",
+            vec![pass("flaky"), pass("broken"), pass("steady")],
+        );
+        add_run(
+            &mut repo,
+            "a",
+            vec![fail("flaky"), pass("broken"), pass("steady")],
+        );
+        add_run(&mut repo, "a", vec![pass("flaky"), pass("steady")]);
+        // A real regression: the outcome only changed with the code
+        add_run(
+            &mut repo,
+            "b",
+            vec![fail("broken"), TestResult::skip("flaky")],
+        );
+        add_run(&mut repo, "b", vec![fail("broken"), pass("flaky")]);
+
+        let run_ids = repo.list_run_ids().unwrap();
+        let flaky = find_flaky_tests(repo.as_ref(), &run_ids, &TestSelector::All).unwrap();
+        assert_eq!(
+            flaky,
+            vec![FlakyTest {
+                test_id: TestId::new("flaky"),
+                flips: 2,
+                comparisons: 2,
+                last_flip: "2".to_string(),
+            }]
+        );
+        assert_eq!(flaky[0].score(), 1.0);
+
+        // Runs without a recorded revision are never compared
+        let mut run = TestRun::new(String::new());
+        run.add_result(fail("steady"));
+        repo.insert_test_run(run).unwrap();
+        let run_ids = repo.list_run_ids().unwrap();
+        assert_eq!(
+            find_flaky_tests(repo.as_ref(), &run_ids, &TestSelector::All)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Passing on retry is a flip within the run, whatever the revision
+        let mut run = TestRun::new(String::new());
+        run.add_result(pass("steady").with_tag(retry_tag(2)));
+        let run_id = repo.insert_test_run(run).unwrap();
+        let run_ids = repo.list_run_ids().unwrap();
+        let flaky = find_flaky_tests(repo.as_ref(), &run_ids, &TestSelector::All).unwrap();
+        let steady = flaky.iter().find(|test| test.test_id.as_str() == "steady");
+        assert_eq!(steady.unwrap().last_flip, run_id);
+        assert_eq!(
+            retry_attempt(&pass("steady").with_tag(retry_tag(2))),
+            Some(2)
+        );
+        assert_eq!(retry_attempt(&pass("steady")), None);
+
+        // Only the selected tests are considered
+        let selected = TestSelector::AnyOf(vec![TestId::new("steady")]);
+        let flaky = find_flaky_tests(repo.as_ref(), &run_ids, &selected).unwrap();
+        assert_eq!(flaky.len(), 1);
+        assert_eq!(flaky[0].test_id.as_str(), "steady");
+    }
+}
@@ -0,0 +1,492 @@
+//! In-memory repository implementation
+//!
+//! Nothing is written to disk, which makes this repository convenient for
+//! library users and tests. Repositories are kept by their factory, so a
+//! repository initialised through a factory can be opened again through the
+//! same factory (or a clone of it) for as long as the factory lives.
+
+use crate::error::{Error, Result};
+use crate::repository::{
+    CheckIssue, RepoConfig, Repository, RepositoryFactory, RunMetadata, RunStreamWriter, TestId,
+    TestResult, TestRun, TestTiming,
+};
+use crate::subunit_stream;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// A stored run: its raw stream and what is known about how it was stored
+struct StoredRun {
+    raw: Vec<u8>,
+    stored_at: DateTime<Utc>,
+    metadata: RunMetadata,
+}
+
+/// Everything held by one in-memory repository
+#[derive(Default)]
+struct MemoryState {
+    next_run: u64,
+    runs: BTreeMap<u64, StoredRun>,
+    failing: HashMap<TestId, TestResult>,
+    times: HashMap<TestId, TestTiming>,
+    config: RepoConfig,
+    bookmarks: BTreeMap<String, String>,
+}
+
+type SharedState = Arc<Mutex<MemoryState>>;
+
+fn lock(state: &SharedState) -> MutexGuard<'_, MemoryState> {
+    // A panic elsewhere can't leave the state half updated, so carry on
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Factory for creating in-memory repositories.
+///
+/// Repositories are keyed by their base path, which is never touched.
+#[derive(Clone, Default)]
+pub struct MemoryRepositoryFactory {
+    repos: Arc<Mutex<HashMap<PathBuf, SharedState>>>,
+}
+
+impl MemoryRepositoryFactory {
+    /// Creates a factory with no repositories.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RepositoryFactory for MemoryRepositoryFactory {
+    fn initialise(&self, base: &Path) -> Result<Box<dyn Repository>> {
+        let mut repos = self.repos.lock().unwrap_or_else(|e| e.into_inner());
+        if repos.contains_key(base) {
+            return Err(Error::RepositoryExists(base.to_path_buf()));
+        }
+
+        let state = SharedState::default();
+        repos.insert(base.to_path_buf(), state.clone());
+        Ok(Box::new(MemoryRepository { state }))
+    }
+
+    fn open(&self, base: &Path) -> Result<Box<dyn Repository>> {
+        let repos = self.repos.lock().unwrap_or_else(|e| e.into_inner());
+        let state = repos
+            .get(base)
+            .ok_or_else(|| Error::RepositoryNotFound(base.to_path_buf()))?;
+        Ok(Box::new(MemoryRepository {
+            state: state.clone(),
+        }))
+    }
+}
+
+/// In-memory repository implementation.
+///
+/// Handles opened from the same factory share one repository.
+pub struct MemoryRepository {
+    state: SharedState,
+}
+
+/// Writer for a new run's stream, which is stored when the writer is committed
+struct MemoryRunWriter {
+    state: SharedState,
+    run_id: u64,
+    buffer: Vec<u8>,
+}
+
+impl Write for MemoryRunWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl RunStreamWriter for MemoryRunWriter {
+    fn commit(self: Box<Self>) -> Result<()> {
+        lock(&self.state).runs.insert(
+            self.run_id,
+            StoredRun {
+                raw: self.buffer,
+                stored_at: Utc::now(),
+                metadata: RunMetadata::default(),
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Parse a run ID, treating anything that isn't a number as an unknown run
+fn parse_run_id(run_id: &str) -> Result<u64> {
+    run_id
+        .parse()
+        .map_err(|_| Error::TestRunNotFound(run_id.to_string()))
+}
+
+impl MemoryState {
+    fn stored_run(&self, run_id: &str) -> Result<&StoredRun> {
+        self.runs
+            .get(&parse_run_id(run_id)?)
+            .ok_or_else(|| Error::TestRunNotFound(run_id.to_string()))
+    }
+
+    fn parse_run(&self, run_id: &str) -> Result<TestRun> {
+        let stored = self.stored_run(run_id)?;
+        let mut run = subunit_stream::parse_stream(&stored.raw[..], run_id.to_string())?;
+        run.timestamp = stored.metadata.start_time.unwrap_or(stored.stored_at);
+        Ok(run)
+    }
+
+    /// Apply a stored run's results to the failing tests, as a partial or full run
+    fn apply_to_failing(&mut self, run_id: &str, partial: bool) -> Result<()> {
+        let run = self.parse_run(run_id)?;
+        if !partial {
+            self.failing.clear();
+        }
+        for result in run.results.into_values() {
+            if result.status.is_failure() {
+                self.failing.insert(result.test_id.clone(), result);
+            } else if result.status.is_success() {
+                self.failing.remove(&result.test_id);
+            }
+        }
+        Ok(())
+    }
+
+    fn record_failing(&mut self, run_id: &str, partial: bool) -> Result<()> {
+        self.apply_to_failing(run_id, partial)?;
+        if let Some(stored) = self.runs.get_mut(&parse_run_id(run_id)?) {
+            stored.metadata.partial = Some(partial);
+        }
+        Ok(())
+    }
+}
+
+impl Repository for MemoryRepository {
+    fn get_test_run(&self, run_id: &str) -> Result<TestRun> {
+        lock(&self.state).parse_run(run_id)
+    }
+
+    fn begin_test_run_raw(&mut self) -> Result<(String, Box<dyn RunStreamWriter>)> {
+        let run_id = {
+            let mut state = lock(&self.state);
+            let run_id = state.next_run;
+            state.next_run += 1;
+            run_id
+        };
+
+        let writer = MemoryRunWriter {
+            state: self.state.clone(),
+            run_id,
+            buffer: Vec::new(),
+        };
+        Ok((run_id.to_string(), Box::new(writer)))
+    }
+
+    fn get_latest_run(&self) -> Result<TestRun> {
+        let state = lock(&self.state);
+        let run_id = state.runs.keys().next_back().ok_or(Error::NoTestRuns)?;
+        state.parse_run(&run_id.to_string())
+    }
+
+    fn get_test_run_raw(&self, run_id: &str) -> Result<Box<dyn std::io::Read>> {
+        let raw = lock(&self.state).stored_run(run_id)?.raw.clone();
+        Ok(Box::new(std::io::Cursor::new(raw)))
+    }
+
+    fn update_failing_tests(&mut self, run: &TestRun) -> Result<()> {
+        lock(&self.state).record_failing(&run.id, true)
+    }
+
+    fn replace_failing_tests(&mut self, run: &TestRun) -> Result<()> {
+        lock(&self.state).record_failing(&run.id, false)
+    }
+
+    fn add_failing_tests(&mut self, results: &[TestResult]) -> Result<()> {
+        let mut state = lock(&self.state);
+        for result in results {
+            state.failing.insert(result.test_id.clone(), result.clone());
+        }
+        Ok(())
+    }
+
+    fn get_failing_tests(&self) -> Result<Vec<TestId>> {
+        Ok(lock(&self.state).failing.keys().cloned().collect())
+    }
+
+    fn get_failing_tests_raw(&self) -> Result<Box<dyn std::io::Read>> {
+        let mut run = TestRun::new("failing".to_string());
+        for result in lock(&self.state).failing.values() {
+            run.add_result(result.clone());
+        }
+
+        let mut stream = Vec::new();
+        subunit_stream::write_stream(&run, &mut stream)?;
+        Ok(Box::new(std::io::Cursor::new(stream)))
+    }
+
+    fn get_test_times(&self) -> Result<HashMap<TestId, Duration>> {
+        Ok(lock(&self.state)
+            .times
+            .iter()
+            .filter_map(|(test_id, timing)| Some((test_id.clone(), timing.last()?)))
+            .collect())
+    }
+
+    fn get_test_times_for_ids(&self, test_ids: &[TestId]) -> Result<HashMap<TestId, Duration>> {
+        let state = lock(&self.state);
+        Ok(test_ids
+            .iter()
+            .filter_map(|test_id| {
+                state
+                    .times
+                    .get(test_id)
+                    .and_then(TestTiming::last)
+                    .map(|duration| (test_id.clone(), duration))
+            })
+            .collect())
+    }
+
+    fn get_test_timings_for_ids(&self, test_ids: &[TestId]) -> Result<HashMap<TestId, TestTiming>> {
+        let state = lock(&self.state);
+        Ok(test_ids
+            .iter()
+            .filter_map(|test_id| Some((test_id.clone(), state.times.get(test_id)?.clone())))
+            .collect())
+    }
+
+    fn update_test_times(&mut self, times: &HashMap<TestId, Duration>) -> Result<()> {
+        let mut state = lock(&self.state);
+        for (test_id, duration) in times {
+            state
+                .times
+                .entry(test_id.clone())
+                .or_default()
+                .record(*duration);
+        }
+        Ok(())
+    }
+
+    fn get_next_run_id(&self) -> Result<u64> {
+        Ok(lock(&self.state).next_run)
+    }
+
+    fn list_run_ids(&self) -> Result<Vec<String>> {
+        Ok(lock(&self.state)
+            .runs
+            .keys()
+            .map(|run_id| run_id.to_string())
+            .collect())
+    }
+
+    fn count(&self) -> Result<usize> {
+        Ok(lock(&self.state).runs.len())
+    }
+
+    fn delete_run(&mut self, run_id: &str) -> Result<()> {
+        lock(&self.state)
+            .runs
+            .remove(&parse_run_id(run_id)?)
+            .map(|_| ())
+            .ok_or_else(|| Error::TestRunNotFound(run_id.to_string()))
+    }
+
+    fn get_run_time(&self, run_id: &str) -> Result<DateTime<Utc>> {
+        let state = lock(&self.state);
+        let stored = state.stored_run(run_id)?;
+        Ok(stored.metadata.start_time.unwrap_or(stored.stored_at))
+    }
+
+    fn get_run_metadata(&self, run_id: &str) -> Result<RunMetadata> {
+        Ok(lock(&self.state).stored_run(run_id)?.metadata.clone())
+    }
+
+    fn set_run_metadata(&mut self, run_id: &str, metadata: &RunMetadata) -> Result<()> {
+        let mut state = lock(&self.state);
+        let stored = state
+            .runs
+            .get_mut(&parse_run_id(run_id)?)
+            .ok_or_else(|| Error::TestRunNotFound(run_id.to_string()))?;
+        stored.metadata = RunMetadata {
+            partial: metadata.partial.or(stored.metadata.partial),
+            ..metadata.clone()
+        };
+        Ok(())
+    }
+
+    fn rebuild_failing_and_times(&mut self, run_ids: &[String]) -> Result<()> {
+        let mut state = lock(&self.state);
+        state.failing.clear();
+        state.times.clear();
+
+        for run_id in run_ids {
+            // Runs whose mode was not recorded are replayed as partial
+            let partial = state.stored_run(run_id)?.metadata.partial != Some(false);
+            state.apply_to_failing(run_id, partial)?;
+
+            let run = state.parse_run(run_id)?;
+            for result in run.results.into_values() {
+                if let Some(duration) = result.duration {
+                    state
+                        .times
+                        .entry(result.test_id)
+                        .or_default()
+                        .record(duration);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check(&mut self, repair: bool) -> Result<Vec<CheckIssue>> {
+        let mut issues = Vec::new();
+        let mut intact_runs = Vec::new();
+
+        {
+            let mut state = lock(&self.state);
+            for (run_id, stored) in state.runs.iter_mut() {
+                let problems = subunit_stream::validate_stream(&stored.raw);
+                // Only a torn final packet can be cut off safely
+                let torn_tail = match problems.as_slice() {
+                    [problem] if problem.truncated => Some(problem.offset),
+                    _ => None,
+                };
+                let repaired = repair && torn_tail.is_some();
+
+                for problem in &problems {
+                    issues.push(CheckIssue {
+                        location: format!("run {}", run_id),
+                        message: format!("offset {}: {}", problem.offset, problem.message),
+                        repaired,
+                    });
+                }
+
+                if let (true, Some(offset)) = (repair, torn_tail) {
+                    stored.raw.truncate(offset as usize);
+                }
+                if problems.is_empty() || repaired {
+                    intact_runs.push(run_id.to_string());
+                }
+            }
+        }
+
+        if repair && !issues.is_empty() {
+            self.rebuild_failing_and_times(&intact_runs)?;
+        }
+
+        Ok(issues)
+    }
+
+    fn get_config(&self) -> Result<RepoConfig> {
+        Ok(lock(&self.state).config.clone())
+    }
+
+    fn set_config(&mut self, config: &RepoConfig) -> Result<()> {
+        lock(&self.state).config = config.clone();
+        Ok(())
+    }
+
+    fn get_bookmarks(&self) -> Result<BTreeMap<String, String>> {
+        Ok(lock(&self.state).bookmarks.clone())
+    }
+
+    fn set_bookmark(&mut self, name: &str, run_id: Option<&str>) -> Result<()> {
+        let mut state = lock(&self.state);
+        match run_id {
+            Some(run_id) => state.bookmarks.insert(name.to_string(), run_id.to_string()),
+            None => state.bookmarks.remove(name),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_initialise_and_open() {
+        let factory = MemoryRepositoryFactory::new();
+        let base = Path::new("/nonexistent");
+
+        assert!(matches!(
+            factory.open(base),
+            Err(Error::RepositoryNotFound(_))
+        ));
+        let mut repo = factory.initialise(base).unwrap();
+        assert!(matches!(
+            factory.initialise(base),
+            Err(Error::RepositoryExists(_))
+        ));
+        assert!(!base.exists());
+
+        repo.insert_test_run(TestRun::new("0".to_string())).unwrap();
+
+        // Handles opened through a clone of the factory share the repository
+        let other = factory.clone().open(base).unwrap();
+        assert_eq!(other.list_run_ids().unwrap(), vec!["0"]);
+        assert_eq!(other.get_next_run_id().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_runs_failing_and_times() {
+        let factory = MemoryRepositoryFactory::new();
+        let mut repo = factory.initialise(Path::new("repo")).unwrap();
+        assert!(matches!(repo.get_latest_run(), Err(Error::NoTestRuns)));
+
+        let mut run = TestRun::new("0".to_string());
+        run.add_result(
+            TestResult::failure("test1", "Failed").with_duration(Duration::from_secs(2)),
+        );
+        run.add_result(TestResult::failure("test2", "Failed"));
+        repo.insert_test_run(run).unwrap();
+
+        let mut run = TestRun::new("1".to_string());
+        run.add_result(TestResult::success("test1").with_duration(Duration::from_secs(1)));
+        repo.insert_test_run_partial(run, true).unwrap();
+
+        assert_eq!(repo.get_latest_run().unwrap().id, "1");
+        assert_eq!(repo.get_run_metadata("1").unwrap().partial, Some(true));
+        assert_eq!(
+            repo.get_failing_tests().unwrap(),
+            vec![TestId::new("test2")]
+        );
+        assert_eq!(
+            repo.get_test_times().unwrap(),
+            HashMap::from([(TestId::new("test1"), Duration::from_secs(1))])
+        );
+        assert_eq!(
+            repo.get_test_timings_for_ids(&[TestId::new("test1")])
+                .unwrap()[&TestId::new("test1")]
+                .samples(),
+            [Duration::from_secs(2), Duration::from_secs(1)]
+        );
+
+        let mut raw = Vec::new();
+        repo.get_failing_tests_raw()
+            .unwrap()
+            .read_to_end(&mut raw)
+            .unwrap();
+        let failing = subunit_stream::parse_stream(&raw[..], "failing".to_string()).unwrap();
+        assert_eq!(failing.count_failures(), 1);
+
+        repo.rebuild_failing_and_times(&["0".to_string()]).unwrap();
+        let mut failing = repo.get_failing_tests().unwrap();
+        failing.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        assert_eq!(failing, vec![TestId::new("test1"), TestId::new("test2")]);
+
+        repo.delete_run("0").unwrap();
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["1"]);
+        assert!(matches!(
+            repo.delete_run("0"),
+            Err(Error::TestRunNotFound(_))
+        ));
+        assert!(repo.check(false).unwrap().is_empty());
+    }
+}
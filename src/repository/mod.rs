@@ -3,15 +3,42 @@
 //! This module provides traits and implementations for storing and retrieving
 //! test results. The on-disk format is compatible with the Python version.
 
-use crate::error::Result;
-use std::collections::HashMap;
-use std::path::Path;
+use crate::error::{Error, Result};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::Duration;
 
+pub mod config;
 pub mod file;
+pub mod flaky;
+pub mod memory;
+pub mod run_diff;
+pub mod run_metadata;
+pub mod run_spec;
+pub mod sql;
+pub mod test_history;
 pub mod test_run;
+pub mod test_times;
 
+pub use config::RepoConfig;
+pub use run_metadata::RunMetadata;
+pub use run_spec::RunSpec;
+pub use test_history::{HistoryEntry, HistorySummary, TestSelector};
 pub use test_run::{TestId, TestResult, TestRun, TestStatus};
+pub use test_times::{TestTiming, TimeEstimator};
+
+/// Writer for a new run's raw stream, returned by
+/// [`Repository::begin_test_run_raw`]
+///
+/// The run is only stored once the writer is committed. A writer dropped
+/// without being committed, by an error or a panic part way through a run,
+/// discards what was written.
+pub trait RunStreamWriter: std::io::Write + Send {
+    /// Store the stream written so far as the run
+    fn commit(self: Box<Self>) -> Result<()>;
+}
 
 /// Abstract repository trait for test result storage
 ///
@@ -56,20 +83,18 @@ pub trait Repository {
 
     /// Begin inserting a raw test run stream, returning (run_id, writer)
     /// This preserves the original stream byte-for-byte including non-subunit output
-    /// The caller should write the raw subunit bytes to the returned writer
-    fn begin_test_run_raw(&mut self) -> Result<(String, Box<dyn std::io::Write + Send>)>;
+    /// The caller should write the raw subunit bytes to the returned writer,
+    /// and commit it once the run is complete
+    fn begin_test_run_raw(&mut self) -> Result<(String, Box<dyn RunStreamWriter>)>;
 
     /// Insert a test run (convenience method for tests - prefer begin_test_run_raw in production)
     ///
     /// This is a convenience wrapper around begin_test_run_raw() for test code.
     /// Production code should prefer the streaming API for better performance.
     fn insert_test_run(&mut self, run: TestRun) -> Result<String> {
-        use std::io::Write;
-
         let (run_id, mut writer) = self.begin_test_run_raw()?;
         crate::subunit_stream::write_stream(&run, &mut *writer)?;
-        writer.flush()?;
-        drop(writer);
+        writer.commit()?;
 
         // Update failing tests and times
         self.replace_failing_tests(&run)?;
@@ -96,12 +121,9 @@ pub trait Repository {
     ///
     /// In full (non-partial) mode, all previous failures are cleared.
     fn insert_test_run_partial(&mut self, run: TestRun, partial: bool) -> Result<String> {
-        use std::io::Write;
-
         let (run_id, mut writer) = self.begin_test_run_raw()?;
         crate::subunit_stream::write_stream(&run, &mut *writer)?;
-        writer.flush()?;
-        drop(writer);
+        writer.commit()?;
 
         // Update failing tests based on mode
         if partial {
@@ -130,6 +152,12 @@ pub trait Repository {
     /// Replace all failing tests (for full runs)
     fn replace_failing_tests(&mut self, run: &TestRun) -> Result<()>;
 
+    /// Add results to the failing tests, replacing any recorded for the same tests
+    ///
+    /// This keeps tests failing that their latest run doesn't show as failing,
+    /// such as tests that only passed when retried.
+    fn add_failing_tests(&mut self, results: &[TestResult]) -> Result<()>;
+
     /// Get the latest test run
     fn get_latest_run(&self) -> Result<TestRun>;
 
@@ -142,13 +170,16 @@ pub trait Repository {
     /// Get the raw subunit stream for failing tests as a reader
     fn get_failing_tests_raw(&self) -> Result<Box<dyn std::io::Read>>;
 
-    /// Get test execution times
+    /// Get the most recent execution time of every test
     fn get_test_times(&self) -> Result<HashMap<TestId, Duration>>;
 
-    /// Get test execution times for specific test IDs
+    /// Get the most recent execution times for specific test IDs
     fn get_test_times_for_ids(&self, test_ids: &[TestId]) -> Result<HashMap<TestId, Duration>>;
 
-    /// Update test execution times
+    /// Get the recent execution times for specific test IDs
+    fn get_test_timings_for_ids(&self, test_ids: &[TestId]) -> Result<HashMap<TestId, TestTiming>>;
+
+    /// Record test execution times, adding each to its test's recent times
     fn update_test_times(&mut self, times: &HashMap<TestId, Duration>) -> Result<()>;
 
     /// Get the next run ID that will be assigned
@@ -159,6 +190,84 @@ pub trait Repository {
 
     /// Get the number of test runs in the repository
     fn count(&self) -> Result<usize>;
+
+    /// Delete a stored test run
+    ///
+    /// The failing tests and the times database are left untouched, and the
+    /// run's ID is never handed out again.
+    fn delete_run(&mut self, run_id: &str) -> Result<()>;
+
+    /// Get the time at which a test run started
+    ///
+    /// Runs stored without a recorded start time are dated by when they were
+    /// stored.
+    fn get_run_time(&self, run_id: &str) -> Result<DateTime<Utc>>;
+
+    /// Get the results of the selected tests in every stored run
+    ///
+    /// Entries are ordered by run, oldest first, and by test ID within a run.
+    fn get_test_history(&self, tests: &TestSelector) -> Result<Vec<HistoryEntry>> {
+        let mut history = Vec::new();
+        for run_id in self.list_run_ids()? {
+            let run = self.get_test_run(&run_id)?;
+            let mut results: Vec<TestResult> = run
+                .results
+                .into_values()
+                .filter(|result| tests.matches(&result.test_id))
+                .collect();
+            results.sort_by(|a, b| a.test_id.as_str().cmp(b.test_id.as_str()));
+            history.extend(results.into_iter().map(|result| HistoryEntry {
+                run_id: run_id.clone(),
+                timestamp: run.timestamp,
+                result,
+            }));
+        }
+        Ok(history)
+    }
+
+    /// Get the metadata recorded for a test run
+    ///
+    /// Runs stored without metadata yield an empty [`RunMetadata`].
+    fn get_run_metadata(&self, run_id: &str) -> Result<RunMetadata>;
+
+    /// Record metadata for a test run, replacing what was recorded before
+    ///
+    /// If `metadata.partial` is unset, the mode recorded when the failing
+    /// tests were updated is kept.
+    fn set_run_metadata(&mut self, run_id: &str, metadata: &RunMetadata) -> Result<()>;
+
+    /// Recompute the failing tests and the times database from stored runs
+    ///
+    /// Both are cleared and the given runs are replayed in order: full runs
+    /// replace the failing tests and partial runs update them additively.
+    /// Runs whose mode was not recorded are replayed as partial.
+    fn rebuild_failing_and_times(&mut self, run_ids: &[String]) -> Result<()>;
+
+    /// Check the repository for damage, repairing what can be repaired if asked
+    fn check(&mut self, repair: bool) -> Result<Vec<CheckIssue>>;
+
+    /// Get the repository's own settings (repo.conf)
+    fn get_config(&self) -> Result<RepoConfig>;
+
+    /// Replace the repository's own settings (repo.conf)
+    fn set_config(&mut self, config: &RepoConfig) -> Result<()>;
+
+    /// Get the named bookmarks and the run each one points at
+    fn get_bookmarks(&self) -> Result<BTreeMap<String, String>>;
+
+    /// Point a bookmark at a run, or remove it if `run_id` is `None`
+    fn set_bookmark(&mut self, name: &str, run_id: Option<&str>) -> Result<()>;
+}
+
+/// A problem found by [`Repository::check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckIssue {
+    /// Where the problem was found, such as a run ID or file name
+    pub location: String,
+    /// Description of the problem
+    pub message: String,
+    /// Whether the problem was repaired
+    pub repaired: bool,
 }
 
 /// Factory trait for creating and opening repositories
@@ -170,6 +279,101 @@ pub trait RepositoryFactory {
     fn open(&self, base: &Path) -> Result<Box<dyn Repository>>;
 }
 
+/// The kinds of repository that can be created on disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RepositoryType {
+    /// Plain files, compatible with the Python implementation
+    #[default]
+    File,
+    /// Raw streams plus a SQLite index of every run's results
+    Sql,
+}
+
+impl RepositoryType {
+    /// Get the factory for this kind of repository
+    pub fn factory(self) -> Box<dyn RepositoryFactory> {
+        match self {
+            RepositoryType::File => Box::new(file::FileRepositoryFactory),
+            RepositoryType::Sql => Box::new(sql::SqlRepositoryFactory),
+        }
+    }
+
+    /// Work out which kind of repository is stored under `base`
+    ///
+    /// Anything that isn't recognisably a SQL repository is treated as a file
+    /// repository, whose factory reports missing or unknown formats.
+    pub fn detect(base: &Path) -> RepositoryType {
+        let format = std::fs::read_to_string(base.join(".testrepository").join("format"))
+            .unwrap_or_default();
+        if format.trim() == sql::SQL_FORMAT {
+            RepositoryType::Sql
+        } else {
+            RepositoryType::File
+        }
+    }
+}
+
+impl FromStr for RepositoryType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "file" => Ok(RepositoryType::File),
+            "sql" => Ok(RepositoryType::Sql),
+            _ => Err(Error::Config(format!(
+                "Unknown repository type '{}' (expected 'file' or 'sql')",
+                s
+            ))),
+        }
+    }
+}
+
+/// Open the repository under `base`, whichever kind it is
+pub fn open_repository(base: &Path) -> Result<Box<dyn Repository>> {
+    RepositoryType::detect(base).factory().open(base)
+}
+
+/// Environment variable that names the project root, bypassing discovery
+pub const TESTR_DIR_ENV: &str = "TESTR_DIR";
+
+/// Get the project root named by `TESTR_DIR`, if it is set
+///
+/// A relative path is taken relative to `start`.
+pub fn root_override(start: &Path) -> Option<PathBuf> {
+    std::env::var_os(TESTR_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| start.join(dir))
+}
+
+/// Find the nearest directory at or above `start` that contains a
+/// `.testrepository` directory or a `.testr.conf` file
+pub fn discover_root(start: &Path) -> Option<PathBuf> {
+    if is_root(start) {
+        return Some(start.to_path_buf());
+    }
+    std::path::absolute(start)
+        .ok()?
+        .ancestors()
+        .skip(1)
+        .find(|dir| is_root(dir))
+        .map(Path::to_path_buf)
+}
+
+fn is_root(dir: &Path) -> bool {
+    dir.join(".testrepository").is_dir() || dir.join(".testr.conf").is_file()
+}
+
+/// Work out the project root for a command started in `start`
+///
+/// `TESTR_DIR` wins if it is set; otherwise the root is discovered by walking
+/// up from `start`, as git does. When nothing is found `start` itself is
+/// returned, so that errors name the directory the user asked about.
+pub fn find_root(start: &Path) -> PathBuf {
+    root_override(start)
+        .or_else(|| discover_root(start))
+        .unwrap_or_else(|| start.to_path_buf())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -186,4 +390,40 @@ mod tests {
         assert_eq!(TestStatus::Success, TestStatus::Success);
         assert_ne!(TestStatus::Success, TestStatus::Failure);
     }
+
+    #[test]
+    fn test_open_repository_detects_type() {
+        let temp = tempfile::TempDir::new().unwrap();
+        assert!(open_repository(temp.path()).is_err());
+
+        sql::SqlRepositoryFactory.initialise(temp.path()).unwrap();
+        assert_eq!(RepositoryType::detect(temp.path()), RepositoryType::Sql);
+        let mut repo = open_repository(temp.path()).unwrap();
+        repo.insert_test_run(TestRun::new("0".to_string())).unwrap();
+        assert_eq!(repo.count().unwrap(), 1);
+
+        assert_eq!(
+            "sql".parse::<RepositoryType>().unwrap(),
+            RepositoryType::Sql
+        );
+        assert!("dbm".parse::<RepositoryType>().is_err());
+    }
+
+    #[test]
+    fn test_discover_root() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path().join("project");
+        let nested = root.join("src").join("module");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(discover_root(&nested), None);
+
+        std::fs::write(root.join(".testr.conf"), "[DEFAULT]\n").unwrap();
+        assert_eq!(discover_root(&root), Some(root.clone()));
+        assert_eq!(discover_root(&nested), Some(root.clone()));
+
+        // The nearest repository wins over a configuration further up
+        let sub = root.join("src");
+        file::FileRepositoryFactory.initialise(&sub).unwrap();
+        assert_eq!(discover_root(&nested), Some(sub));
+    }
 }
@@ -0,0 +1,225 @@
+//! Differences between two test runs
+
+use crate::repository::{TestId, TestRun, TestStatus};
+use std::time::Duration;
+
+/// Tests whose duration grew by less than this are never reported as slower,
+/// however large the relative change
+pub const MIN_DURATION_CHANGE: Duration = Duration::from_millis(100);
+
+/// How a test's outcome changed between two runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Fails in the new run but didn't fail (or didn't run) in the old one
+    NewFailure,
+    /// Failed in the old run and passes in the new one
+    Fixed,
+    /// Only ran in the new run, and didn't fail
+    Added,
+    /// Only ran in the old run
+    Removed,
+    /// Ran in both with different statuses, both failing or both passing
+    StatusChange,
+}
+
+/// A test whose outcome changed between two runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestChange {
+    /// The test
+    pub test_id: TestId,
+    /// How its outcome changed
+    pub kind: ChangeKind,
+    /// Its status in the old run, if it ran
+    pub old_status: Option<TestStatus>,
+    /// Its status in the new run, if it ran
+    pub new_status: Option<TestStatus>,
+}
+
+/// A test that took noticeably longer in the new run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurationChange {
+    /// The test
+    pub test_id: TestId,
+    /// How long it took in the old run
+    pub old_duration: Duration,
+    /// How long it took in the new run
+    pub new_duration: Duration,
+}
+
+impl DurationChange {
+    /// The change in duration as a percentage of the old duration
+    pub fn percent_change(&self) -> f64 {
+        let old = self.old_duration.as_secs_f64();
+        if old > 0.0 {
+            (self.new_duration.as_secs_f64() - old) / old * 100.0
+        } else {
+            f64::INFINITY
+        }
+    }
+}
+
+/// Everything that differs between two runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunDiff {
+    /// ID of the old run
+    pub old_run: String,
+    /// ID of the new run
+    pub new_run: String,
+    /// Tests whose outcome changed, sorted by test ID
+    pub changes: Vec<TestChange>,
+    /// Tests that got slower by more than the threshold, slowest change first
+    pub slower: Vec<DurationChange>,
+}
+
+impl RunDiff {
+    /// Compare two runs
+    ///
+    /// A test counts as slower when its duration grew by more than
+    /// `threshold_percent` percent and by at least [`MIN_DURATION_CHANGE`].
+    pub fn compare(old: &TestRun, new: &TestRun, threshold_percent: f64) -> Self {
+        let mut changes = Vec::new();
+        let mut slower = Vec::new();
+
+        for (test_id, new_result) in &new.results {
+            let new_status = new_result.status;
+            let Some(old_result) = old.results.get(test_id) else {
+                let kind = if new_status.is_failure() {
+                    ChangeKind::NewFailure
+                } else {
+                    ChangeKind::Added
+                };
+                changes.push(TestChange {
+                    test_id: test_id.clone(),
+                    kind,
+                    old_status: None,
+                    new_status: Some(new_status),
+                });
+                continue;
+            };
+
+            let old_status = old_result.status;
+            if old_status != new_status {
+                let kind = match (old_status.is_failure(), new_status.is_failure()) {
+                    (false, true) => ChangeKind::NewFailure,
+                    (true, false) => ChangeKind::Fixed,
+                    _ => ChangeKind::StatusChange,
+                };
+                changes.push(TestChange {
+                    test_id: test_id.clone(),
+                    kind,
+                    old_status: Some(old_status),
+                    new_status: Some(new_status),
+                });
+            }
+
+            if let (Some(old_duration), Some(new_duration)) =
+                (old_result.duration, new_result.duration)
+            {
+                let change = DurationChange {
+                    test_id: test_id.clone(),
+                    old_duration,
+                    new_duration,
+                };
+                if new_duration >= old_duration + MIN_DURATION_CHANGE
+                    && change.percent_change() > threshold_percent
+                {
+                    slower.push(change);
+                }
+            }
+        }
+
+        for (test_id, old_result) in &old.results {
+            if !new.results.contains_key(test_id) {
+                changes.push(TestChange {
+                    test_id: test_id.clone(),
+                    kind: ChangeKind::Removed,
+                    old_status: Some(old_result.status),
+                    new_status: None,
+                });
+            }
+        }
+
+        changes.sort_by(|a, b| a.test_id.as_str().cmp(b.test_id.as_str()));
+        slower.sort_by(|a, b| {
+            (b.new_duration - b.old_duration)
+                .cmp(&(a.new_duration - a.old_duration))
+                .then_with(|| a.test_id.as_str().cmp(b.test_id.as_str()))
+        });
+
+        RunDiff {
+            old_run: old.id.clone(),
+            new_run: new.id.clone(),
+            changes,
+            slower,
+        }
+    }
+
+    /// The changes of one kind, sorted by test ID
+    pub fn changes_of_kind(&self, kind: ChangeKind) -> impl Iterator<Item = &TestChange> {
+        self.changes
+            .iter()
+            .filter(move |change| change.kind == kind)
+    }
+
+    /// Whether any test fails in the new run that didn't fail in the old one
+    pub fn has_new_failures(&self) -> bool {
+        self.changes_of_kind(ChangeKind::NewFailure)
+            .next()
+            .is_some()
+    }
+
+    /// Whether the runs differ at all
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.slower.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::TestResult;
+
+    #[test]
+    fn test_compare() {
+        let mut old = TestRun::new("1".to_string());
+        old.add_result(TestResult::success("broken"));
+        old.add_result(TestResult::failure("fixed", "boom"));
+        old.add_result(TestResult::success("gone"));
+        old.add_result(TestResult::skip("unskipped"));
+        old.add_result(TestResult::failure("worse", "boom"));
+        old.add_result(TestResult::success("slow").with_duration(Duration::from_millis(200)));
+        old.add_result(TestResult::success("noisy").with_duration(Duration::from_millis(10)));
+
+        let mut new = TestRun::new("2".to_string());
+        new.add_result(TestResult::failure("broken", "boom"));
+        new.add_result(TestResult::success("fixed"));
+        new.add_result(TestResult::success("unskipped"));
+        new.add_result(TestResult::error("worse", "boom"));
+        new.add_result(TestResult::success("slow").with_duration(Duration::from_millis(500)));
+        new.add_result(TestResult::success("noisy").with_duration(Duration::from_millis(50)));
+        new.add_result(TestResult::success("new"));
+        new.add_result(TestResult::failure("new_broken", "boom"));
+
+        let diff = RunDiff::compare(&old, &new, 50.0);
+        let ids = |kind| {
+            diff.changes_of_kind(kind)
+                .map(|change| change.test_id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(ChangeKind::NewFailure), vec!["broken", "new_broken"]);
+        assert_eq!(ids(ChangeKind::Fixed), vec!["fixed"]);
+        assert_eq!(ids(ChangeKind::Added), vec!["new"]);
+        assert_eq!(ids(ChangeKind::Removed), vec!["gone"]);
+        assert_eq!(ids(ChangeKind::StatusChange), vec!["unskipped", "worse"]);
+        assert!(diff.has_new_failures());
+
+        // noisy grew by 400% but only by 40ms
+        assert_eq!(diff.slower.len(), 1);
+        assert_eq!(diff.slower[0].test_id.as_str(), "slow");
+        assert!((diff.slower[0].percent_change() - 150.0).abs() < 1e-9);
+
+        let diff = RunDiff::compare(&new, &new, 50.0);
+        assert!(diff.is_empty());
+        assert!(!diff.has_new_failures());
+    }
+}
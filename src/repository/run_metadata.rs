@@ -0,0 +1,232 @@
+//! Per-run metadata (.testrepository/N.meta)
+//!
+//! Describes how and where a run was made: when it started and finished, the
+//! test command, how many workers ran it and how they exited, whether it was
+//! partial or interrupted, and the state of the checkout. The file repository stores it in
+//! INI files with a `[DEFAULT]` section next to each run, which the Python
+//! implementation ignores.
+
+use crate::error::{Error, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Metadata recorded for a test run
+///
+/// Every field is optional: runs loaded from a stream only know whether they
+/// were partial, and runs stored by older versions know nothing at all.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunMetadata {
+    /// Whether the run only updated the failing tests rather than replacing them
+    pub partial: Option<bool>,
+
+    /// When the run started
+    pub start_time: Option<DateTime<Utc>>,
+
+    /// When the run finished
+    pub end_time: Option<DateTime<Utc>>,
+
+    /// The test command, with variables expanded; for runs split across
+    /// several processes, the command of the first
+    pub command: Option<String>,
+
+    /// Number of workers the tests were split across
+    pub concurrency: Option<usize>,
+
+    /// Exit code of each test process, in the order they were started;
+    /// processes killed by a signal are recorded as the negated signal number
+    pub exit_codes: Vec<i32>,
+
+    /// Whether testr was interrupted by a signal before the run finished
+    pub interrupted: Option<bool>,
+
+    /// Commit checked out when the run started
+    pub git_commit: Option<String>,
+
+    /// Whether the checkout had uncommitted changes
+    pub git_dirty: Option<bool>,
+
+    /// Host the run was made on
+    pub hostname: Option<String>,
+}
+
+impl RunMetadata {
+    /// Parse metadata from the contents of a .meta file
+    ///
+    /// Keys this version doesn't know about are ignored.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let ini: HashMap<String, HashMap<String, String>> = serde_ini::from_str(contents)
+            .map_err(|e| Error::Parse(format!("Invalid run metadata: {}", e)))?;
+
+        let mut metadata = RunMetadata::default();
+        let Some(default) = ini.get("DEFAULT") else {
+            return Ok(metadata);
+        };
+
+        for (key, value) in default {
+            let value = value.trim();
+            match key.as_str() {
+                "partial" => metadata.partial = Some(parse_value(key, value)?),
+                "start_time" => metadata.start_time = Some(parse_time(key, value)?),
+                "end_time" => metadata.end_time = Some(parse_time(key, value)?),
+                "command" => metadata.command = Some(unescape(value)),
+                "concurrency" => metadata.concurrency = Some(parse_value(key, value)?),
+                "exit_codes" => {
+                    metadata.exit_codes = value
+                        .split(',')
+                        .filter(|code| !code.trim().is_empty())
+                        .map(|code| parse_value(key, code.trim()))
+                        .collect::<Result<_>>()?
+                }
+                "interrupted" => metadata.interrupted = Some(parse_value(key, value)?),
+                "git_commit" => metadata.git_commit = Some(value.to_string()),
+                "git_dirty" => metadata.git_dirty = Some(parse_value(key, value)?),
+                "hostname" => metadata.hostname = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    /// Serialize the metadata in .meta format
+    pub fn to_ini_string(&self) -> String {
+        let mut contents = String::from("[DEFAULT]\n");
+        let mut add = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                contents.push_str(&format!("{}={}\n", key, value));
+            }
+        };
+
+        add("partial", self.partial.map(|b| b.to_string()));
+        add("start_time", self.start_time.map(|t| t.to_rfc3339()));
+        add("end_time", self.end_time.map(|t| t.to_rfc3339()));
+        add("command", self.command.as_deref().map(escape));
+        add("concurrency", self.concurrency.map(|n| n.to_string()));
+        add(
+            "exit_codes",
+            (!self.exit_codes.is_empty()).then(|| {
+                self.exit_codes
+                    .iter()
+                    .map(|code| code.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }),
+        );
+        add("interrupted", self.interrupted.map(|b| b.to_string()));
+        add("git_commit", self.git_commit.clone());
+        add("git_dirty", self.git_dirty.map(|b| b.to_string()));
+        add("hostname", self.hostname.clone());
+        contents
+    }
+
+    /// How long the run took, if both its start and end were recorded
+    pub fn duration(&self) -> Option<Duration> {
+        (self.end_time? - self.start_time?).to_std().ok()
+    }
+
+    /// Record the git revision of the checkout in `dir` and the hostname
+    ///
+    /// Anything that can't be determined, such as the revision of a directory
+    /// that isn't a git checkout, is left unset.
+    pub fn record_environment(&mut self, dir: &Path) {
+        self.git_commit = command_output(dir, "git", &["rev-parse", "HEAD"]);
+        if self.git_commit.is_some() {
+            self.git_dirty = command_output(dir, "git", &["status", "--porcelain"])
+                .map(|status| !status.is_empty());
+        }
+        self.hostname = command_output(dir, "hostname", &[]);
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| Error::Parse(format!("Invalid run metadata {}: '{}'", key, value)))
+}
+
+fn parse_time(key: &str, value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| Error::Parse(format!("Invalid run metadata {}: '{}'", key, value)))
+}
+
+/// Keep multi-line commands on one INI line
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Run a command and return its trimmed output, or `None` if it fails
+fn command_output(dir: &Path, program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let metadata = RunMetadata {
+            partial: Some(false),
+            start_time: DateTime::from_timestamp(1000000000, 0),
+            end_time: DateTime::from_timestamp(1000000042, 0),
+            command: Some("python -m subunit.run \\\n  discover --list=a=b".to_string()),
+            concurrency: Some(2),
+            exit_codes: vec![0, 1, -9],
+            interrupted: Some(true),
+            git_commit: Some("0123456789abcdef".to_string()),
+            git_dirty: Some(true),
+            hostname: Some("build-1".to_string()),
+        };
+
+        let contents = metadata.to_ini_string();
+        assert_eq!(RunMetadata::parse(&contents).unwrap(), metadata);
+        assert_eq!(metadata.duration(), Some(Duration::from_secs(42)));
+    }
+
+    #[test]
+    fn test_parse_partial_only() {
+        // Runs recorded before the other fields existed
+        let metadata = RunMetadata::parse("[DEFAULT]\npartial=true\nfuture=1\n").unwrap();
+        assert_eq!(
+            metadata,
+            RunMetadata {
+                partial: Some(true),
+                ..Default::default()
+            }
+        );
+        assert_eq!(metadata.duration(), None);
+
+        assert!(RunMetadata::parse("[DEFAULT]\nconcurrency=many\n").is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! Run references accepted on the command line
+//!
+//! A run spec names one run or a range of runs:
+//!
+//! - `17`: run 17
+//! - `-1`, `-2`: the latest run, the one before it, and so on
+//! - `last`, `last~3`: the latest run, and the third run before it
+//! - `good`, `good~1`: a bookmark, and the run before it
+//! - `10..15`, `-5..`, `..good`: every run between the two ends, inclusive;
+//!   a missing end means the first or the latest run
+//!
+//! Offsets count stored runs rather than run IDs, so they skip over runs that
+//! have been pruned. Bookmarks are stored in .testrepository/bookmarks, an
+//! INI file with a `[DEFAULT]` section mapping names to run IDs.
+
+use crate::error::{Error, Result};
+use crate::repository::Repository;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The run a reference counts back from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunBase {
+    /// A run ID
+    Id(u64),
+    /// The latest run
+    Last,
+    /// A named bookmark
+    Bookmark(String),
+}
+
+/// A reference to a single run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunRef {
+    /// The run to start from
+    pub base: RunBase,
+    /// How many stored runs to count back from the base
+    pub back: usize,
+}
+
+/// A reference to one run or a range of runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunSpec {
+    /// A single run
+    Single(RunRef),
+    /// All runs between two ends, inclusive; a missing end is open
+    Range(Option<RunRef>, Option<RunRef>),
+}
+
+impl FromStr for RunRef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::Config(format!("Invalid run reference: '{}'", s));
+
+        if let Some(offset) = s.strip_prefix('-') {
+            let n: usize = offset.parse().map_err(|_| invalid())?;
+            if n == 0 {
+                return Err(invalid());
+            }
+            return Ok(RunRef {
+                base: RunBase::Last,
+                back: n - 1,
+            });
+        }
+
+        let (base, back) = match s.split_once('~') {
+            Some((base, "")) => (base, 1),
+            Some((base, back)) => (base, back.parse().map_err(|_| invalid())?),
+            None => (s, 0),
+        };
+
+        let base = if base == "last" {
+            RunBase::Last
+        } else if !base.is_empty() && base.bytes().all(|b| b.is_ascii_digit()) {
+            RunBase::Id(base.parse().map_err(|_| invalid())?)
+        } else if validate_bookmark_name(base).is_ok() {
+            RunBase::Bookmark(base.to_string())
+        } else {
+            return Err(invalid());
+        };
+
+        Ok(RunRef { base, back })
+    }
+}
+
+impl FromStr for RunSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s.split_once("..") {
+            Some((from, to)) => {
+                let parse_end = |end: &str| {
+                    if end.is_empty() {
+                        Ok(None)
+                    } else {
+                        end.parse().map(Some)
+                    }
+                };
+                Ok(RunSpec::Range(parse_end(from)?, parse_end(to)?))
+            }
+            None => Ok(RunSpec::Single(s.parse()?)),
+        }
+    }
+}
+
+impl fmt::Display for RunRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.base {
+            RunBase::Id(id) => write!(f, "{}", id)?,
+            RunBase::Last => write!(f, "last")?,
+            RunBase::Bookmark(name) => write!(f, "{}", name)?,
+        }
+        if self.back > 0 {
+            write!(f, "~{}", self.back)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RunSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunSpec::Single(run) => write!(f, "{}", run),
+            RunSpec::Range(from, to) => {
+                if let Some(from) = from {
+                    write!(f, "{}", from)?;
+                }
+                write!(f, "..")?;
+                if let Some(to) = to {
+                    write!(f, "{}", to)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The stored runs, oldest first, with bookmarks loaded on first use
+struct Runs<'a> {
+    repo: &'a dyn Repository,
+    ids: Vec<u64>,
+    bookmarks: Option<BTreeMap<String, String>>,
+}
+
+impl<'a> Runs<'a> {
+    fn load(repo: &'a dyn Repository) -> Result<Self> {
+        let mut ids: Vec<u64> = repo
+            .list_run_ids()?
+            .iter()
+            .filter_map(|id| id.parse().ok())
+            .collect();
+        ids.sort_unstable();
+        Ok(Runs {
+            repo,
+            ids,
+            bookmarks: None,
+        })
+    }
+
+    /// Resolve a reference to the ID of a stored run
+    fn resolve(&mut self, run: &RunRef) -> Result<u64> {
+        let not_found = || Error::TestRunNotFound(run.to_string());
+
+        let base = match &run.base {
+            RunBase::Id(id) => *id,
+            RunBase::Last => *self.ids.last().ok_or(Error::NoTestRuns)?,
+            RunBase::Bookmark(name) => {
+                if self.bookmarks.is_none() {
+                    self.bookmarks = Some(self.repo.get_bookmarks()?);
+                }
+                let target = self.bookmarks.as_ref().and_then(|b| b.get(name));
+                let target =
+                    target.ok_or_else(|| Error::Config(format!("No bookmark named '{}'", name)))?;
+                target
+                    .parse()
+                    .map_err(|_| Error::TestRunNotFound(target.clone()))?
+            }
+        };
+
+        let position = self.ids.binary_search(&base).map_err(|_| {
+            if run.back == 0 {
+                Error::TestRunNotFound(base.to_string())
+            } else {
+                not_found()
+            }
+        })?;
+        let position = position.checked_sub(run.back).ok_or_else(not_found)?;
+        Ok(self.ids[position])
+    }
+
+    /// Resolve one end of a range; plain run IDs needn't exist
+    fn bound(&mut self, run: &RunRef) -> Result<u64> {
+        match run.base {
+            RunBase::Id(id) if run.back == 0 => Ok(id),
+            _ => self.resolve(run),
+        }
+    }
+}
+
+impl RunSpec {
+    /// Resolve the spec to the IDs of the runs it selects, oldest first
+    ///
+    /// A range may select no runs at all; a single reference must name a
+    /// stored run.
+    pub fn resolve(&self, repo: &dyn Repository) -> Result<Vec<String>> {
+        let mut runs = Runs::load(repo)?;
+        if runs.ids.is_empty() {
+            return Err(Error::NoTestRuns);
+        }
+
+        match self {
+            RunSpec::Single(run) => Ok(vec![runs.resolve(run)?.to_string()]),
+            RunSpec::Range(from, to) => {
+                let lo = from.as_ref().map(|r| runs.bound(r)).transpose()?;
+                let hi = to.as_ref().map(|r| runs.bound(r)).transpose()?;
+                let lo = lo.unwrap_or(u64::MIN);
+                let hi = hi.unwrap_or(u64::MAX);
+                if lo > hi {
+                    return Err(Error::Config(format!(
+                        "Invalid run range '{}': run {} comes after run {}",
+                        self, lo, hi
+                    )));
+                }
+                Ok(runs
+                    .ids
+                    .iter()
+                    .filter(|id| (lo..=hi).contains(*id))
+                    .map(u64::to_string)
+                    .collect())
+            }
+        }
+    }
+}
+
+/// Resolve a run spec to the runs it selects, oldest first
+pub fn resolve_runs(repo: &dyn Repository, spec: &str) -> Result<Vec<String>> {
+    spec.parse::<RunSpec>()?.resolve(repo)
+}
+
+/// Resolve a run spec that must name exactly one run
+pub fn resolve_run(repo: &dyn Repository, spec: &str) -> Result<String> {
+    match spec.parse::<RunSpec>()? {
+        RunSpec::Single(run) => Ok(RunSpec::Single(run).resolve(repo)?.remove(0)),
+        RunSpec::Range(..) => Err(Error::Config(format!(
+            "'{}' is a range of runs, but a single run is needed",
+            spec
+        ))),
+    }
+}
+
+/// Check that a name can be used for a bookmark
+///
+/// Names start with a letter and may contain letters, digits, `_`, `-` and
+/// `.`, so they can't be mistaken for run IDs, offsets or ranges.
+pub fn validate_bookmark_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !name.contains("..")
+        && name != "last";
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Config(format!("Invalid bookmark name: '{}'", name)))
+    }
+}
+
+/// Load bookmarks from a bookmarks file, returning none if it is missing
+pub(crate) fn load_bookmarks(path: &Path) -> Result<BTreeMap<String, String>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let contents = fs::read_to_string(path)?;
+    let mut ini: HashMap<String, HashMap<String, String>> = serde_ini::from_str(&contents)
+        .map_err(|e| Error::InvalidFormat(format!("bookmarks: {}", e)))?;
+
+    Ok(ini
+        .remove("DEFAULT")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, run_id)| (name, run_id.trim().to_string()))
+        .collect())
+}
+
+/// Point a bookmark in a bookmarks file at a run, or remove it
+pub(crate) fn store_bookmark(path: &Path, name: &str, run_id: Option<&str>) -> Result<()> {
+    let mut bookmarks = load_bookmarks(path)?;
+    match run_id {
+        Some(run_id) => bookmarks.insert(name.to_string(), run_id.to_string()),
+        None => bookmarks.remove(name),
+    };
+
+    let mut contents = String::from("[DEFAULT]\n");
+    for (name, run_id) in &bookmarks {
+        contents.push_str(&format!("{}={}\n", name, run_id));
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryRepositoryFactory;
+    use crate::repository::{RepositoryFactory, TestRun};
+
+    fn repo_with_runs(count: usize) -> Box<dyn Repository> {
+        let factory = MemoryRepositoryFactory::new();
+        let mut repo = factory.initialise(Path::new("/repo")).unwrap();
+        for _ in 0..count {
+            let id = repo.get_next_run_id().unwrap().to_string();
+            repo.insert_test_run(TestRun::new(id)).unwrap();
+        }
+        repo
+    }
+
+    #[test]
+    fn test_parse() {
+        let run = |base, back| RunRef { base, back };
+        assert_eq!(
+            "17".parse::<RunSpec>().unwrap(),
+            RunSpec::Single(run(RunBase::Id(17), 0))
+        );
+        assert_eq!(
+            "-1".parse::<RunSpec>().unwrap(),
+            RunSpec::Single(run(RunBase::Last, 0))
+        );
+        assert_eq!(
+            "last~3".parse::<RunSpec>().unwrap(),
+            RunSpec::Single(run(RunBase::Last, 3))
+        );
+        assert_eq!(
+            "good~".parse::<RunSpec>().unwrap(),
+            RunSpec::Single(run(RunBase::Bookmark("good".to_string()), 1))
+        );
+        assert_eq!(
+            "10..-2".parse::<RunSpec>().unwrap(),
+            RunSpec::Range(Some(run(RunBase::Id(10), 0)), Some(run(RunBase::Last, 1)))
+        );
+        assert_eq!("..".parse::<RunSpec>().unwrap(), RunSpec::Range(None, None));
+
+        for invalid in ["", "-0", "-x", "last~x", "1.5", "1..2..3", "~2"] {
+            assert!(invalid.parse::<RunSpec>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut repo = repo_with_runs(6);
+        repo.delete_run("2").unwrap();
+        repo.set_bookmark("good", Some("3")).unwrap();
+
+        let resolve = |spec: &str| resolve_runs(repo.as_ref(), spec).unwrap();
+        assert_eq!(resolve("4"), vec!["4"]);
+        assert_eq!(resolve("-1"), vec!["5"]);
+        assert_eq!(resolve("last~3"), vec!["1"]);
+        assert_eq!(resolve("good~1"), vec!["1"]);
+        assert_eq!(resolve("1..4"), vec!["1", "3", "4"]);
+        assert_eq!(resolve("good.."), vec!["3", "4", "5"]);
+        assert_eq!(resolve("..-4"), vec!["0", "1"]);
+        assert_eq!(resolve("7..9"), Vec::<String>::new());
+
+        assert!(matches!(
+            resolve_runs(repo.as_ref(), "2"),
+            Err(Error::TestRunNotFound(_))
+        ));
+        assert!(resolve_runs(repo.as_ref(), "-6").is_err());
+        assert!(resolve_runs(repo.as_ref(), "bad").is_err());
+        assert!(resolve_runs(repo.as_ref(), "4..1").is_err());
+        assert_eq!(resolve_run(repo.as_ref(), "good").unwrap(), "3");
+        assert!(resolve_run(repo.as_ref(), "3..4").is_err());
+
+        let empty = repo_with_runs(0);
+        assert!(matches!(
+            resolve_runs(empty.as_ref(), "-1"),
+            Err(Error::NoTestRuns)
+        ));
+    }
+
+    #[test]
+    fn test_bookmark_names() {
+        assert!(validate_bookmark_name("good").is_ok());
+        assert!(validate_bookmark_name("release-1.2_rc").is_ok());
+        for invalid in ["", "last", "17", "-2", "a..b", "a~1", "1a", "a b"] {
+            assert!(validate_bookmark_name(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,1409 @@
+//! SQLite-backed repository implementation
+//!
+//! Raw streams are stored as files named after their run ID, as in a file
+//! repository, and every run is also indexed into results.db as it is stored
+//! so that queries are answered without re-parsing streams:
+//!
+//! - format: "sql", which the Python implementation refuses to open
+//! - results.db: runs and their metadata, per-test results, failing tests, test
+//!   times and the run counter
+//! - 0, 1, 2, ...: individual test run files (subunit format)
+//! - repo.conf: per-repository settings
+
+use super::file::{
+    check_run_numbering, check_stale_run_files, check_stream, run_files, runs_in_progress,
+    RunWriter, REPO_DIR,
+};
+use crate::error::{Error, Result};
+use crate::repository::{
+    run_spec, CheckIssue, HistoryEntry, RepoConfig, Repository, RepositoryFactory, RunMetadata,
+    RunStreamWriter, TestId, TestResult, TestRun, TestSelector, TestStatus, TestTiming,
+};
+use crate::subunit_stream;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Contents of the format file of a SQL repository
+pub const SQL_FORMAT: &str = "sql";
+
+const DATABASE: &str = "results.db";
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE counter (next_run INTEGER NOT NULL);
+    INSERT INTO counter (next_run) VALUES (0);
+
+    CREATE TABLE runs (
+        id INTEGER PRIMARY KEY,
+        stored_at TEXT NOT NULL,
+        partial INTEGER,
+        indexed INTEGER NOT NULL DEFAULT 0,
+        index_error TEXT,
+        tags TEXT NOT NULL DEFAULT '',
+        start_time TEXT,
+        end_time TEXT,
+        command TEXT,
+        concurrency INTEGER,
+        exit_codes TEXT,
+        git_commit TEXT,
+        git_dirty INTEGER,
+        hostname TEXT,
+        interrupted INTEGER
+    );
+
+    CREATE TABLE results (
+        run_id INTEGER NOT NULL,
+        test_id TEXT NOT NULL,
+        status TEXT NOT NULL,
+        duration REAL,
+        message TEXT,
+        details TEXT,
+        tags TEXT NOT NULL,
+        raw BLOB,
+        PRIMARY KEY (run_id, test_id)
+    );
+    CREATE INDEX results_by_test ON results (test_id, run_id);
+
+    CREATE TABLE failing (
+        test_id TEXT PRIMARY KEY,
+        status TEXT NOT NULL,
+        duration REAL,
+        message TEXT,
+        details TEXT,
+        tags TEXT NOT NULL,
+        raw BLOB
+    );
+
+    CREATE TABLE times (
+        test_id TEXT PRIMARY KEY,
+        duration REAL NOT NULL,
+        history TEXT NOT NULL
+    );
+
+    PRAGMA user_version = 1;
+";
+
+/// Columns shared by the results and failing tables, in the order read by `query_results`
+///
+/// Both tables also have a `raw` column holding a failing test's events from
+/// its run's stream, attachments included.
+const RESULT_COLUMNS: &str = "test_id, status, duration, message, details, tags";
+
+/// Stored statuses for which [`TestStatus::is_failure`] is true
+const FAILURE_STATUSES: &str = "('failure', 'error', 'uxsuccess')";
+
+/// Stored statuses for which [`TestStatus::is_success`] is true
+const SUCCESS_STATUSES: &str = "('success', 'skip', 'xfail')";
+
+/// Factory for creating SQLite-backed repositories.
+///
+/// These use the same `.testrepository` directory and raw stream files as
+/// file repositories, plus an index of every run's results in results.db.
+pub struct SqlRepositoryFactory;
+
+impl SqlRepositoryFactory {
+    fn connect(repo_path: &Path) -> Result<Connection> {
+        let conn = Connection::open(repo_path.join(DATABASE))?;
+        // Several testr processes may share a repository, so wait for each
+        // other's transactions rather than failing
+        conn.busy_timeout(Duration::from_secs(60))?;
+        Ok(conn)
+    }
+}
+
+impl RepositoryFactory for SqlRepositoryFactory {
+    fn initialise(&self, base: &Path) -> Result<Box<dyn Repository>> {
+        let repo_path = base.join(REPO_DIR);
+
+        if repo_path.exists() {
+            return Err(Error::RepositoryExists(repo_path));
+        }
+
+        fs::create_dir_all(&repo_path)?;
+
+        let conn = Self::connect(&repo_path)?;
+        conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        conn.execute_batch(SCHEMA)?;
+
+        // Written last, so that an interrupted initialise is never opened
+        fs::write(repo_path.join("format"), format!("{}\n", SQL_FORMAT))?;
+
+        Ok(Box::new(SqlRepository {
+            path: repo_path,
+            conn,
+        }))
+    }
+
+    fn open(&self, base: &Path) -> Result<Box<dyn Repository>> {
+        let repo_path = base.join(REPO_DIR);
+
+        if !repo_path.exists() {
+            return Err(Error::RepositoryNotFound(repo_path));
+        }
+
+        let format_path = repo_path.join("format");
+        if !format_path.exists() {
+            return Err(Error::InvalidFormat("Missing format file".to_string()));
+        }
+
+        let format = fs::read_to_string(&format_path)?.trim().to_string();
+        if format != SQL_FORMAT {
+            return Err(Error::InvalidFormat(format!(
+                "Unsupported format version: {}",
+                format
+            )));
+        }
+
+        let conn = Self::connect(&repo_path)?;
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            return Err(Error::InvalidFormat(format!(
+                "Unsupported {} schema version: {}",
+                DATABASE, version
+            )));
+        }
+
+        Ok(Box::new(SqlRepository {
+            path: repo_path,
+            conn,
+        }))
+    }
+}
+
+/// SQLite-backed repository implementation.
+///
+/// Stores raw streams on disk and indexes each run's results, the failing
+/// tests and the test times in SQLite.
+pub struct SqlRepository {
+    path: PathBuf,
+    conn: Connection,
+}
+
+/// Parse a run ID, treating anything that isn't a number as an unknown run
+fn parse_run_id(run_id: &str) -> Result<i64> {
+    run_id
+        .parse()
+        .map_err(|_| Error::TestRunNotFound(run_id.to_string()))
+}
+
+fn parse_status(status: &str) -> Result<TestStatus> {
+    Ok(match status {
+        "success" => TestStatus::Success,
+        "failure" => TestStatus::Failure,
+        "error" => TestStatus::Error,
+        "skip" => TestStatus::Skip,
+        "xfail" => TestStatus::ExpectedFailure,
+        "uxsuccess" => TestStatus::UnexpectedSuccess,
+        other => {
+            return Err(Error::InvalidFormat(format!(
+                "Unknown test status in {}: {}",
+                DATABASE, other
+            )))
+        }
+    })
+}
+
+fn parse_time(time: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| Error::InvalidFormat(format!("Invalid time in {}: {}", DATABASE, e)))
+}
+
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split_whitespace().map(String::from).collect()
+}
+
+/// The [`RESULT_COLUMNS`] of a row, as read by [`read_result`]
+type ResultRow = (
+    String,
+    String,
+    Option<f64>,
+    Option<String>,
+    Option<String>,
+    String,
+);
+
+/// Read the [`RESULT_COLUMNS`] of a row, starting at column `first`
+fn read_result(row: &rusqlite::Row, first: usize) -> rusqlite::Result<ResultRow> {
+    Ok((
+        row.get(first)?,
+        row.get(first + 1)?,
+        row.get(first + 2)?,
+        row.get(first + 3)?,
+        row.get(first + 4)?,
+        row.get(first + 5)?,
+    ))
+}
+
+fn to_result(row: ResultRow) -> Result<TestResult> {
+    let (test_id, status, duration, message, details, tags) = row;
+    Ok(TestResult {
+        test_id: TestId::new(test_id),
+        status: parse_status(&status)?,
+        duration: duration.map(Duration::from_secs_f64),
+        message,
+        details,
+        tags: split_tags(&tags),
+    })
+}
+
+/// Apply a stored run's results to the failing tests, as a partial or full run
+fn apply_to_failing(conn: &Connection, run_id: i64, partial: bool) -> Result<()> {
+    if !partial {
+        conn.execute("DELETE FROM failing", [])?;
+    }
+    conn.execute(
+        &format!(
+            "INSERT OR REPLACE INTO failing ({0}, raw) SELECT {0}, raw FROM results \
+             WHERE run_id = ?1 AND status IN {1}",
+            RESULT_COLUMNS, FAILURE_STATUSES
+        ),
+        params![run_id],
+    )?;
+    if partial {
+        conn.execute(
+            &format!(
+                "DELETE FROM failing WHERE test_id IN \
+                 (SELECT test_id FROM results WHERE run_id = ?1 AND status IN {})",
+                SUCCESS_STATUSES
+            ),
+            params![run_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Read a test's recent durations from the times table
+fn read_timing(conn: &Connection, test_id: &str) -> Result<Option<TestTiming>> {
+    let history: Option<String> = conn
+        .prepare_cached("SELECT history FROM times WHERE test_id = ?1")?
+        .query_row(params![test_id], |row| row.get(0))
+        .optional()?;
+    Ok(history.map(|history| TestTiming::decode(&history)))
+}
+
+/// Record durations in the times table, adding each to its test's history
+fn record_times<'a>(
+    conn: &Connection,
+    times: impl IntoIterator<Item = (&'a str, Duration)>,
+) -> Result<()> {
+    for (test_id, duration) in times {
+        let mut timing = read_timing(conn, test_id)?.unwrap_or_default();
+        timing.record(duration);
+        conn.prepare_cached(
+            "INSERT OR REPLACE INTO times (test_id, duration, history) VALUES (?1, ?2, ?3)",
+        )?
+        .execute(params![test_id, duration.as_secs_f64(), timing.encode()])?;
+    }
+    Ok(())
+}
+
+/// Record the durations from a stored run in the times table
+fn apply_to_times(conn: &Connection, run_id: i64) -> Result<()> {
+    let times: Vec<(String, f64)> = conn
+        .prepare_cached(
+            "SELECT test_id, duration FROM results WHERE run_id = ?1 AND duration IS NOT NULL",
+        )?
+        .query_map(params![run_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    record_times(
+        conn,
+        times
+            .iter()
+            .map(|(test_id, secs)| (test_id.as_str(), Duration::from_secs_f64(*secs))),
+    )
+}
+
+/// Replace the indexed results of a run with those in its stored stream
+///
+/// A stream that can't be parsed is recorded as such and left un-indexed, so
+/// that one bad run doesn't break every query; `check` reports it.
+fn index_run(conn: &Connection, run_id: i64, path: &Path) -> Result<()> {
+    let data = fs::read(path)?;
+    let parsed = subunit_stream::parse_stream_bytes(&data, run_id.to_string())
+        .and_then(|run| Ok((run, subunit_stream::failing_test_packets(&data)?)));
+    let (run, mut packets) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            conn.execute(
+                "UPDATE runs SET index_error = ?2 WHERE id = ?1",
+                params![run_id, e.to_string()],
+            )?;
+            return Ok(());
+        }
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM results WHERE run_id = ?1", params![run_id])?;
+    {
+        let mut insert = tx.prepare_cached(&format!(
+            "INSERT INTO results (run_id, {}, raw) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            RESULT_COLUMNS
+        ))?;
+        for result in run.results.values() {
+            insert.execute(params![
+                run_id,
+                result.test_id.as_str(),
+                result.status.to_string(),
+                result.duration.map(|d| d.as_secs_f64()),
+                result.message,
+                result.details,
+                result.tags.join(" "),
+                packets.remove(result.test_id.as_str()),
+            ])?;
+        }
+    }
+    tx.execute(
+        "UPDATE runs SET indexed = 1, index_error = NULL, tags = ?2 WHERE id = ?1",
+        params![run_id, run.tags.join(" ")],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Forget a run whose writer was dropped, giving its ID back unless a later
+/// run has been given an ID since
+fn release_run_id(repo_path: &Path, run_id: i64) -> Result<()> {
+    let mut conn = SqlRepositoryFactory::connect(repo_path)?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    tx.execute(
+        "DELETE FROM runs WHERE id = ?1 AND indexed = 0",
+        params![run_id],
+    )?;
+    tx.execute(
+        "UPDATE counter SET next_run = ?1 WHERE next_run = ?1 + 1",
+        params![run_id],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Writer for a run's stream that indexes the run once it is stored
+struct SqlRunWriter {
+    writer: RunWriter,
+    repo_path: PathBuf,
+    run_id: i64,
+}
+
+impl RunStreamWriter for SqlRunWriter {
+    fn commit(mut self: Box<Self>) -> Result<()> {
+        self.writer.finish()?;
+        let conn = SqlRepositoryFactory::connect(&self.repo_path)?;
+        index_run(
+            &conn,
+            self.run_id,
+            &self.repo_path.join(self.run_id.to_string()),
+        )
+    }
+}
+
+impl Write for SqlRunWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl SqlRepository {
+    fn get_run_path(&self, run_id: &str) -> PathBuf {
+        self.path.join(run_id)
+    }
+
+    fn get_config_path(&self) -> PathBuf {
+        self.path.join("repo.conf")
+    }
+
+    fn get_bookmarks_path(&self) -> PathBuf {
+        self.path.join("bookmarks")
+    }
+
+    /// Run a query selecting [`RESULT_COLUMNS`] and build the results
+    fn query_results(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<TestResult>> {
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let rows = stmt.query_map(params, |row| read_result(row, 0))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(to_result(row?)?);
+        }
+        Ok(results)
+    }
+
+    /// Run a query for history entries, selecting a run ID and the time the
+    /// run started followed by [`RESULT_COLUMNS`]
+    fn query_history(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let rows = stmt.query_map(params, |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                read_result(row, 2)?,
+            ))
+        })?;
+
+        let mut history = Vec::new();
+        for row in rows {
+            let (run_id, started_at, result) = row?;
+            history.push(HistoryEntry {
+                run_id: run_id.to_string(),
+                timestamp: parse_time(&started_at)?,
+                result: to_result(result)?,
+            });
+        }
+        Ok(history)
+    }
+
+    /// Whether a run has been stored and indexed
+    fn is_indexed(&self, run_id: i64) -> Result<bool> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT 1 FROM runs WHERE id = ?1 AND indexed = 1",
+                params![run_id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    /// Apply a run to the failing tests and record whether it was partial
+    fn record_failing(&mut self, run: &TestRun, partial: bool) -> Result<()> {
+        let run_id = parse_run_id(&run.id)?;
+
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "UPDATE runs SET partial = ?2 WHERE id = ?1",
+            params![run_id, partial],
+        )?;
+        apply_to_failing(&tx, run_id, partial)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete_indexed_run(&self, run_id: i64) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM results WHERE run_id = ?1", params![run_id])?;
+        tx.execute("DELETE FROM runs WHERE id = ?1", params![run_id])?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl Repository for SqlRepository {
+    fn get_test_run(&self, run_id: &str) -> Result<TestRun> {
+        let id = parse_run_id(run_id)?;
+
+        let (started_at, tags): (String, String) = self
+            .conn
+            .query_row(
+                "SELECT coalesce(start_time, stored_at), tags FROM runs \
+                 WHERE id = ?1 AND indexed = 1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| Error::TestRunNotFound(run_id.to_string()))?;
+
+        let mut run = TestRun::new(run_id.to_string());
+        run.timestamp = parse_time(&started_at)?;
+        run.tags = split_tags(&tags);
+        for result in self.query_results(
+            &format!("SELECT {} FROM results WHERE run_id = ?1", RESULT_COLUMNS),
+            params![id],
+        )? {
+            run.add_result(result);
+        }
+
+        Ok(run)
+    }
+
+    fn begin_test_run_raw(&mut self) -> Result<(String, Box<dyn RunStreamWriter>)> {
+        let repo_path = self.path.clone();
+        let tx = self
+            .conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let run_id: i64 = tx.query_row("SELECT next_run FROM counter", [], |row| row.get(0))?;
+        tx.execute("UPDATE counter SET next_run = ?1", params![run_id + 1])?;
+        tx.execute(
+            "INSERT INTO runs (id, stored_at) VALUES (?1, ?2)",
+            params![run_id, Utc::now().to_rfc3339()],
+        )?;
+        // The temporary file is created before the run is visible, so that
+        // check can always tell a run in progress from an abandoned one
+        let writer = RunWriter::create(repo_path.join(run_id.to_string()))?;
+        tx.commit()?;
+
+        let release_path = repo_path.clone();
+        let writer = SqlRunWriter {
+            writer: writer.on_release(move || release_run_id(&release_path, run_id)),
+            repo_path,
+            run_id,
+        };
+
+        Ok((run_id.to_string(), Box::new(writer)))
+    }
+
+    fn get_latest_run(&self) -> Result<TestRun> {
+        let latest: Option<i64> =
+            self.conn
+                .query_row("SELECT max(id) FROM runs WHERE indexed = 1", [], |row| {
+                    row.get(0)
+                })?;
+        let run_id = latest.ok_or(Error::NoTestRuns)?;
+        self.get_test_run(&run_id.to_string())
+    }
+
+    fn get_test_run_raw(&self, run_id: &str) -> Result<Box<dyn std::io::Read>> {
+        let path = self.get_run_path(run_id);
+        if parse_run_id(run_id).is_err() || !path.is_file() {
+            return Err(Error::TestRunNotFound(run_id.to_string()));
+        }
+        Ok(Box::new(File::open(&path)?))
+    }
+
+    fn update_failing_tests(&mut self, run: &TestRun) -> Result<()> {
+        self.record_failing(run, true)
+    }
+
+    fn replace_failing_tests(&mut self, run: &TestRun) -> Result<()> {
+        self.record_failing(run, false)
+    }
+
+    fn add_failing_tests(&mut self, results: &[TestResult]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(&format!(
+                "INSERT OR REPLACE INTO failing ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                RESULT_COLUMNS
+            ))?;
+            for result in results {
+                insert.execute(params![
+                    result.test_id.as_str(),
+                    result.status.to_string(),
+                    result.duration.map(|d| d.as_secs_f64()),
+                    result.message,
+                    result.details,
+                    result.tags.join(" "),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_failing_tests(&self) -> Result<Vec<TestId>> {
+        let mut stmt = self.conn.prepare_cached("SELECT test_id FROM failing")?;
+        let test_ids: Vec<TestId> = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|test_id| test_id.map(TestId::new))
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(test_ids)
+    }
+
+    fn get_failing_tests_raw(&self) -> Result<Box<dyn std::io::Read>> {
+        // Failing tests taken from a stored run keep their events from its
+        // stream; those added directly only have their results to go on
+        let mut stream = Vec::new();
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT raw FROM failing WHERE raw IS NOT NULL ORDER BY test_id")?;
+        for raw in stmt.query_map([], |row| row.get::<_, Vec<u8>>(0))? {
+            stream.extend_from_slice(&raw?);
+        }
+
+        let mut run = TestRun::new("failing".to_string());
+        for result in self.query_results(
+            &format!("SELECT {} FROM failing WHERE raw IS NULL", RESULT_COLUMNS),
+            [],
+        )? {
+            run.add_result(result);
+        }
+        subunit_stream::write_stream(&run, &mut stream)?;
+        Ok(Box::new(std::io::Cursor::new(stream)))
+    }
+
+    fn get_test_times(&self) -> Result<HashMap<TestId, Duration>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT test_id, duration FROM times")?;
+        let times: HashMap<TestId, Duration> = stmt
+            .query_map([], |row| {
+                Ok((
+                    TestId::new(row.get::<_, String>(0)?),
+                    Duration::from_secs_f64(row.get(1)?),
+                ))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(times)
+    }
+
+    fn get_test_times_for_ids(&self, test_ids: &[TestId]) -> Result<HashMap<TestId, Duration>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT duration FROM times WHERE test_id = ?1")?;
+
+        let mut times = HashMap::new();
+        for test_id in test_ids {
+            let duration: Option<f64> = stmt
+                .query_row(params![test_id.as_str()], |row| row.get(0))
+                .optional()?;
+            if let Some(duration) = duration {
+                times.insert(test_id.clone(), Duration::from_secs_f64(duration));
+            }
+        }
+        Ok(times)
+    }
+
+    fn get_test_timings_for_ids(&self, test_ids: &[TestId]) -> Result<HashMap<TestId, TestTiming>> {
+        let mut timings = HashMap::new();
+        for test_id in test_ids {
+            if let Some(timing) = read_timing(&self.conn, test_id.as_str())? {
+                timings.insert(test_id.clone(), timing);
+            }
+        }
+        Ok(timings)
+    }
+
+    fn update_test_times(&mut self, times: &HashMap<TestId, Duration>) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        record_times(
+            &tx,
+            times
+                .iter()
+                .map(|(test_id, duration)| (test_id.as_str(), *duration)),
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_next_run_id(&self) -> Result<u64> {
+        let next_run: i64 = self
+            .conn
+            .query_row("SELECT next_run FROM counter", [], |row| row.get(0))?;
+        Ok(next_run as u64)
+    }
+
+    fn list_run_ids(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT id FROM runs WHERE indexed = 1 ORDER BY id")?;
+        let ids: Vec<String> = stmt
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .map(|id| id.map(|id| id.to_string()))
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(ids)
+    }
+
+    fn count(&self) -> Result<usize> {
+        let count: i64 =
+            self.conn
+                .query_row("SELECT count(*) FROM runs WHERE indexed = 1", [], |row| {
+                    row.get(0)
+                })?;
+        Ok(count as usize)
+    }
+
+    fn delete_run(&mut self, run_id: &str) -> Result<()> {
+        let id = parse_run_id(run_id)?;
+        if !self.is_indexed(id)? {
+            return Err(Error::TestRunNotFound(run_id.to_string()));
+        }
+
+        self.delete_indexed_run(id)?;
+        let path = self.get_run_path(run_id);
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    fn get_run_time(&self, run_id: &str) -> Result<DateTime<Utc>> {
+        let id = parse_run_id(run_id)?;
+        let started_at: String = self
+            .conn
+            .query_row(
+                "SELECT coalesce(start_time, stored_at) FROM runs WHERE id = ?1 AND indexed = 1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| Error::TestRunNotFound(run_id.to_string()))?;
+        parse_time(&started_at)
+    }
+
+    fn get_test_history(&self, tests: &TestSelector) -> Result<Vec<HistoryEntry>> {
+        // Read from the results table rather than run by run, so that a
+        // test's results are found through the results_by_test index
+        let query = |condition: &str| {
+            format!(
+                "WITH indexed_runs AS (\
+                     SELECT id, coalesce(start_time, stored_at) AS started_at \
+                     FROM runs WHERE indexed = 1) \
+                 SELECT run_id, started_at, {} FROM results \
+                 JOIN indexed_runs ON indexed_runs.id = results.run_id{} \
+                 ORDER BY run_id, test_id",
+                RESULT_COLUMNS, condition
+            )
+        };
+
+        match tests {
+            TestSelector::All => self.query_history(&query(""), []),
+            TestSelector::Exact(test_id) => {
+                self.query_history(&query(" WHERE test_id = ?1"), params![test_id.as_str()])
+            }
+            TestSelector::AnyOf(_) | TestSelector::Pattern(_) => {
+                let mut test_ids: Vec<String> = match tests {
+                    TestSelector::AnyOf(test_ids) => {
+                        test_ids.iter().map(|id| id.as_str().to_string()).collect()
+                    }
+                    _ => {
+                        let known: Vec<String> = self
+                            .conn
+                            .prepare_cached("SELECT DISTINCT test_id FROM results")?
+                            .query_map([], |row| row.get(0))?
+                            .collect::<rusqlite::Result<_>>()?;
+                        known
+                            .into_iter()
+                            .filter(|id| tests.matches(&TestId::new(id.as_str())))
+                            .collect()
+                    }
+                };
+                test_ids.sort();
+                test_ids.dedup();
+
+                let sql = query(" WHERE test_id = ?1");
+                let mut history = Vec::new();
+                for test_id in test_ids {
+                    history.extend(self.query_history(&sql, params![test_id])?);
+                }
+                let run = |entry: &HistoryEntry| entry.run_id.parse::<i64>().unwrap_or_default();
+                history.sort_by(|a, b| {
+                    run(a)
+                        .cmp(&run(b))
+                        .then_with(|| a.result.test_id.as_str().cmp(b.result.test_id.as_str()))
+                });
+                Ok(history)
+            }
+        }
+    }
+
+    fn get_run_metadata(&self, run_id: &str) -> Result<RunMetadata> {
+        let id = parse_run_id(run_id)?;
+
+        // Times and exit codes are stored as text and parsed afterwards
+        let (mut metadata, start_time, end_time, exit_codes) = self
+            .conn
+            .query_row(
+                "SELECT partial, command, concurrency, git_commit, git_dirty, hostname, \
+                 interrupted, start_time, end_time, exit_codes FROM runs \
+                 WHERE id = ?1 AND indexed = 1",
+                params![id],
+                |row| {
+                    let metadata = RunMetadata {
+                        partial: row.get(0)?,
+                        command: row.get(1)?,
+                        concurrency: row.get::<_, Option<i64>>(2)?.map(|n| n as usize),
+                        git_commit: row.get(3)?,
+                        git_dirty: row.get(4)?,
+                        hostname: row.get(5)?,
+                        interrupted: row.get(6)?,
+                        ..Default::default()
+                    };
+                    let start_time: Option<String> = row.get(7)?;
+                    let end_time: Option<String> = row.get(8)?;
+                    let exit_codes: Option<String> = row.get(9)?;
+                    Ok((metadata, start_time, end_time, exit_codes))
+                },
+            )
+            .optional()?
+            .ok_or_else(|| Error::TestRunNotFound(run_id.to_string()))?;
+
+        metadata.start_time = start_time.as_deref().map(parse_time).transpose()?;
+        metadata.end_time = end_time.as_deref().map(parse_time).transpose()?;
+        metadata.exit_codes = exit_codes
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|code| !code.is_empty())
+            .map(|code| {
+                code.parse().map_err(|_| {
+                    Error::InvalidFormat(format!("Invalid exit code in {}: {}", DATABASE, code))
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(metadata)
+    }
+
+    fn set_run_metadata(&mut self, run_id: &str, metadata: &RunMetadata) -> Result<()> {
+        let id = parse_run_id(run_id)?;
+
+        let exit_codes = metadata
+            .exit_codes
+            .iter()
+            .map(|code| code.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let updated = self.conn.execute(
+            "UPDATE runs SET partial = coalesce(?2, partial), start_time = ?3, end_time = ?4, \
+             command = ?5, concurrency = ?6, exit_codes = ?7, git_commit = ?8, git_dirty = ?9, \
+             hostname = ?10, interrupted = ?11 WHERE id = ?1 AND indexed = 1",
+            params![
+                id,
+                metadata.partial,
+                metadata.start_time.map(|t| t.to_rfc3339()),
+                metadata.end_time.map(|t| t.to_rfc3339()),
+                metadata.command,
+                metadata.concurrency.map(|n| n as i64),
+                exit_codes,
+                metadata.git_commit,
+                metadata.git_dirty,
+                metadata.hostname,
+                metadata.interrupted,
+            ],
+        )?;
+        if updated == 0 {
+            return Err(Error::TestRunNotFound(run_id.to_string()));
+        }
+        Ok(())
+    }
+
+    fn rebuild_failing_and_times(&mut self, run_ids: &[String]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute_batch("DELETE FROM failing; DELETE FROM times;")?;
+        for run_id in run_ids {
+            let id = parse_run_id(run_id)?;
+            let partial: Option<bool> = tx
+                .query_row(
+                    "SELECT partial FROM runs WHERE id = ?1 AND indexed = 1",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .ok_or_else(|| Error::TestRunNotFound(run_id.clone()))?;
+
+            // Runs whose mode was not recorded are replayed as partial
+            apply_to_failing(&tx, id, partial != Some(false))?;
+            apply_to_times(&tx, id)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn check(&mut self, repair: bool) -> Result<Vec<CheckIssue>> {
+        let mut issues = Vec::new();
+
+        let integrity: String = self
+            .conn
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+        if integrity != "ok" {
+            issues.push(CheckIssue {
+                location: DATABASE.to_string(),
+                message: integrity,
+                repaired: false,
+            });
+        }
+
+        // Runs in progress allocate their IDs and create their temporary
+        // files in a write transaction, so with one held every run that
+        // isn't stored or in progress was abandoned
+        let behavior = if repair {
+            TransactionBehavior::Immediate
+        } else {
+            TransactionBehavior::Deferred
+        };
+        let tx = self.conn.transaction_with_behavior(behavior)?;
+        let stored = run_files(&self.path)?;
+        let in_progress = runs_in_progress(&self.path)?;
+        let unindexed: Vec<i64> = tx
+            .prepare("SELECT id FROM runs WHERE indexed = 0 ORDER BY id")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for id in unindexed {
+            let run_id = id as u64;
+            if stored.contains(&run_id) || in_progress.contains(&run_id) {
+                continue;
+            }
+            if repair {
+                tx.execute("DELETE FROM runs WHERE id = ?1", params![id])?;
+            }
+            issues.push(CheckIssue {
+                location: format!("run {}", id),
+                message: "run was abandoned before its stream was stored".to_string(),
+                repaired: repair,
+            });
+        }
+
+        let mut runs = stored;
+        runs.extend(in_progress);
+        let counter: Option<i64> = tx
+            .query_row("SELECT next_run FROM counter", [], |row| row.get(0))
+            .optional()?;
+        if let Some(next_run) = check_run_numbering(
+            "counter",
+            counter.map(|c| c as u64),
+            &runs,
+            repair,
+            &mut issues,
+        ) {
+            if repair {
+                tx.execute("UPDATE counter SET next_run = ?1", params![next_run as i64])?;
+            }
+        }
+        tx.commit()?;
+
+        let runs: Vec<(i64, bool, Option<String>)> = self
+            .conn
+            .prepare("SELECT id, indexed, index_error FROM runs ORDER BY id")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut runs_damaged = false;
+        let mut intact_runs = Vec::new();
+        for (id, indexed, index_error) in runs {
+            let run_id = id.to_string();
+            let path = self.get_run_path(&run_id);
+            let location = format!("run {}", run_id);
+
+            if !indexed {
+                // Runs without a stream are still being written, or were
+                // abandoned and reported above
+                if !path.is_file() {
+                    continue;
+                }
+                let message = match index_error {
+                    Some(error) => format!("stream could not be indexed: {}", error),
+                    None => "stream was never indexed".to_string(),
+                };
+                let repaired = repair && {
+                    index_run(&self.conn, id, &path)?;
+                    self.is_indexed(id)?
+                };
+                issues.push(CheckIssue {
+                    location,
+                    message,
+                    repaired,
+                });
+                if repaired {
+                    runs_damaged = true;
+                    intact_runs.push(run_id);
+                }
+                continue;
+            }
+
+            if !path.is_file() {
+                if repair {
+                    self.delete_indexed_run(id)?;
+                }
+                issues.push(CheckIssue {
+                    location,
+                    message: "stream is missing".to_string(),
+                    repaired: repair,
+                });
+                runs_damaged = true;
+                continue;
+            }
+
+            let found = issues.len();
+            if check_stream(&path, &location, repair, &mut issues)? {
+                runs_damaged = true;
+                continue;
+            }
+            if issues.len() > found {
+                // A torn tail was cut off, so index what is left
+                index_run(&self.conn, id, &path)?;
+                runs_damaged = true;
+            }
+            intact_runs.push(run_id);
+        }
+        check_stale_run_files(&self.path, repair, &mut issues)?;
+
+        if repair && runs_damaged {
+            self.rebuild_failing_and_times(&intact_runs)?;
+        }
+
+        Ok(issues)
+    }
+
+    fn get_config(&self) -> Result<RepoConfig> {
+        RepoConfig::load_from_file(&self.get_config_path())
+    }
+
+    fn set_config(&mut self, config: &RepoConfig) -> Result<()> {
+        // The write transaction keeps other writers out while the file is
+        // replaced
+        let tx = self
+            .conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let path = self.get_config_path();
+        let temp_path = path.with_extension("conf.tmp");
+        fs::write(&temp_path, config.to_ini_string())?;
+        fs::rename(&temp_path, &path)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_bookmarks(&self) -> Result<BTreeMap<String, String>> {
+        run_spec::load_bookmarks(&self.get_bookmarks_path())
+    }
+
+    fn set_bookmark(&mut self, name: &str, run_id: Option<&str>) -> Result<()> {
+        run_spec::store_bookmark(&self.get_bookmarks_path(), name, run_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_initialise_and_open() {
+        let temp = TempDir::new().unwrap();
+        let factory = SqlRepositoryFactory;
+
+        factory.initialise(temp.path()).unwrap();
+        let repo_path = temp.path().join(".testrepository");
+        assert_eq!(
+            fs::read_to_string(repo_path.join("format")).unwrap(),
+            "sql\n"
+        );
+        assert!(repo_path.join(DATABASE).exists());
+
+        assert!(matches!(
+            factory.initialise(temp.path()),
+            Err(Error::RepositoryExists(_))
+        ));
+        let repo = factory.open(temp.path()).unwrap();
+        assert_eq!(repo.count().unwrap(), 0);
+        assert!(matches!(repo.get_latest_run(), Err(Error::NoTestRuns)));
+
+        // A file repository is not a SQL repository
+        let other = TempDir::new().unwrap();
+        crate::repository::file::FileRepositoryFactory
+            .initialise(other.path())
+            .unwrap();
+        assert!(matches!(
+            factory.open(other.path()),
+            Err(Error::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_runs_are_indexed() {
+        let temp = TempDir::new().unwrap();
+        let mut repo = SqlRepositoryFactory.initialise(temp.path()).unwrap();
+
+        let mut run = TestRun::new("0".to_string());
+        run.add_result(
+            TestResult::failure("test1", "Failed")
+                .with_duration(Duration::from_secs(2))
+                .with_details("Traceback")
+                .with_tag("worker-0"),
+        );
+        run.add_result(TestResult::success("test2").with_duration(Duration::from_secs(1)));
+        assert_eq!(repo.insert_test_run(run).unwrap(), "0");
+
+        let run = repo.get_test_run("0").unwrap();
+        assert_eq!(run.total_tests(), 2);
+        let result = &run.results[&TestId::new("test1")];
+        assert_eq!(result.status, TestStatus::Failure);
+        assert_eq!(result.duration, Some(Duration::from_secs(2)));
+        assert_eq!(result.details.as_deref(), Some("Traceback"));
+        assert_eq!(result.tags, vec!["worker-0"]);
+
+        // The raw stream is kept byte for byte
+        let mut raw = Vec::new();
+        repo.get_test_run_raw("0")
+            .unwrap()
+            .read_to_end(&mut raw)
+            .unwrap();
+        assert_eq!(
+            raw,
+            fs::read(temp.path().join(".testrepository").join("0")).unwrap()
+        );
+
+        assert_eq!(
+            repo.get_failing_tests().unwrap(),
+            vec![TestId::new("test1")]
+        );
+
+        // The failing stream keeps the events of the run, attachments and all
+        let mut failing = Vec::new();
+        repo.get_failing_tests_raw()
+            .unwrap()
+            .read_to_end(&mut failing)
+            .unwrap();
+        let mut expected = Vec::new();
+        subunit_stream::filter_failing_tests(&raw[..], &mut expected).unwrap();
+        assert_eq!(failing, expected);
+        assert_eq!(
+            repo.get_test_times_for_ids(&[TestId::new("test2"), TestId::new("test3")])
+                .unwrap(),
+            HashMap::from([(TestId::new("test2"), Duration::from_secs(1))])
+        );
+
+        // A stream written through the raw API is indexed once it is committed
+        let (run_id, mut writer) = repo.begin_test_run_raw().unwrap();
+        let mut run = TestRun::new(run_id.clone());
+        run.add_result(TestResult::success("test1"));
+        subunit_stream::write_stream(&run, &mut writer).unwrap();
+        assert_eq!(repo.count().unwrap(), 1);
+        writer.commit().unwrap();
+        assert_eq!(repo.count().unwrap(), 2);
+        repo.update_failing_tests(&run).unwrap();
+
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["0", "1"]);
+        assert_eq!(repo.get_latest_run().unwrap().id, "1");
+        assert!(repo.get_failing_tests().unwrap().is_empty());
+        assert_eq!(repo.get_next_run_id().unwrap(), 2);
+
+        repo.add_failing_tests(&[TestResult::failure("test1", "Flaky")])
+            .unwrap();
+        assert_eq!(
+            repo.get_failing_tests().unwrap(),
+            vec![TestId::new("test1")]
+        );
+    }
+
+    #[test]
+    fn test_get_test_history() {
+        let temp = TempDir::new().unwrap();
+        let mut repo = SqlRepositoryFactory.initialise(temp.path()).unwrap();
+        for i in 0..11 {
+            let mut run = TestRun::new(i.to_string());
+            run.add_result(TestResult::success("pkg.test_b"));
+            run.add_result(TestResult::failure("pkg.test_a", "boom"));
+            run.add_result(TestResult::success("other"));
+            repo.insert_test_run(run).unwrap();
+        }
+
+        let found = |tests: &TestSelector| -> Vec<(String, String)> {
+            repo.get_test_history(tests)
+                .unwrap()
+                .into_iter()
+                .map(|entry| (entry.run_id, entry.result.test_id.as_str().to_string()))
+                .collect()
+        };
+        let exact = found(&TestSelector::Exact(TestId::new("pkg.test_a")));
+        assert_eq!(exact.len(), 11);
+        assert!(exact.iter().all(|(_, test_id)| test_id == "pkg.test_a"));
+        assert_eq!(exact[10].0, "10");
+
+        // Runs are in numeric order, and tests by ID within a run
+        let pattern = found(&TestSelector::pattern("^pkg").unwrap());
+        assert_eq!(pattern.len(), 22);
+        assert_eq!(
+            pattern[..2],
+            [
+                ("0".to_string(), "pkg.test_a".to_string()),
+                ("0".to_string(), "pkg.test_b".to_string())
+            ]
+        );
+        assert_eq!(pattern[21].0, "10");
+        assert_eq!(found(&TestSelector::All).len(), 33);
+        let any_of = TestSelector::AnyOf(vec![TestId::new("pkg.test_b"), TestId::new("missing")]);
+        assert_eq!(found(&any_of).len(), 11);
+
+        let history = repo
+            .get_test_history(&TestSelector::Exact(TestId::new("pkg.test_a")))
+            .unwrap();
+        assert_eq!(history[0].result.status, TestStatus::Failure);
+        assert_eq!(history[0].result.message.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn test_unindexed_runs_are_reported() {
+        let temp = TempDir::new().unwrap();
+        let repo_path = temp.path().join(".testrepository");
+        let mut repo = SqlRepositoryFactory.initialise(temp.path()).unwrap();
+        repo.insert_test_run(TestRun::new("0".to_string())).unwrap();
+
+        // A stream whose process was killed after storing it but before
+        // indexing it
+        let (run_id, mut writer) = repo.begin_test_run_raw().unwrap();
+        let mut run = TestRun::new(run_id.clone());
+        run.add_result(TestResult::failure("test1", "Failed"));
+        subunit_stream::write_stream(&run, &mut writer).unwrap();
+        writer.flush().unwrap();
+        std::mem::forget(writer);
+        fs::rename(
+            repo_path.join(format!("{}.tmp", run_id)),
+            repo_path.join(&run_id),
+        )
+        .unwrap();
+
+        // Reads skip it rather than indexing it
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["0"]);
+        assert_eq!(repo.get_latest_run().unwrap().id, "0");
+
+        let issues = repo.check(false).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].location, "run 1");
+        assert_eq!(issues[0].message, "stream was never indexed");
+        assert!(!issues[0].repaired);
+
+        Connection::open(repo_path.join(DATABASE))
+            .unwrap()
+            .execute(
+                "UPDATE runs SET index_error = 'bad timestamp' WHERE id = 1",
+                [],
+            )
+            .unwrap();
+        let issues = repo.check(false).unwrap();
+        assert_eq!(
+            issues[0].message,
+            "stream could not be indexed: bad timestamp"
+        );
+
+        assert!(repo.check(true).unwrap()[0].repaired);
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["0", "1"]);
+        assert_eq!(
+            repo.get_failing_tests().unwrap(),
+            vec![TestId::new("test1")]
+        );
+        assert!(repo.check(false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_uncommitted_run_is_discarded() {
+        let temp = TempDir::new().unwrap();
+        let mut repo = SqlRepositoryFactory.initialise(temp.path()).unwrap();
+
+        let (run_id, mut writer) = repo.begin_test_run_raw().unwrap();
+        writer.write_all(b"partial output").unwrap();
+        drop(writer);
+        assert!(!temp.path().join(".testrepository").join(&run_id).exists());
+        assert_eq!(repo.get_next_run_id().unwrap(), 0);
+        assert!(repo.check(false).unwrap().is_empty());
+
+        // A process killed part way through a run leaves its ID taken
+        let (run_id, writer) = repo.begin_test_run_raw().unwrap();
+        std::mem::forget(writer);
+        fs::remove_file(
+            temp.path()
+                .join(".testrepository")
+                .join(format!("{}.tmp", run_id)),
+        )
+        .unwrap();
+        let issues = repo.check(false).unwrap();
+        let found: Vec<_> = issues
+            .iter()
+            .map(|i| (i.location.as_str(), i.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("run 0", "run was abandoned before its stream was stored"),
+                ("counter", "counter is 1 but there are no runs"),
+            ]
+        );
+        assert!(repo.check(true).unwrap().iter().all(|i| i.repaired));
+        assert_eq!(repo.get_next_run_id().unwrap(), 0);
+        assert!(repo.check(false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_delete_and_rebuild() {
+        let temp = TempDir::new().unwrap();
+        let mut repo = SqlRepositoryFactory.initialise(temp.path()).unwrap();
+
+        let runs = [
+            (vec![("a", false), ("b", false)], false),
+            (vec![("a", true)], true),
+            (vec![("c", false)], false),
+        ];
+        for (i, (results, partial)) in runs.into_iter().enumerate() {
+            let mut run = TestRun::new(i.to_string());
+            for (test_id, passed) in results {
+                run.add_result(if passed {
+                    TestResult::success(test_id)
+                } else {
+                    TestResult::failure(test_id, "Failed")
+                });
+            }
+            repo.insert_test_run_partial(run, partial).unwrap();
+        }
+        assert_eq!(repo.get_failing_tests().unwrap(), vec![TestId::new("c")]);
+
+        repo.rebuild_failing_and_times(&["0".to_string(), "1".to_string()])
+            .unwrap();
+        assert_eq!(repo.get_failing_tests().unwrap(), vec![TestId::new("b")]);
+
+        repo.delete_run("2").unwrap();
+        assert_eq!(repo.list_run_ids().unwrap(), vec!["0", "1"]);
+        assert!(!temp.path().join(".testrepository").join("2").exists());
+        assert!(matches!(
+            repo.get_test_run("2"),
+            Err(Error::TestRunNotFound(_))
+        ));
+        assert_eq!(repo.get_next_run_id().unwrap(), 3);
+        assert!(repo.check(false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_run_metadata() {
+        let temp = TempDir::new().unwrap();
+        let mut repo = SqlRepositoryFactory.initialise(temp.path()).unwrap();
+        repo.insert_test_run(TestRun::new("0".to_string())).unwrap();
+        assert_eq!(
+            repo.get_run_metadata("0").unwrap(),
+            RunMetadata {
+                partial: Some(false),
+                ..Default::default()
+            }
+        );
+
+        // Each test keeps a history of its times
+        for secs in [2, 4] {
+            repo.update_test_times(&HashMap::from([(
+                TestId::new("test1"),
+                Duration::from_secs(secs),
+            )]))
+            .unwrap();
+        }
+        let timings = repo
+            .get_test_timings_for_ids(&[TestId::new("test1")])
+            .unwrap();
+        assert_eq!(
+            timings[&TestId::new("test1")].samples(),
+            [Duration::from_secs(2), Duration::from_secs(4)]
+        );
+
+        let start_time = DateTime::from_timestamp(1000000000, 0).unwrap();
+        let metadata = RunMetadata {
+            start_time: Some(start_time),
+            end_time: DateTime::from_timestamp(1000000010, 0),
+            command: Some("python -m test".to_string()),
+            concurrency: Some(2),
+            exit_codes: vec![0, -9],
+            interrupted: Some(true),
+            git_commit: Some("0123456789abcdef".to_string()),
+            git_dirty: Some(false),
+            hostname: Some("build-1".to_string()),
+            ..Default::default()
+        };
+        repo.set_run_metadata("0", &metadata).unwrap();
+        assert_eq!(
+            repo.get_run_metadata("0").unwrap(),
+            RunMetadata {
+                partial: Some(false),
+                ..metadata.clone()
+            }
+        );
+        assert_eq!(repo.get_test_run("0").unwrap().timestamp, start_time);
+        assert_eq!(repo.get_run_time("0").unwrap(), start_time);
+        assert!(matches!(
+            repo.set_run_metadata("1", &metadata),
+            Err(Error::TestRunNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,218 @@
+//! How individual tests behaved across stored runs
+
+use crate::error::{Error, Result};
+use crate::repository::{TestId, TestResult, TestStatus};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::time::Duration;
+
+/// The tests a history query is for
+#[derive(Debug, Clone)]
+pub enum TestSelector {
+    /// Every test
+    All,
+    /// The test with exactly this ID
+    Exact(TestId),
+    /// Each of these tests
+    AnyOf(Vec<TestId>),
+    /// The tests whose ID matches this regex, or is its pattern
+    Pattern(Regex),
+}
+
+impl TestSelector {
+    /// Select the tests whose ID matches a regex, or is the pattern itself
+    pub fn pattern(pattern: &str) -> Result<Self> {
+        Regex::new(pattern)
+            .map(TestSelector::Pattern)
+            .map_err(|e| Error::Config(format!("Invalid test filter regex '{}': {}", pattern, e)))
+    }
+
+    /// Whether a test is selected
+    pub fn matches(&self, test_id: &TestId) -> bool {
+        match self {
+            TestSelector::All => true,
+            TestSelector::Exact(id) => id == test_id,
+            TestSelector::AnyOf(ids) => ids.contains(test_id),
+            TestSelector::Pattern(regex) => {
+                test_id.as_str() == regex.as_str() || regex.is_match(test_id.as_str())
+            }
+        }
+    }
+}
+
+/// A test's result in one stored run
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    /// ID of the run
+    pub run_id: String,
+    /// When the run started
+    pub timestamp: DateTime<Utc>,
+    /// The test's result in that run
+    pub result: TestResult,
+}
+
+/// Statistics over the recorded durations of a test
+#[derive(Debug, Clone, PartialEq)]
+pub struct DurationStats {
+    /// Number of runs that recorded a duration
+    pub count: usize,
+    /// Mean duration
+    pub mean: Duration,
+    /// Shortest duration
+    pub min: Duration,
+    /// Longest duration
+    pub max: Duration,
+    /// Population standard deviation of the durations
+    pub stddev: Duration,
+}
+
+impl DurationStats {
+    /// Compute statistics over some durations, or `None` if there are none
+    pub fn from_durations(durations: &[Duration]) -> Option<Self> {
+        let min = *durations.iter().min()?;
+        let max = *durations.iter().max()?;
+        let count = durations.len();
+        let secs: Vec<f64> = durations.iter().map(Duration::as_secs_f64).collect();
+        let mean = secs.iter().sum::<f64>() / count as f64;
+        let variance = secs.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / count as f64;
+        Some(DurationStats {
+            count,
+            mean: Duration::from_secs_f64(mean),
+            min,
+            max,
+            stddev: Duration::from_secs_f64(variance.sqrt()),
+        })
+    }
+}
+
+/// Summary of one test's history
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistorySummary {
+    /// Number of runs the test appeared in
+    pub runs: usize,
+    /// Runs in which it passed (including expected failures)
+    pub passes: usize,
+    /// Runs in which it failed
+    pub failures: usize,
+    /// Runs in which it was skipped
+    pub skips: usize,
+    /// The first run in which it failed
+    pub first_failure: Option<String>,
+    /// The last run in which it passed
+    pub last_success: Option<String>,
+    /// Statistics over its recorded durations
+    pub duration: Option<DurationStats>,
+}
+
+impl HistorySummary {
+    /// Summarize the history of a single test, given oldest run first
+    pub fn from_entries(entries: &[HistoryEntry]) -> Self {
+        let passed = |entry: &&HistoryEntry| {
+            matches!(
+                entry.result.status,
+                TestStatus::Success | TestStatus::ExpectedFailure
+            )
+        };
+        let failed = |entry: &&HistoryEntry| entry.result.status.is_failure();
+
+        let durations: Vec<Duration> = entries
+            .iter()
+            .filter_map(|entry| entry.result.duration)
+            .collect();
+
+        HistorySummary {
+            runs: entries.len(),
+            passes: entries.iter().filter(passed).count(),
+            failures: entries.iter().filter(failed).count(),
+            skips: entries
+                .iter()
+                .filter(|entry| entry.result.status == TestStatus::Skip)
+                .count(),
+            first_failure: entries.iter().find(failed).map(|e| e.run_id.clone()),
+            last_success: entries.iter().rev().find(passed).map(|e| e.run_id.clone()),
+            duration: DurationStats::from_durations(&durations),
+        }
+    }
+
+    /// Fraction of the runs in which the test passed or failed that it
+    /// passed, or `None` if it was always skipped
+    pub fn pass_rate(&self) -> Option<f64> {
+        let decided = self.passes + self.failures;
+        (decided > 0).then(|| self.passes as f64 / decided as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(run_id: &str, result: TestResult) -> HistoryEntry {
+        HistoryEntry {
+            run_id: run_id.to_string(),
+            timestamp: DateTime::from_timestamp(1000000000, 0).unwrap(),
+            result,
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let entries = vec![
+            entry(
+                "0",
+                TestResult::success("t").with_duration(Duration::from_secs(1)),
+            ),
+            entry(
+                "2",
+                TestResult::failure("t", "boom").with_duration(Duration::from_secs(3)),
+            ),
+            entry("3", TestResult::skip("t")),
+            entry(
+                "5",
+                TestResult::success("t").with_duration(Duration::from_secs(2)),
+            ),
+            entry("6", TestResult::error("t", "boom")),
+        ];
+
+        let summary = HistorySummary::from_entries(&entries);
+        assert_eq!(summary.runs, 5);
+        assert_eq!(summary.passes, 2);
+        assert_eq!(summary.failures, 2);
+        assert_eq!(summary.skips, 1);
+        assert_eq!(summary.first_failure.as_deref(), Some("2"));
+        assert_eq!(summary.last_success.as_deref(), Some("5"));
+        assert_eq!(summary.pass_rate(), Some(0.5));
+
+        let duration = summary.duration.unwrap();
+        assert_eq!(duration.count, 3);
+        assert_eq!(duration.mean, Duration::from_secs(2));
+        assert_eq!(duration.min, Duration::from_secs(1));
+        assert_eq!(duration.max, Duration::from_secs(3));
+        assert!((duration.stddev.as_secs_f64() - (2.0f64 / 3.0).sqrt()).abs() < 1e-6);
+
+        let summary = HistorySummary::from_entries(&[entry("0", TestResult::skip("t"))]);
+        assert_eq!(summary.pass_rate(), None);
+        assert_eq!(summary.duration, None);
+        assert_eq!(summary.first_failure, None);
+    }
+
+    #[test]
+    fn test_selector() {
+        let exact = TestSelector::Exact(TestId::new("pkg.test_a"));
+        assert!(exact.matches(&TestId::new("pkg.test_a")));
+        assert!(!exact.matches(&TestId::new("pkg.test_ab")));
+
+        let pattern = TestSelector::pattern("pkg\\.test_.").unwrap();
+        assert!(pattern.matches(&TestId::new("pkg.test_a")));
+        assert!(!pattern.matches(&TestId::new("other.test_a")));
+        // A test ID that happens to contain regex syntax is still selected
+        let pattern = TestSelector::pattern("test[1]").unwrap();
+        assert!(pattern.matches(&TestId::new("test[1]")));
+
+        let any_of = TestSelector::AnyOf(vec![TestId::new("a"), TestId::new("b")]);
+        assert!(any_of.matches(&TestId::new("b")));
+        assert!(!any_of.matches(&TestId::new("c")));
+
+        assert!(TestSelector::pattern("test(").is_err());
+        assert!(TestSelector::All.matches(&TestId::new("anything")));
+    }
+}
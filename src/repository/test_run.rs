@@ -285,6 +285,32 @@ impl TestRun {
             .count()
     }
 
+    /// Returns a copy of this run with tagged pseudo-tests hidden
+    ///
+    /// Implements the `filter_tags` configuration option: results carrying any
+    /// of the given tags are dropped unless they failed, so that they only show
+    /// up in counts and reports when something went wrong.
+    pub fn hide_filtered_tags(&self, filter_tags: &[String]) -> TestRun {
+        let mut run = self.clone();
+        if !filter_tags.is_empty() {
+            run.results
+                .retain(|_, r| r.status.is_failure() || !Self::matches_filter(r, filter_tags));
+        }
+        run
+    }
+
+    /// Returns the IDs of tests in this run that carry any of the given tags
+    pub fn tagged_test_ids(&self, tags: &[String]) -> Vec<&TestId> {
+        if tags.is_empty() {
+            return Vec::new();
+        }
+        self.results
+            .values()
+            .filter(|r| Self::matches_filter(r, tags))
+            .map(|r| &r.test_id)
+            .collect()
+    }
+
     /// Returns a list of test IDs for all tests that failed in this run.
     pub fn get_failing_tests(&self) -> Vec<&TestId> {
         self.results
@@ -501,4 +527,28 @@ mod tests {
         let filter = vec!["slow".to_string()];
         assert_eq!(run.total_tests_filtered(&filter), 1);
     }
+
+    #[test]
+    fn test_hide_filtered_tags() {
+        let mut run = TestRun::new("0".to_string());
+
+        run.add_result(TestResult::success("layer.setUp").with_tag("zope:layer"));
+        run.add_result(TestResult::failure("layer.tearDown", "Failed").with_tag("zope:layer"));
+        run.add_result(TestResult::success("test1"));
+        run.add_result(TestResult::failure("test2", "Failed"));
+
+        let filter = vec!["zope:layer".to_string()];
+        let visible = run.hide_filtered_tags(&filter);
+
+        // Tagged tests only show up when they fail
+        assert_eq!(visible.total_tests(), 3);
+        assert_eq!(visible.count_successes(), 1);
+        assert_eq!(visible.count_failures(), 2);
+        assert!(!visible.results.contains_key(&TestId::new("layer.setUp")));
+
+        // No filter leaves the run untouched
+        assert_eq!(run.hide_filtered_tags(&[]).total_tests(), 4);
+        assert_eq!(run.tagged_test_ids(&filter).len(), 2);
+        assert!(run.tagged_test_ids(&[]).is_empty());
+    }
 }
@@ -0,0 +1,225 @@
+//! Recorded durations of tests and the estimates used to schedule them
+//!
+//! The times database keeps the most recent durations of every test rather
+//! than just the last one, so that one unusually slow or fast run doesn't
+//! throw off how tests are partitioned across workers.
+
+use crate::error::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Number of recent durations kept for each test
+pub const TIMING_HISTORY_SIZE: usize = 20;
+
+/// The most recent recorded durations of a test, oldest first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TestTiming {
+    samples: Vec<Duration>,
+}
+
+impl TestTiming {
+    /// Create a timing from durations given oldest first
+    ///
+    /// Only the last [`TIMING_HISTORY_SIZE`] durations are kept.
+    pub fn new(samples: Vec<Duration>) -> Self {
+        let mut timing = TestTiming { samples };
+        timing.trim();
+        timing
+    }
+
+    fn trim(&mut self) {
+        let excess = self.samples.len().saturating_sub(TIMING_HISTORY_SIZE);
+        self.samples.drain(..excess);
+    }
+
+    /// Record a new duration, dropping the oldest if the history is full
+    pub fn record(&mut self, duration: Duration) {
+        self.samples.push(duration);
+        self.trim();
+    }
+
+    /// The recorded durations, oldest first
+    pub fn samples(&self) -> &[Duration] {
+        &self.samples
+    }
+
+    /// Number of recorded durations
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// The most recently recorded duration
+    pub fn last(&self) -> Option<Duration> {
+        self.samples.last().copied()
+    }
+
+    /// Mean of the recorded durations
+    pub fn mean(&self) -> Option<Duration> {
+        self.mean_secs().map(Duration::from_secs_f64)
+    }
+
+    fn mean_secs(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let total: f64 = self.samples.iter().map(Duration::as_secs_f64).sum();
+        Some(total / self.samples.len() as f64)
+    }
+
+    /// Population variance of the recorded durations, in seconds squared
+    pub fn variance(&self) -> Option<f64> {
+        let mean = self.mean_secs()?;
+        let total: f64 = self
+            .samples
+            .iter()
+            .map(|d| (d.as_secs_f64() - mean).powi(2))
+            .sum();
+        Some(total / self.samples.len() as f64)
+    }
+
+    /// The duration that `percent` percent of the recorded durations don't
+    /// exceed, by the nearest-rank method
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+        sorted.get(rank.clamp(1, sorted.len().max(1)) - 1).copied()
+    }
+
+    /// Encode the durations for storage, as seconds separated by spaces
+    pub fn encode(&self) -> String {
+        self.samples
+            .iter()
+            .map(|d| d.as_secs_f64().to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Decode durations written by [`TestTiming::encode`], skipping any
+    /// that can't be parsed
+    pub fn decode(encoded: &str) -> Self {
+        TestTiming::new(
+            encoded
+                .split_whitespace()
+                .filter_map(|secs| secs.parse::<f64>().ok())
+                .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                .map(Duration::from_secs_f64)
+                .collect(),
+        )
+    }
+}
+
+/// How a test's recorded durations are turned into the one it is expected
+/// to take when partitioning tests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeEstimator {
+    /// The most recently recorded duration
+    Last,
+    /// The mean of the recorded durations
+    #[default]
+    Mean,
+    /// The 90th percentile of the recorded durations
+    P90,
+}
+
+impl TimeEstimator {
+    /// Estimate a test's duration, or `None` if none was recorded
+    pub fn estimate(&self, timing: &TestTiming) -> Option<Duration> {
+        match self {
+            TimeEstimator::Last => timing.last(),
+            TimeEstimator::Mean => timing.mean(),
+            TimeEstimator::P90 => timing.percentile(90.0),
+        }
+    }
+}
+
+impl fmt::Display for TimeEstimator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TimeEstimator::Last => "last",
+            TimeEstimator::Mean => "mean",
+            TimeEstimator::P90 => "p90",
+        })
+    }
+}
+
+impl FromStr for TimeEstimator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "last" => Ok(TimeEstimator::Last),
+            "mean" => Ok(TimeEstimator::Mean),
+            "p90" => Ok(TimeEstimator::P90),
+            other => Err(Error::Config(format!(
+                "Invalid time estimator: '{}' (expected 'last', 'mean' or 'p90')",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(values: &[u64]) -> TestTiming {
+        TestTiming::new(values.iter().map(|&s| Duration::from_secs(s)).collect())
+    }
+
+    #[test]
+    fn test_timing_statistics() {
+        let timing = secs(&[4, 1, 2, 3, 10]);
+        assert_eq!(timing.count(), 5);
+        assert_eq!(timing.last(), Some(Duration::from_secs(10)));
+        assert_eq!(timing.mean(), Some(Duration::from_secs(4)));
+        assert_eq!(timing.variance(), Some(10.0));
+        assert_eq!(timing.percentile(50.0), Some(Duration::from_secs(3)));
+        assert_eq!(timing.percentile(90.0), Some(Duration::from_secs(10)));
+        assert_eq!(timing.percentile(0.0), Some(Duration::from_secs(1)));
+
+        let empty = TestTiming::default();
+        assert_eq!(empty.mean(), None);
+        assert_eq!(empty.variance(), None);
+        assert_eq!(empty.percentile(90.0), None);
+        assert_eq!(TimeEstimator::Mean.estimate(&empty), None);
+    }
+
+    #[test]
+    fn test_timing_keeps_recent_history() {
+        let mut timing = TestTiming::default();
+        for i in 0..TIMING_HISTORY_SIZE as u64 + 5 {
+            timing.record(Duration::from_secs(i));
+        }
+        assert_eq!(timing.count(), TIMING_HISTORY_SIZE);
+        assert_eq!(timing.samples()[0], Duration::from_secs(5));
+
+        let timing = TestTiming::new(vec![Duration::from_millis(1500), Duration::from_secs(2)]);
+        assert_eq!(timing.encode(), "1.5 2");
+        assert_eq!(TestTiming::decode(&timing.encode()), timing);
+        assert_eq!(TestTiming::decode("1.5 bogus -1 2"), timing);
+    }
+
+    #[test]
+    fn test_time_estimator() {
+        let timing = secs(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 0]);
+        assert_eq!(TimeEstimator::Last.estimate(&timing), Some(Duration::ZERO));
+        assert_eq!(
+            TimeEstimator::Mean.estimate(&timing),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            TimeEstimator::P90.estimate(&timing),
+            Some(Duration::from_secs(9))
+        );
+
+        for estimator in [TimeEstimator::Last, TimeEstimator::Mean, TimeEstimator::P90] {
+            assert_eq!(
+                estimator.to_string().parse::<TimeEstimator>().unwrap(),
+                estimator
+            );
+        }
+        assert!("median".parse::<TimeEstimator>().is_err());
+    }
+}
@@ -488,30 +488,13 @@ pub fn parse_stream<R: Read>(reader: R, run_id: String) -> Result<TestRun> {
 /// Filter a raw subunit stream to only include failing tests
 ///
 /// This preserves the complete subunit events including file attachments (log, traceback)
-/// for tests that have failing status.
+/// for tests that have failing status. A test that ran more than once, such as one
+/// retried after failing, is judged by its last result, as in [`parse_stream`].
 pub fn filter_failing_tests<R: Read, W: Write>(mut reader: R, mut writer: W) -> Result<()> {
-    use std::collections::HashSet;
-
     // First pass: identify which tests are failures
     let mut buffer = Vec::new();
     reader.read_to_end(&mut buffer)?;
-
-    let mut failing_tests = HashSet::new();
-
-    for item in iter_stream(&buffer[..]) {
-        if let Ok(ScannedItem::Event(event)) = item {
-            if let Some(ref test_id) = event.test_id {
-                let is_failure = matches!(
-                    event.status,
-                    SubunitTestStatus::Failed | SubunitTestStatus::UnexpectedSuccess
-                );
-
-                if is_failure {
-                    failing_tests.insert(test_id.clone());
-                }
-            }
-        }
-    }
+    let failing_tests = failing_test_ids(&buffer);
 
     // Second pass: write events only for failing tests
     for item in iter_stream(&buffer[..]) {
@@ -540,6 +523,105 @@ pub fn filter_failing_tests<R: Read, W: Write>(mut reader: R, mut writer: W) ->
     Ok(())
 }
 
+/// Split the events of the failing tests in a raw subunit stream by test
+///
+/// Each failing test gets the same events, attachments included, that
+/// [`filter_failing_tests`] would write for it.
+pub fn failing_test_packets(data: &[u8]) -> Result<std::collections::HashMap<String, Vec<u8>>> {
+    use std::collections::HashMap;
+
+    let failing_tests = failing_test_ids(data);
+
+    let mut packets: HashMap<String, Vec<u8>> = HashMap::new();
+    for item in iter_stream(data) {
+        if let Ok(ScannedItem::Event(event)) = item {
+            if let Some(ref test_id) = event.test_id {
+                if failing_tests.contains(test_id) {
+                    let writer = packets.entry(test_id.clone()).or_default();
+                    event
+                        .serialize(writer)
+                        .map_err(|e| Error::Subunit(format!("Failed to serialize event: {}", e)))?;
+                }
+            }
+        }
+    }
+
+    Ok(packets)
+}
+
+/// The tests whose last result in a raw subunit stream is a failure
+fn failing_test_ids(data: &[u8]) -> std::collections::HashSet<String> {
+    use std::collections::HashMap;
+
+    let mut last_failed: HashMap<String, bool> = HashMap::new();
+
+    for item in iter_stream(data) {
+        if let Ok(ScannedItem::Event(event)) = item {
+            if let Some(ref test_id) = event.test_id {
+                if convert_subunit_status(event.status).is_none() {
+                    continue;
+                }
+                let is_failure = matches!(
+                    event.status,
+                    SubunitTestStatus::Failed | SubunitTestStatus::UnexpectedSuccess
+                );
+                last_failed.insert(test_id.clone(), is_failure);
+            }
+        }
+    }
+    last_failed
+        .into_iter()
+        .filter_map(|(test_id, failed)| failed.then_some(test_id))
+        .collect()
+}
+
+/// Copy a raw subunit stream, adding a tag to every test event
+///
+/// This is used to multiplex the output of several parallel workers into a
+/// single stored run: each worker's events are tagged `worker-N`, matching the
+/// Python implementation. Non-subunit bytes, and packets that fail to parse,
+/// are copied through unchanged so that the stored stream still carries any
+/// interleaved output.
+pub fn tag_stream<R: Read, W: Write>(reader: R, mut writer: W, tag: &str) -> Result<()> {
+    let mut consecutive_errors = 0;
+    for item in iter_stream(reader) {
+        if item.is_ok() {
+            consecutive_errors = 0;
+        }
+        match item {
+            Ok(ScannedItem::Event(mut event)) => {
+                if event.test_id.is_some() {
+                    let tags = event.tags.get_or_insert_with(Vec::new);
+                    if !tags.iter().any(|t| t == tag) {
+                        tags.push(tag.to_string());
+                    }
+                }
+                event
+                    .serialize(&mut writer)
+                    .map_err(|e| Error::Subunit(format!("Failed to serialize event: {}", e)))?;
+            }
+            Ok(ScannedItem::Bytes(bytes)) => {
+                writer.write_all(&bytes)?;
+            }
+            Ok(ScannedItem::Unknown(data, _err)) => {
+                writer.write_all(&data)?;
+            }
+            Err(_e) => {
+                // Nothing is left to copy: a worker that died mid-packet
+                // leaves a truncated stream, which is stored up to that point
+                consecutive_errors += 1;
+                if consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                    break;
+                }
+            }
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
 /// Write a TestRun as a subunit stream
 ///
 /// Returns an error if timestamp conversion fails or if the event is too large to serialize.
@@ -611,6 +693,185 @@ pub fn write_stream<W: Write>(test_run: &TestRun, mut writer: W) -> Result<()> {
     Ok(())
 }
 
+/// Write a file attachment for a test, ahead of the event with its result
+pub fn write_attachment<W: Write>(
+    mut writer: W,
+    test_id: &TestId,
+    name: &str,
+    content: &[u8],
+) -> Result<()> {
+    Event::new(SubunitTestStatus::Undefined)
+        .test_id(test_id.as_str())
+        .mime_type("text/plain")
+        .file_content(name, content)
+        .build()
+        .serialize(&mut writer)
+        .map_err(|e| {
+            Error::Subunit(format!(
+                "Failed to write attachment for {}: {}",
+                test_id.as_str(),
+                e
+            ))
+        })?;
+    Ok(())
+}
+
+/// Subunit v2 packet signature byte
+const PACKET_SIGNATURE: u8 = 0xb3;
+
+/// Flag bit subunit v2 leaves unused, so a real packet never sets it
+const PACKET_RESERVED_FLAG: u8 = 0x08;
+
+/// Largest packet subunit v2 allows
+const PACKET_MAX_LENGTH: usize = 4 * 1024 * 1024;
+
+/// A problem found in a subunit stream by [`validate_stream`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamProblem {
+    /// Byte offset of the packet the problem was found in
+    pub offset: u64,
+    /// Whether the stream simply ends part way through this packet, as it does
+    /// when a writer was interrupted; everything before `offset` is intact
+    pub truncated: bool,
+    /// Description of the problem
+    pub message: String,
+}
+
+/// Decode a subunit v2 variable length number, returning (value, bytes used)
+fn decode_varint(data: &[u8]) -> Option<(usize, usize)> {
+    let first = *data.first()?;
+    let size = 1 + (first >> 6) as usize;
+    if data.len() < size {
+        return None;
+    }
+    let value = data[1..size]
+        .iter()
+        .fold((first & 0x3f) as usize, |acc, b| (acc << 8) | *b as usize);
+    Some((value, size))
+}
+
+/// What a signature byte in a stream turned out to start
+enum Candidate {
+    /// A packet of the given length whose CRC32 checks out
+    Packet(usize),
+    /// A plausible packet header that the data ends part way through
+    Torn(String),
+    /// A plausible packet whose CRC32 doesn't check out
+    Corrupt(usize, String),
+    /// Not a packet, just a signature byte in ordinary output
+    Noise,
+}
+
+/// Look at the candidate packet starting at `pos`, which holds a signature byte
+fn inspect_candidate(data: &[u8], pos: usize) -> Candidate {
+    let header = &data[pos + 1..];
+    // Anything can follow the signature byte in ordinary output, so even a
+    // torn header must show the version and no reserved flag to count
+    match header {
+        [] => return Candidate::Noise,
+        [version, ..] if version >> 4 != 2 => return Candidate::Noise,
+        [_, flags, ..] if flags & PACKET_RESERVED_FLAG != 0 => return Candidate::Noise,
+        [_] | [_, _] => return Candidate::Torn("Truncated packet header".to_string()),
+        _ => {}
+    }
+
+    let Some((length, length_size)) = decode_varint(&header[2..]) else {
+        return Candidate::Torn("Truncated packet length".to_string());
+    };
+    if length < 3 + length_size + 4 || length > PACKET_MAX_LENGTH {
+        return Candidate::Noise;
+    }
+    if pos + length > data.len() {
+        return Candidate::Torn(format!(
+            "Packet of {} bytes truncated after {} bytes",
+            length,
+            data.len() - pos
+        ));
+    }
+
+    let body = &data[pos..pos + length - 4];
+    let stored = &data[pos + length - 4..pos + length];
+    let expected = u32::from_be_bytes([stored[0], stored[1], stored[2], stored[3]]);
+    let actual = crc32fast::hash(body);
+    if actual == expected {
+        Candidate::Packet(length)
+    } else {
+        Candidate::Corrupt(
+            length,
+            format!(
+                "CRC32 mismatch in packet of {} bytes: stored {:08x}, computed {:08x}",
+                length, expected, actual
+            ),
+        )
+    }
+}
+
+/// Check the packet framing of a subunit v2 stream
+///
+/// Walks every packet, checking its version, flags, length and CRC32.
+/// Signature bytes that don't start a valid packet are ordinary non-subunit
+/// output, such as the second byte of UTF-8 "ó", and are not problems. Two
+/// kinds of problem are reported, each at the offset of its packet:
+///
+/// - a corrupt packet: a plausible header whose CRC32 doesn't check out and
+///   whose length ends at the end of the stream or at a valid packet, as when
+///   a packet is damaged in place or overwritten by another writer
+/// - a torn final packet: a plausible header that the stream ends part way
+///   through, with no valid packet after it
+pub fn validate_stream(data: &[u8]) -> Vec<StreamProblem> {
+    let next_packet = |from: usize| {
+        (from..data.len()).find(|&pos| {
+            data[pos] == PACKET_SIGNATURE
+                && matches!(inspect_candidate(data, pos), Candidate::Packet(_))
+        })
+    };
+
+    let ends_at_packet = |end: usize| {
+        end == data.len()
+            || (data[end] == PACKET_SIGNATURE
+                && matches!(inspect_candidate(data, end), Candidate::Packet(_)))
+    };
+
+    let mut problems = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        if data[pos] != PACKET_SIGNATURE {
+            pos += 1;
+            continue;
+        }
+
+        match inspect_candidate(data, pos) {
+            Candidate::Packet(length) => pos += length,
+            Candidate::Noise => pos += 1,
+            // Output can look like a header too, but then its length is
+            // unlikely to land exactly on the end of the stream or a packet
+            Candidate::Corrupt(length, message) if ends_at_packet(pos + length) => {
+                problems.push(StreamProblem {
+                    offset: pos as u64,
+                    truncated: false,
+                    message,
+                });
+                pos += length;
+            }
+            Candidate::Corrupt(..) => pos += 1,
+            Candidate::Torn(message) => match next_packet(pos + 1) {
+                // Valid packets further on show this was output, not a packet
+                Some(next) => pos = next,
+                None => {
+                    problems.push(StreamProblem {
+                        offset: pos as u64,
+                        truncated: true,
+                        message,
+                    });
+                    break;
+                }
+            },
+        }
+    }
+
+    problems
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -827,4 +1088,139 @@ mod tests {
         assert!(parsed.results.contains_key(&TestId::new("test_uxsuccess")));
         assert!(!parsed.results.contains_key(&TestId::new("test_pass")));
     }
+
+    #[test]
+    fn test_filter_failing_tests_uses_last_result() {
+        let mut first = TestRun::new("0".to_string());
+        first.add_result(TestResult::failure("test_flaky", "Failed"));
+        first.add_result(TestResult::success("test_broken"));
+        let mut second = TestRun::new("0".to_string());
+        second.add_result(TestResult::success("test_flaky"));
+        second.add_result(TestResult::failure("test_broken", "Failed"));
+
+        let mut stream = Vec::new();
+        write_stream(&first, &mut stream).unwrap();
+        write_stream(&second, &mut stream).unwrap();
+
+        let mut filtered_stream = Vec::new();
+        filter_failing_tests(&stream[..], &mut filtered_stream).unwrap();
+
+        let parsed = parse_stream(&filtered_stream[..], "filtered".to_string()).unwrap();
+        assert_eq!(parsed.total_tests(), 1);
+        assert!(parsed.results.contains_key(&TestId::new("test_broken")));
+    }
+
+    #[test]
+    fn test_tag_stream() {
+        let mut test_run = TestRun::new("0".to_string());
+        test_run.timestamp = chrono::DateTime::from_timestamp(1000000000, 0).unwrap();
+        test_run.add_result(TestResult::success("test1").with_duration(Duration::from_secs(1)));
+        test_run.add_result(TestResult::failure("test2", "Failed").with_tag("worker-1"));
+
+        let mut stream = Vec::new();
+        write_stream(&test_run, &mut stream).unwrap();
+
+        let mut tagged = Vec::new();
+        tag_stream(&stream[..], &mut tagged, "worker-1").unwrap();
+
+        let parsed = parse_stream(&tagged[..], "tagged".to_string()).unwrap();
+        assert_eq!(parsed.total_tests(), 2);
+        for result in parsed.results.values() {
+            // Existing tags are not duplicated
+            assert_eq!(result.tags, vec!["worker-1".to_string()]);
+        }
+        let test1 = parsed.results.get(&TestId::new("test1")).unwrap();
+        assert_eq!(test1.duration, Some(Duration::from_secs(1)));
+
+        // A packet that fails to parse is passed through as it was
+        let mut damaged = TestRun::new("1".to_string());
+        damaged.add_result(TestResult::success("test3"));
+        let mut packet = Vec::new();
+        write_stream(&damaged, &mut packet).unwrap();
+        *packet.last_mut().unwrap() ^= 0xff;
+        stream.extend_from_slice(&packet);
+
+        let mut tagged = Vec::new();
+        tag_stream(&stream[..], &mut tagged, "worker-1").unwrap();
+        assert!(tagged.ends_with(&packet));
+        let parsed = parse_stream(&tagged[..], "tagged".to_string()).unwrap();
+        assert_eq!(parsed.total_tests(), 2);
+    }
+
+    #[test]
+    fn test_write_attachment() {
+        let test_id = TestId::new("test1");
+        let mut stream = Vec::new();
+        write_attachment(&mut stream, &test_id, "stderr", b"Segmentation fault\n").unwrap();
+        let mut test_run = TestRun::new("0".to_string());
+        test_run.add_result(TestResult::error("test1", "crashed").with_details("crashed"));
+        write_stream(&test_run, &mut stream).unwrap();
+
+        assert!(validate_stream(&stream).is_empty());
+        let parsed = parse_stream(&stream[..], "0".to_string()).unwrap();
+        let test1 = parsed.results.get(&test_id).unwrap();
+        assert_eq!(test1.status, TestStatus::Failure);
+        assert_eq!(test1.details.as_deref(), Some("crashed"));
+    }
+
+    #[test]
+    fn test_validate_stream() {
+        let mut test_run = TestRun::new("0".to_string());
+        test_run.add_result(TestResult::success("test1"));
+        test_run.add_result(TestResult::failure("test2", "Failed"));
+
+        let mut stream = b"plain output\n".to_vec();
+        write_stream(&test_run, &mut stream).unwrap();
+        assert!(validate_stream(&stream).is_empty());
+
+        // A torn write leaves a truncated final packet
+        let torn = &stream[..stream.len() - 3];
+        let problems = validate_stream(torn);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].truncated);
+        assert!(problems[0].offset >= 13);
+
+        // A packet whose CRC32 doesn't check out is reported where it starts
+        let mut damaged = stream.clone();
+        damaged[13 + 6] ^= 0xff;
+        let problems = validate_stream(&damaged);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].offset, 13);
+        assert!(!problems[0].truncated);
+        assert!(problems[0].message.contains("CRC32 mismatch"));
+
+        // As is the final packet, alongside the first
+        let last = stream.len() - 2;
+        damaged[last] ^= 0xff;
+        let problems = validate_stream(&damaged);
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].offset, 13);
+        assert!(problems[1].offset > 13);
+
+        // A plausible header is only torn if nothing valid follows it
+        let mut fake_header = b"\xb3\x20\x00\x50".to_vec();
+        fake_header.extend_from_slice(&stream);
+        assert!(validate_stream(&fake_header).is_empty());
+    }
+
+    #[test]
+    fn test_validate_stream_with_non_ascii_output() {
+        let mut test_run = TestRun::new("0".to_string());
+        test_run.add_result(TestResult::success("test1"));
+        let mut packets = Vec::new();
+        write_stream(&test_run, &mut packets).unwrap();
+
+        // "г", "ó" and "³" all contain the signature byte 0xb3
+        for output in ["г", "ó", "³", "ó\n", "x³ = 27\n"] {
+            let mut stream = output.as_bytes().to_vec();
+            stream.extend_from_slice(&packets);
+            stream.extend_from_slice(output.as_bytes());
+            stream.extend_from_slice(&packets);
+            assert!(validate_stream(&stream).is_empty(), "{:?}", output);
+
+            // At the very end of the stream it is not a torn packet either
+            stream.extend_from_slice(output.as_bytes());
+            assert!(validate_stream(&stream).is_empty(), "{:?}", output);
+        }
+    }
 }
@@ -3,9 +3,18 @@
 //! This module provides common utilities for spawning test processes and
 //! managing their stdout/stderr streams with tee and parsing capabilities.
 
+use crate::error::{Error, Result};
+use crate::repository::{TestId, TestResult};
+use crate::subunit_stream::ProgressStatus;
 use indicatif::ProgressBar;
+use std::collections::HashMap;
 use std::io::{Read, Write};
-use std::sync::mpsc::{Receiver, SyncSender};
+use std::process::{Command, ExitStatus};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::mpsc::{Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 /// A writer that tees output to both a file and a channel
 pub struct TeeWriter<W: Write> {
@@ -22,6 +31,11 @@ impl<W: Write> TeeWriter<W> {
     pub fn new(writer: W, tx: SyncSender<Vec<u8>>) -> Self {
         TeeWriter { writer, tx }
     }
+
+    /// Returns the underlying writer, dropping the channel sender.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
 }
 
 impl<W: Write> Write for TeeWriter<W> {
@@ -82,14 +96,21 @@ impl Read for ChannelReader {
     }
 }
 
+/// Number of bytes kept from the end of a test process's stderr
+pub const STDERR_TAIL_SIZE: usize = 4096;
+
 /// Spawn a thread to forward stderr to the terminal via progress bar suspension
+///
+/// The thread hands back the last [`STDERR_TAIL_SIZE`] bytes it forwarded, to
+/// show why a test process died.
 pub fn spawn_stderr_forwarder<R: Read + Send + 'static>(
     mut stderr: R,
     progress_bar: ProgressBar,
-) -> std::thread::JoinHandle<std::io::Result<()>> {
-    std::thread::spawn(move || -> std::io::Result<()> {
+) -> std::thread::JoinHandle<std::io::Result<Vec<u8>>> {
+    std::thread::spawn(move || -> std::io::Result<Vec<u8>> {
         use std::io::Write;
         let mut buffer = [0u8; 8192];
+        let mut tail = Vec::new();
         loop {
             match stderr.read(&mut buffer) {
                 Ok(0) => break, // EOF
@@ -99,28 +120,623 @@ pub fn spawn_stderr_forwarder<R: Read + Send + 'static>(
                         let _ = std::io::stderr().write_all(&buffer[..n]);
                         let _ = std::io::stderr().flush();
                     });
+                    tail.extend_from_slice(&buffer[..n]);
+                    if tail.len() > STDERR_TAIL_SIZE {
+                        tail.drain(..tail.len() - STDERR_TAIL_SIZE);
+                    }
                 }
                 Err(e) => return Err(e),
             }
         }
-        Ok(())
+        Ok(tail)
     })
 }
 
 /// Spawn a thread to tee stdout to both storage and parsing
+///
+/// The thread hands back the storage writer once stdout is exhausted, so
+/// that more can be written to it.
 pub fn spawn_stdout_tee<R: Read + Send + 'static, W: Write + Send + 'static>(
     mut stdout: R,
     writer: W,
     tx: SyncSender<Vec<u8>>,
-) -> std::thread::JoinHandle<std::io::Result<()>> {
-    std::thread::spawn(move || -> std::io::Result<()> {
+) -> std::thread::JoinHandle<std::io::Result<W>> {
+    std::thread::spawn(move || -> std::io::Result<W> {
         let mut tee = TeeWriter::new(writer, tx);
         std::io::copy(&mut stdout, &mut tee)?;
         tee.flush()?;
-        Ok(())
+        Ok(tee.into_inner())
     })
 }
 
+/// Exit code of a finished test process, or the negated signal number if it
+/// was killed by a signal
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return -signal;
+        }
+    }
+    status.code().unwrap_or(-1)
+}
+
+/// Start a test process in a process group of its own
+///
+/// Stopping the group then also stops anything the process started, such as
+/// the test runner behind a `sh -c` wrapper. The group no longer receives the
+/// terminal's Ctrl-C, so processes started this way must be registered with a
+/// [`StopHandle`] that an [`InterruptHandler`] forwards signals through.
+pub fn set_process_group(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// Stops running test processes early, for example once too many tests have
+/// failed or when testr is interrupted
+///
+/// Clones share their state, so that the threads watching the processes'
+/// output can stop all of them.
+#[derive(Clone, Default)]
+pub struct StopHandle {
+    state: Arc<StopState>,
+}
+
+#[derive(Default)]
+struct StopState {
+    stopped: AtomicBool,
+    /// The signal that interrupted testr, or 0
+    interrupted: AtomicI32,
+    pids: Mutex<Vec<u32>>,
+}
+
+impl StopHandle {
+    /// Creates a handle with no processes to stop.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a process started with [`set_process_group`]
+    ///
+    /// A process registered once the handle has been stopped is terminated
+    /// straight away.
+    pub fn add_process(&self, pid: u32) {
+        let mut pids = self.state.pids.lock().unwrap_or_else(|e| e.into_inner());
+        if self.is_stopped() {
+            match self.interrupted() {
+                #[cfg(unix)]
+                Some(signal) => signal_process_group(pid, signal),
+                _ => terminate_process_group(pid),
+            }
+        }
+        pids.push(pid);
+    }
+
+    /// Forget a process once it has been waited for
+    ///
+    /// Its pid may then be reused by an unrelated process, which must not be
+    /// signalled when the handle is stopped.
+    pub fn remove_process(&self, pid: u32) {
+        let mut pids = self.state.pids.lock().unwrap_or_else(|e| e.into_inner());
+        pids.retain(|p| *p != pid);
+    }
+
+    /// Terminate the process groups of every registered process
+    ///
+    /// Returns false if the handle had already been stopped.
+    pub fn stop(&self) -> bool {
+        let pids = self.state.pids.lock().unwrap_or_else(|e| e.into_inner());
+        if self.state.stopped.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        for pid in pids.iter() {
+            terminate_process_group(*pid);
+        }
+        true
+    }
+
+    /// Stop because testr received `signal`, forwarding it to the process
+    /// groups of every registered process rather than terminating them
+    #[cfg(unix)]
+    pub fn interrupt(&self, signal: i32) {
+        let pids = self.state.pids.lock().unwrap_or_else(|e| e.into_inner());
+        self.state.interrupted.store(signal, Ordering::SeqCst);
+        self.state.stopped.store(true, Ordering::SeqCst);
+        for pid in pids.iter() {
+            signal_process_group(*pid, signal);
+        }
+    }
+
+    /// Kill the process groups of every registered process outright
+    #[cfg(unix)]
+    pub fn kill(&self) {
+        let pids = self.state.pids.lock().unwrap_or_else(|e| e.into_inner());
+        for pid in pids.iter() {
+            signal_process_group(*pid, libc::SIGKILL);
+        }
+    }
+
+    /// Whether [`stop`](Self::stop) has been called
+    pub fn is_stopped(&self) -> bool {
+        self.state.stopped.load(Ordering::SeqCst)
+    }
+
+    /// The signal that interrupted testr, if it was interrupted
+    pub fn interrupted(&self) -> Option<i32> {
+        match self.state.interrupted.load(Ordering::SeqCst) {
+            0 => None,
+            signal => Some(signal),
+        }
+    }
+}
+
+/// Send a signal to every process in the group led by `pid`
+#[cfg(unix)]
+fn signal_process_group(pid: u32, signal: i32) {
+    // Safety: kill has no memory safety requirements; a group that has
+    // already exited just makes it fail with ESRCH
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), signal);
+    }
+}
+
+/// Ask every process in the group led by `pid` to terminate
+fn terminate_process_group(pid: u32) {
+    #[cfg(unix)]
+    signal_process_group(pid, libc::SIGTERM);
+    #[cfg(not(unix))]
+    {
+        let _ = Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid.to_string()])
+            .status();
+    }
+}
+
+/// How long test processes get to exit once testr has been interrupted
+/// before they are killed
+pub const INTERRUPT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// How often an [`InterruptHandler`] checks whether a signal has arrived
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The signals an [`InterruptHandler`] handles
+#[cfg(unix)]
+const INTERRUPT_SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+/// The first SIGINT or SIGTERM received while interrupt handlers are
+/// installed, or 0
+#[cfg(unix)]
+static RECEIVED_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+/// Number of installed interrupt handlers, and the signal dispositions they
+/// replaced
+#[cfg(unix)]
+static INSTALLED_HANDLERS: Mutex<(usize, Vec<(libc::c_int, libc::sighandler_t)>)> =
+    Mutex::new((0, Vec::new()));
+
+#[cfg(unix)]
+extern "C" fn record_signal(signal: libc::c_int) {
+    // A second signal means the user doesn't want to wait for the tests
+    if RECEIVED_SIGNAL.swap(signal, Ordering::SeqCst) != 0 {
+        // Safety: signal and raise are async-signal-safe
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+}
+
+/// Handles SIGINT and SIGTERM while test processes run
+///
+/// Test processes run in process groups of their own, so the terminal's
+/// Ctrl-C doesn't reach them. Instead the first signal testr receives stops
+/// the run through a [`StopHandle`], which forwards the signal to every
+/// registered process group; groups still running after
+/// [`INTERRUPT_GRACE_PERIOD`] are killed. Whatever the tests reported up to
+/// then can still be stored. A second signal terminates testr straight away.
+///
+/// Signals that testr was started ignoring, as in background jobs, stay
+/// ignored. The previous handling is restored once every handler is dropped.
+pub struct InterruptHandler {
+    done: Option<Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl InterruptHandler {
+    /// Start handling signals for the processes registered with `stop`.
+    ///
+    /// # Arguments
+    /// * `stop` - Handle through which the run is stopped when a signal arrives
+    pub fn install(stop: &StopHandle) -> Self {
+        #[cfg(unix)]
+        {
+            let mut installed = INSTALLED_HANDLERS.lock().unwrap_or_else(|e| e.into_inner());
+            if installed.0 == 0 {
+                RECEIVED_SIGNAL.store(0, Ordering::SeqCst);
+                for signal in INTERRUPT_SIGNALS {
+                    // Safety: record_signal only touches an atomic and calls
+                    // async-signal-safe functions
+                    let handler = record_signal as extern "C" fn(libc::c_int);
+                    let previous = unsafe { libc::signal(signal, handler as libc::sighandler_t) };
+                    if previous == libc::SIG_IGN {
+                        unsafe { libc::signal(signal, libc::SIG_IGN) };
+                    }
+                    installed.1.push((signal, previous));
+                }
+            }
+            installed.0 += 1;
+
+            let (done, rx) = std::sync::mpsc::channel::<()>();
+            let stop = stop.clone();
+            let thread = std::thread::spawn(move || {
+                use std::sync::mpsc::RecvTimeoutError;
+
+                let signal = loop {
+                    match rx.recv_timeout(INTERRUPT_POLL_INTERVAL) {
+                        Err(RecvTimeoutError::Timeout) => {}
+                        _ => return,
+                    }
+                    let signal = RECEIVED_SIGNAL.load(Ordering::SeqCst);
+                    if signal != 0 {
+                        break signal;
+                    }
+                };
+                stop.interrupt(signal);
+                if let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(INTERRUPT_GRACE_PERIOD) {
+                    stop.kill();
+                }
+            });
+            InterruptHandler {
+                done: Some(done),
+                thread: Some(thread),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = stop;
+            InterruptHandler {
+                done: None,
+                thread: None,
+            }
+        }
+    }
+}
+
+impl Drop for InterruptHandler {
+    fn drop(&mut self) {
+        drop(self.done.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        #[cfg(unix)]
+        {
+            let mut installed = INSTALLED_HANDLERS.lock().unwrap_or_else(|e| e.into_inner());
+            installed.0 -= 1;
+            if installed.0 == 0 {
+                for (signal, previous) in installed.1.drain(..) {
+                    // Safety: restores the disposition found at install time
+                    unsafe { libc::signal(signal, previous) };
+                }
+            }
+        }
+    }
+}
+
+/// Shortest limit a [`TestTimeout::Scaled`] timeout gives any test, so that
+/// fast tests aren't killed by ordinary jitter
+pub const MIN_SCALED_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the watchdog checks for tests that have run for too long
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Parse a number of seconds, optionally suffixed with `s`
+pub fn parse_seconds(s: &str) -> Result<Duration> {
+    s.strip_suffix('s')
+        .unwrap_or(s)
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs > 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| Error::Config(format!("Invalid number of seconds '{}'", s)))
+}
+
+/// How long a single test may run before it is taken to have hung
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestTimeout {
+    /// The same limit for every test
+    Fixed(Duration),
+    /// A multiple of the test's recorded duration, but at least
+    /// [`MIN_SCALED_TIMEOUT`]; tests without a recorded duration have no limit
+    Scaled(f64),
+}
+
+impl TestTimeout {
+    /// The limit for a test, given its recorded duration
+    pub fn limit(&self, recorded: Option<Duration>) -> Option<Duration> {
+        match self {
+            TestTimeout::Fixed(limit) => Some(*limit),
+            TestTimeout::Scaled(factor) => {
+                recorded.map(|recorded| recorded.mul_f64(*factor).max(MIN_SCALED_TIMEOUT))
+            }
+        }
+    }
+}
+
+impl FromStr for TestTimeout {
+    type Err = Error;
+
+    /// Parses a number of seconds such as `30` or `2.5s`, or a multiple of
+    /// the recorded duration such as `5x`
+    fn from_str(s: &str) -> Result<Self> {
+        match s.strip_suffix('x') {
+            Some(factor) => factor
+                .parse::<f64>()
+                .ok()
+                .filter(|factor| factor.is_finite() && *factor > 0.0)
+                .map(TestTimeout::Scaled)
+                .ok_or_else(|| Error::Config(format!("Invalid test timeout '{}'", s))),
+            None => parse_seconds(s).map(TestTimeout::Fixed),
+        }
+    }
+}
+
+/// A test whose process was terminated because it ran for too long
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedOutTest {
+    /// The test
+    pub test_id: TestId,
+    /// How long it had been running
+    pub elapsed: Duration,
+    /// The limit it went over, its own or the whole run's
+    pub limit: Duration,
+}
+
+impl TimedOutTest {
+    /// How long the test ran for and what it was allowed, in words
+    pub fn timeout(&self) -> String {
+        format!(
+            "ran for {:.1}s, over the limit of {:.1}s",
+            self.elapsed.as_secs_f64(),
+            self.limit.as_secs_f64()
+        )
+    }
+
+    /// The error recorded for the test
+    pub fn to_result(&self) -> TestResult {
+        let message = format!(
+            "timed out after {:.1}s (limit {:.1}s)",
+            self.elapsed.as_secs_f64(),
+            self.limit.as_secs_f64()
+        );
+        TestResult::error(self.test_id.clone(), message.clone()).with_details(message)
+    }
+}
+
+/// A test that was running when its test process exited by itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrashedTest {
+    /// The test
+    pub test_id: TestId,
+    /// How the process exited, as returned by [`exit_code`]
+    pub exit_code: i32,
+}
+
+impl CrashedTest {
+    /// How the process exited, in words
+    pub fn exit_status(&self) -> String {
+        if self.exit_code < 0 {
+            format!("killed by signal {}", -self.exit_code)
+        } else {
+            format!("exit status {}", self.exit_code)
+        }
+    }
+
+    /// The error recorded for the test
+    pub fn to_result(&self) -> TestResult {
+        let message = format!(
+            "test process exited before the test finished ({})",
+            self.exit_status()
+        );
+        TestResult::error(self.test_id.clone(), message.clone()).with_details(message)
+    }
+}
+
+/// Terminates test processes that have run for too long
+///
+/// The threads parsing each process's output report which test it is
+/// running. A background thread terminates the process group of any process
+/// whose current test goes over its [`TestTimeout`], and stops every process
+/// through the run's [`StopHandle`] once the whole run goes over its limit.
+/// Clones share their state; the background thread exits once every clone
+/// has been dropped.
+#[derive(Clone)]
+pub struct Watchdog {
+    state: Arc<WatchdogState>,
+}
+
+struct WatchdogState {
+    timeout: Option<TestTimeout>,
+    times: HashMap<TestId, Duration>,
+    run_timeout: Option<Duration>,
+    run_deadline: Option<Instant>,
+    stop: StopHandle,
+    run_timed_out: AtomicBool,
+    processes: Mutex<HashMap<usize, WatchedProcess>>,
+}
+
+/// The latest process started for a worker, the tests it has started but not
+/// finished, and what it was killed for
+#[derive(Default)]
+struct WatchedProcess {
+    pid: Option<u32>,
+    running: Vec<(TestId, Instant)>,
+    timed_out: Vec<TimedOutTest>,
+}
+
+impl Watchdog {
+    /// Start watching a run.
+    ///
+    /// # Arguments
+    /// * `timeout` - Limit for each test, if any
+    /// * `times` - Recorded durations of the tests, for a scaled timeout
+    /// * `run_timeout` - Limit for the whole run, if any
+    /// * `stop` - Handle that stops every process in the run
+    pub fn start(
+        timeout: Option<TestTimeout>,
+        times: HashMap<TestId, Duration>,
+        run_timeout: Option<Duration>,
+        stop: StopHandle,
+    ) -> Self {
+        let watchdog = Watchdog {
+            state: Arc::new(WatchdogState {
+                timeout,
+                times,
+                run_timeout,
+                run_deadline: run_timeout.map(|limit| Instant::now() + limit),
+                stop,
+                run_timed_out: AtomicBool::new(false),
+                processes: Mutex::new(HashMap::new()),
+            }),
+        };
+        if timeout.is_some() || run_timeout.is_some() {
+            let weak = Arc::downgrade(&watchdog.state);
+            std::thread::spawn(move || {
+                while let Some(state) = weak.upgrade() {
+                    state.check();
+                    drop(state);
+                    std::thread::sleep(WATCHDOG_INTERVAL);
+                }
+            });
+        }
+        watchdog
+    }
+
+    /// Watch a process started with [`set_process_group`] for a worker,
+    /// replacing any earlier process of that worker
+    ///
+    /// The process is also added to the run's [`StopHandle`].
+    pub fn watch(&self, worker: usize, pid: u32) {
+        let mut processes = self.state.processes();
+        let process = processes.entry(worker).or_default();
+        process.pid = Some(pid);
+        process.running.clear();
+        self.state.stop.add_process(pid);
+    }
+
+    /// Stop watching a worker's process once it has been waited for
+    ///
+    /// The process is also removed from the run's [`StopHandle`]. What it
+    /// was running and timed out on is kept for the `take_` methods.
+    pub fn unwatch(&self, worker: usize) {
+        let mut processes = self.state.processes();
+        if let Some(pid) = processes.get_mut(&worker).and_then(|p| p.pid.take()) {
+            self.state.stop.remove_process(pid);
+        }
+    }
+
+    /// Note a progress event from a worker's output
+    pub fn progress(&self, worker: usize, test_id: &str, status: ProgressStatus) {
+        let mut processes = self.state.processes();
+        let process = processes.entry(worker).or_default();
+        process.running.retain(|(id, _)| id.as_str() != test_id);
+        if status == ProgressStatus::InProgress {
+            process.running.push((TestId::new(test_id), Instant::now()));
+        }
+    }
+
+    /// Take the tests that a worker's processes were terminated for
+    pub fn take_timed_out(&self, worker: usize) -> Vec<TimedOutTest> {
+        let mut processes = self.state.processes();
+        processes
+            .get_mut(&worker)
+            .map(|process| std::mem::take(&mut process.timed_out))
+            .unwrap_or_default()
+    }
+
+    /// Take the tests that a worker's latest process started but never
+    /// finished, other than those it was terminated for
+    ///
+    /// Once the run has been stopped, tests cut short by the stop are not
+    /// included: they didn't get the chance to finish.
+    pub fn take_unfinished(&self, worker: usize) -> Vec<TestId> {
+        let mut processes = self.state.processes();
+        let Some(process) = processes.get_mut(&worker) else {
+            return Vec::new();
+        };
+        let running = std::mem::take(&mut process.running);
+        if self.state.stop.is_stopped() {
+            return Vec::new();
+        }
+        running.into_iter().map(|(test_id, _)| test_id).collect()
+    }
+
+    /// Whether the whole run went over its limit
+    pub fn run_timed_out(&self) -> bool {
+        self.state.run_timed_out.load(Ordering::SeqCst)
+    }
+
+    /// The handle that stops every process in the run
+    pub fn stop_handle(&self) -> &StopHandle {
+        &self.state.stop
+    }
+}
+
+impl WatchdogState {
+    fn processes(&self) -> std::sync::MutexGuard<'_, HashMap<usize, WatchedProcess>> {
+        self.processes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Terminate the processes whose test, or the run, is over its limit
+    fn check(&self) {
+        let run_expired = self
+            .run_deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        let mut processes = self.processes();
+        for process in processes.values_mut() {
+            let timed_out: Vec<TimedOutTest> = process
+                .running
+                .iter()
+                .filter_map(|(test_id, started)| {
+                    let elapsed = started.elapsed();
+                    let limit = self
+                        .timeout
+                        .and_then(|timeout| timeout.limit(self.times.get(test_id).copied()))
+                        .filter(|limit| elapsed >= *limit)
+                        .or(self.run_timeout.filter(|_| run_expired))?;
+                    Some(TimedOutTest {
+                        test_id: test_id.clone(),
+                        elapsed,
+                        limit,
+                    })
+                })
+                .collect();
+            if timed_out.is_empty() {
+                continue;
+            }
+
+            process.timed_out.extend(timed_out);
+            // Whatever else the process was running is cut short, not crashed
+            process.running.clear();
+            // Once the run is over its limit, the stop handle stops everyone
+            if let Some(pid) = process.pid.filter(|_| !run_expired) {
+                terminate_process_group(pid);
+            }
+        }
+        drop(processes);
+
+        if run_expired && !self.run_timed_out.swap(true, Ordering::SeqCst) {
+            self.stop.stop();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -243,6 +859,169 @@ mod tests {
         let progress_bar = ProgressBar::hidden();
 
         let handle = spawn_stderr_forwarder(&input[..], progress_bar);
-        assert!(handle.join().unwrap().is_ok());
+        assert_eq!(handle.join().unwrap().unwrap(), input);
+
+        // Only the end is kept
+        let input = vec![b'x'; STDERR_TAIL_SIZE * 3];
+        let handle = spawn_stderr_forwarder(std::io::Cursor::new(input), ProgressBar::hidden());
+        assert_eq!(handle.join().unwrap().unwrap().len(), STDERR_TAIL_SIZE);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_handle() {
+        let spawn = || {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg("sleep 60; true");
+            set_process_group(&mut command);
+            command.spawn().unwrap()
+        };
+
+        let stop = StopHandle::new();
+        let mut child = spawn();
+        stop.add_process(child.id());
+        assert!(!stop.is_stopped());
+        assert!(stop.stop());
+        assert!(!stop.stop());
+        assert_eq!(exit_code(child.wait().unwrap()), -libc::SIGTERM);
+
+        // Processes started after stopping are stopped straight away
+        let mut child = spawn();
+        stop.add_process(child.id());
+        assert_eq!(exit_code(child.wait().unwrap()), -libc::SIGTERM);
+
+        // An interrupt forwards the signal testr received
+        let stop = StopHandle::new();
+        let mut child = spawn();
+        stop.add_process(child.id());
+        assert_eq!(stop.interrupted(), None);
+        stop.interrupt(libc::SIGINT);
+        assert!(stop.is_stopped());
+        assert_eq!(stop.interrupted(), Some(libc::SIGINT));
+        assert_eq!(exit_code(child.wait().unwrap()), -libc::SIGINT);
+
+        let stop = StopHandle::new();
+        let mut child = spawn();
+        stop.add_process(child.id());
+        stop.kill();
+        assert_eq!(exit_code(child.wait().unwrap()), -libc::SIGKILL);
+
+        // A process that has been waited for is forgotten, since its pid may
+        // have been reused; this one is left running to show it isn't signalled
+        let stop = StopHandle::new();
+        let mut child = spawn();
+        stop.add_process(child.id());
+        stop.remove_process(child.id());
+        assert!(stop.stop());
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(child.try_wait().unwrap().is_none());
+        signal_process_group(child.id(), libc::SIGKILL);
+        assert_eq!(exit_code(child.wait().unwrap()), -libc::SIGKILL);
+    }
+
+    #[test]
+    fn test_test_timeout() {
+        assert_eq!(
+            "30".parse::<TestTimeout>().unwrap(),
+            TestTimeout::Fixed(Duration::from_secs(30))
+        );
+        assert_eq!(
+            "2.5s".parse::<TestTimeout>().unwrap(),
+            TestTimeout::Fixed(Duration::from_millis(2500))
+        );
+        assert_eq!(
+            "5x".parse::<TestTimeout>().unwrap(),
+            TestTimeout::Scaled(5.0)
+        );
+        for invalid in ["", "0", "-1", "x", "fast", "inf"] {
+            assert!(invalid.parse::<TestTimeout>().is_err(), "{}", invalid);
+        }
+
+        let fixed = TestTimeout::Fixed(Duration::from_secs(30));
+        assert_eq!(fixed.limit(None), Some(Duration::from_secs(30)));
+        let scaled = TestTimeout::Scaled(5.0);
+        assert_eq!(scaled.limit(None), None);
+        assert_eq!(
+            scaled.limit(Some(Duration::from_secs(60))),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            scaled.limit(Some(Duration::from_millis(10))),
+            Some(MIN_SCALED_TIMEOUT)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_watchdog() {
+        let spawn = || {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg("sleep 60; true");
+            set_process_group(&mut command);
+            command.spawn().unwrap()
+        };
+
+        // A test over its limit gets its worker killed
+        let stop = StopHandle::new();
+        let timeout = Some(TestTimeout::Fixed(Duration::from_millis(200)));
+        let watchdog = Watchdog::start(timeout, HashMap::new(), None, stop.clone());
+        let mut child = spawn();
+        watchdog.watch(0, child.id());
+        watchdog.progress(0, "test1", ProgressStatus::InProgress);
+        watchdog.progress(0, "test1", ProgressStatus::Success);
+        watchdog.progress(0, "test2", ProgressStatus::InProgress);
+        assert_eq!(exit_code(child.wait().unwrap()), -libc::SIGTERM);
+        let timed_out = watchdog.take_timed_out(0);
+        assert_eq!(timed_out.len(), 1);
+        assert_eq!(timed_out[0].test_id.as_str(), "test2");
+        assert!(timed_out[0].elapsed >= Duration::from_millis(200));
+        assert!(watchdog.take_timed_out(0).is_empty());
+        assert!(!watchdog.run_timed_out());
+        assert!(!stop.is_stopped());
+
+        let result = timed_out[0].to_result();
+        assert_eq!(result.status, crate::repository::TestStatus::Error);
+        assert!(result.details.unwrap().starts_with("timed out after"));
+        assert!(timed_out[0].timeout().ends_with("over the limit of 0.2s"));
+
+        // Going over the run's limit stops everything
+        let stop = StopHandle::new();
+        let run_timeout = Some(Duration::from_millis(200));
+        let watchdog = Watchdog::start(None, HashMap::new(), run_timeout, stop.clone());
+        let mut child = spawn();
+        watchdog.watch(3, child.id());
+        watchdog.progress(3, "test1", ProgressStatus::InProgress);
+        assert_eq!(exit_code(child.wait().unwrap()), -libc::SIGTERM);
+        assert!(watchdog.run_timed_out());
+        assert!(stop.is_stopped());
+        assert_eq!(watchdog.take_timed_out(3)[0].test_id.as_str(), "test1");
+    }
+
+    #[test]
+    fn test_unfinished_tests() {
+        let stop = StopHandle::new();
+        let watchdog = Watchdog::start(None, HashMap::new(), None, stop.clone());
+        watchdog.progress(0, "test1", ProgressStatus::InProgress);
+        watchdog.progress(0, "test2", ProgressStatus::InProgress);
+        watchdog.progress(0, "test1", ProgressStatus::Success);
+        watchdog.progress(1, "test3", ProgressStatus::InProgress);
+        assert_eq!(watchdog.take_unfinished(0), vec![TestId::new("test2")]);
+        assert!(watchdog.take_unfinished(0).is_empty());
+
+        // Tests cut short by stopping the run didn't crash
+        stop.stop();
+        assert!(watchdog.take_unfinished(1).is_empty());
+
+        let crashed = CrashedTest {
+            test_id: TestId::new("test2"),
+            exit_code: -11,
+        };
+        assert_eq!(crashed.exit_status(), "killed by signal 11");
+        let result = crashed.to_result();
+        assert_eq!(result.status, crate::repository::TestStatus::Error);
+        assert_eq!(
+            result.message.as_deref(),
+            Some("test process exited before the test finished (killed by signal 11)")
+        );
     }
 }
@@ -5,7 +5,7 @@
 
 use crate::config::TestrConfig;
 use crate::error::{Error, Result};
-use crate::repository::TestId;
+use crate::repository::{find_root, TestId};
 use std::collections::HashMap;
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -25,15 +25,23 @@ impl TestCommand {
         TestCommand { config, base_dir }
     }
 
-    /// Load TestCommand from .testr.conf in the given directory
+    /// Load TestCommand from .testr.conf in the project root containing `dir`
+    ///
+    /// The root is found with [`find_root`], and tests are run from there.
     pub fn from_directory(dir: &Path) -> Result<Self> {
-        let config_path = dir.join(".testr.conf");
+        let root = find_root(dir);
+        let config_path = root.join(".testr.conf");
         if !config_path.exists() {
             return Err(Error::Config("No .testr.conf file found".to_string()));
         }
 
         let config = TestrConfig::load_from_file(&config_path)?;
-        Ok(TestCommand::new(config, dir.to_path_buf()))
+        Ok(TestCommand::new(config, root))
+    }
+
+    /// Get the directory tests are run from
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
     }
 
     /// Execute the test_run_concurrency callout to determine concurrency
@@ -88,6 +96,18 @@ impl TestCommand {
         Ok(Some(concurrency))
     }
 
+    /// Get the tags configured with the `filter_tags` option
+    ///
+    /// Tests carrying any of these tags are only included in counts and
+    /// reports when they fail, and are never scheduled explicitly.
+    pub fn get_filter_tags(&self) -> Vec<String> {
+        self.config
+            .filter_tags
+            .as_deref()
+            .map(|tags| tags.split_whitespace().map(String::from).collect())
+            .unwrap_or_default()
+    }
+
     /// Build the command to execute tests
     pub fn build_command(
         &self,
@@ -115,14 +135,7 @@ impl TestCommand {
         instance_id: Option<&str>,
         test_args: Option<&[String]>,
     ) -> Result<(String, Option<NamedTempFile>)> {
-        // If instance_execute is configured and we have an instance ID, use that
-        let mut cmd = if let (Some(ref instance_exec), Some(id)) =
-            (&self.config.instance_execute, instance_id)
-        {
-            instance_exec.replace("$INSTANCE_ID", id)
-        } else {
-            self.config.test_command.clone()
-        };
+        let mut cmd = self.config.test_command.clone();
         let mut vars = HashMap::new();
         let mut temp_file = None;
 
@@ -198,6 +211,22 @@ impl TestCommand {
             }
         }
 
+        // If instance_execute is configured and we have an instance ID, wrap the
+        // fully substituted test command in it
+        if let (Some(ref instance_exec), Some(id)) = (&self.config.instance_execute, instance_id) {
+            let files = temp_file
+                .as_ref()
+                .map(|f| f.path().to_string_lossy().to_string())
+                .unwrap_or_default();
+            // $COMMAND goes last so that its contents are never re-substituted
+            cmd = self
+                .config
+                .substitute_variables(instance_exec, &vars)
+                .replace("$INSTANCE_ID", id)
+                .replace("$FILES", &files)
+                .replace("$COMMAND", &cmd);
+        }
+
         Ok((cmd, temp_file))
     }
 
@@ -332,17 +361,29 @@ impl TestCommand {
 
     /// Dispose of test instances
     ///
-    /// Calls the instance_dispose command to clean up test environments.
+    /// Calls the instance_dispose command once, with `$INSTANCE_IDS` expanded to
+    /// the space separated list of instances. Commands that only use the older
+    /// per-instance `$INSTANCE_ID` form are run once for each instance.
     pub fn dispose_instances(&self, instance_ids: &[String]) -> Result<()> {
         let Some(ref cmd) = self.config.instance_dispose else {
             // No disposal configured, nothing to do
             return Ok(());
         };
 
-        // Execute dispose for each instance
-        for instance_id in instance_ids {
-            let full_cmd = cmd.replace("$INSTANCE_ID", instance_id);
+        if instance_ids.is_empty() {
+            return Ok(());
+        }
+
+        let commands: Vec<String> = if cmd.contains("$INSTANCE_IDS") {
+            vec![cmd.replace("$INSTANCE_IDS", &instance_ids.join(" "))]
+        } else {
+            instance_ids
+                .iter()
+                .map(|instance_id| cmd.replace("$INSTANCE_ID", instance_id))
+                .collect()
+        };
 
+        for full_cmd in commands {
             let output = Command::new("sh")
                 .arg("-c")
                 .arg(&full_cmd)
@@ -350,16 +391,16 @@ impl TestCommand {
                 .output()
                 .map_err(|e| {
                     Error::CommandExecution(format!(
-                        "Failed to execute instance_dispose for {}: {}",
-                        instance_id, e
+                        "Failed to execute instance_dispose '{}': {}",
+                        full_cmd, e
                     ))
                 })?;
 
             if !output.status.success() {
                 // Log warning but continue disposing other instances
                 eprintln!(
-                    "Warning: instance_dispose failed for {} with status: {}",
-                    instance_id, output.status
+                    "Warning: instance_dispose '{}' failed with status: {}",
+                    full_cmd, output.status
                 );
             }
         }
@@ -466,6 +507,22 @@ test_command=python -m test
         assert_eq!(tc.config().test_command, "python -m test");
     }
 
+    #[test]
+    fn test_from_directory_in_subdirectory() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join(".testr.conf"),
+            "[DEFAULT]\ntest_command=python -m test\n",
+        )
+        .unwrap();
+        let subdir = temp_dir.path().join("src").join("module");
+        fs::create_dir_all(&subdir).unwrap();
+
+        let tc = TestCommand::from_directory(&subdir).unwrap();
+        assert_eq!(tc.base_dir(), temp_dir.path());
+        assert_eq!(tc.config().test_command, "python -m test");
+    }
+
     #[test]
     fn test_get_concurrency_not_configured() {
         let config = create_test_config();
@@ -555,6 +612,23 @@ test_run_concurrency=exit 1
         assert!(result.unwrap_err().to_string().contains("failed"));
     }
 
+    #[test]
+    fn test_get_filter_tags() {
+        let config = create_test_config();
+        let temp_dir = TempDir::new().unwrap();
+        let tc = TestCommand::new(config, temp_dir.path().to_path_buf());
+        assert!(tc.get_filter_tags().is_empty());
+
+        let config_str = r#"
+[DEFAULT]
+test_command=echo ""
+filter_tags=zope:layer  zope:setup
+"#;
+        let config = TestrConfig::parse(config_str).unwrap();
+        let tc = TestCommand::new(config, temp_dir.path().to_path_buf());
+        assert_eq!(tc.get_filter_tags(), vec!["zope:layer", "zope:setup"]);
+    }
+
     #[test]
     fn test_provision_instances_no_config() {
         let config = create_test_config();
@@ -613,4 +687,54 @@ instance_execute=python -m test --instance=$INSTANCE_ID
             .unwrap();
         assert_eq!(cmd, "python -m test --instance=worker-0");
     }
+
+    #[test]
+    fn test_build_command_instance_wraps_command() {
+        let config_str = r#"
+[DEFAULT]
+test_command=python -m subunit.run $IDOPTION
+test_id_option=--load-list $IDFILE
+instance_execute=ssh $INSTANCE_ID copy $FILES -- $COMMAND
+"#;
+        let config = TestrConfig::parse(config_str).unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let tc = TestCommand::new(config, temp_dir.path().to_path_buf());
+
+        let ids = vec![TestId::new("test1")];
+        let (cmd, temp_file) = tc
+            .build_command_full(Some(&ids), false, Some("vm-1"), Some(&["-v".to_string()]))
+            .unwrap();
+        let path = temp_file.unwrap().path().to_string_lossy().to_string();
+        assert_eq!(
+            cmd,
+            format!(
+                "ssh vm-1 copy {} -- python -m subunit.run --load-list {} -v",
+                path, path
+            )
+        );
+
+        // Without test IDs there are no files to copy
+        let (cmd, _) = tc
+            .build_command_with_instance(None, false, Some("vm-1"))
+            .unwrap();
+        assert_eq!(cmd, "ssh vm-1 copy  -- python -m subunit.run ");
+    }
+
+    #[test]
+    fn test_dispose_instances_batched() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_str = r#"
+[DEFAULT]
+test_command=true
+instance_dispose=echo "$INSTANCE_IDS" >> disposed
+"#;
+        let config = TestrConfig::parse(config_str).unwrap();
+        let tc = TestCommand::new(config, temp_dir.path().to_path_buf());
+
+        tc.dispose_instances(&["a".to_string(), "b".to_string()])
+            .unwrap();
+
+        let disposed = std::fs::read_to_string(temp_dir.path().join("disposed")).unwrap();
+        assert_eq!(disposed, "a b\n");
+    }
 }
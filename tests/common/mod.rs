@@ -0,0 +1,59 @@
+//! Helpers shared by the integration tests
+//!
+//! `testrepository::ui::test_ui` is only built for the crate's own unit
+//! tests, so the integration tests carry their own copy of its `TestUI`.
+
+// Each test binary compiles this module and uses only part of it
+#![allow(dead_code)]
+
+use testrepository::error::Result;
+use testrepository::ui::UI;
+
+/// A UI implementation for testing that captures output in vectors
+pub struct TestUI {
+    /// Captured output messages from `output()` calls
+    pub output: Vec<String>,
+    /// Captured error and warning messages from `error()` and `warning()` calls
+    pub errors: Vec<String>,
+    /// Captured raw bytes from `output_bytes()` calls
+    pub bytes_output: Vec<Vec<u8>>,
+}
+
+impl TestUI {
+    /// Creates a new TestUI with empty vectors
+    pub fn new() -> Self {
+        TestUI {
+            output: Vec::new(),
+            errors: Vec::new(),
+            bytes_output: Vec::new(),
+        }
+    }
+}
+
+impl Default for TestUI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UI for TestUI {
+    fn output(&mut self, message: &str) -> Result<()> {
+        self.output.push(message.to_string());
+        Ok(())
+    }
+
+    fn error(&mut self, message: &str) -> Result<()> {
+        self.errors.push(message.to_string());
+        Ok(())
+    }
+
+    fn warning(&mut self, message: &str) -> Result<()> {
+        self.errors.push(format!("Warning: {}", message));
+        Ok(())
+    }
+
+    fn output_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.bytes_output.push(bytes.to_vec());
+        Ok(())
+    }
+}
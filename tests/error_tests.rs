@@ -9,42 +9,12 @@ use tempfile::TempDir;
 use testrepository::commands::{
     Command, FailingCommand, InitCommand, LastCommand, LoadCommand, RunCommand, StatsCommand,
 };
-use testrepository::error::Result;
 use testrepository::repository::file::FileRepositoryFactory;
 use testrepository::repository::{RepositoryFactory, TestResult, TestRun};
-use testrepository::ui::UI;
 
-// Test UI implementation
-struct TestUI {
-    pub output: Vec<String>,
-    pub errors: Vec<String>,
-}
-
-impl TestUI {
-    fn new() -> Self {
-        TestUI {
-            output: Vec::new(),
-            errors: Vec::new(),
-        }
-    }
-}
-
-impl UI for TestUI {
-    fn output(&mut self, message: &str) -> Result<()> {
-        self.output.push(message.to_string());
-        Ok(())
-    }
-
-    fn error(&mut self, message: &str) -> Result<()> {
-        self.errors.push(message.to_string());
-        Ok(())
-    }
+mod common;
 
-    fn warning(&mut self, message: &str) -> Result<()> {
-        self.errors.push(format!("Warning: {}", message));
-        Ok(())
-    }
-}
+use common::TestUI;
 
 #[test]
 fn test_load_command_no_repository() {
@@ -342,20 +312,8 @@ test_command=echo "test1"
     fs::write(temp.path().join(".testr.conf"), config).unwrap();
 
     let mut ui = TestUI::new();
-    let cmd = RunCommand::with_all_options(
-        Some(temp.path().to_string_lossy().to_string()),
-        false,
-        false,
-        false,
-        Some("/nonexistent/list.txt".to_string()),
-        None,
-        false, // until_failure
-        false, // isolated
-        false, // subunit
-        false, // all_output
-        None,  // test_filters
-        None,  // test_args
-    );
+    let cmd = RunCommand::new(Some(temp.path().to_string_lossy().to_string()))
+        .with_load_list(Some("/nonexistent/list.txt".to_string()));
     let result = cmd.execute(&mut ui);
 
     // Should fail because load-list file doesn't exist
@@ -4,52 +4,16 @@
 //! against real repositories in temporary directories.
 
 use std::fs;
-use std::io::Write;
+use std::io::{Read, Write};
 use tempfile::TempDir;
 use testrepository::commands::{
     AnalyzeIsolationCommand, Command, FailingCommand, InitCommand, LastCommand, StatsCommand,
 };
 use testrepository::repository::{RepositoryFactory, TestResult, TestRun};
-use testrepository::ui::UI;
 
-/// Simple test UI that captures output for assertions
-struct TestUI {
-    output: Vec<String>,
-    errors: Vec<String>,
-    bytes_output: Vec<Vec<u8>>,
-}
+mod common;
 
-impl TestUI {
-    fn new() -> Self {
-        TestUI {
-            output: Vec::new(),
-            errors: Vec::new(),
-            bytes_output: Vec::new(),
-        }
-    }
-}
-
-impl UI for TestUI {
-    fn output(&mut self, message: &str) -> testrepository::error::Result<()> {
-        self.output.push(message.to_string());
-        Ok(())
-    }
-
-    fn error(&mut self, message: &str) -> testrepository::error::Result<()> {
-        self.errors.push(message.to_string());
-        Ok(())
-    }
-
-    fn warning(&mut self, message: &str) -> testrepository::error::Result<()> {
-        self.errors.push(format!("Warning: {}", message));
-        Ok(())
-    }
-
-    fn output_bytes(&mut self, bytes: &[u8]) -> testrepository::error::Result<()> {
-        self.bytes_output.push(bytes.to_vec());
-        Ok(())
-    }
-}
+use common::TestUI;
 
 #[test]
 fn test_full_workflow_init_load_last() {
@@ -329,20 +293,7 @@ test_command=python3 -c "import sys; import time; sys.stdout.buffer.write(b'\xb3
 
     // Run with parallel execution
     let mut ui = TestUI::new();
-    let cmd = RunCommand::with_all_options(
-        Some(base_path.clone()),
-        false,   // partial
-        false,   // failing
-        false,   // force_init
-        None,    // load_list
-        Some(2), // concurrency
-        false,   // until_failure
-        false,   // isolated
-        false,   // subunit
-        false,   // all_output
-        None,    // test_filters
-        None,    // test_args
-    );
+    let cmd = RunCommand::new(Some(base_path.clone())).with_concurrency(Some(2));
 
     // Note: This test will fail to actually run because the command is synthetic
     // But it tests that the parallel code path is exercised
@@ -403,20 +354,7 @@ test_command=echo ""
     // Create command with until_failure set to true
     // The test will succeed but we can verify the flag was accepted
     // by checking that the command can be created
-    let cmd = RunCommand::with_all_options(
-        Some(base_path.clone()),
-        false, // partial
-        false, // failing
-        false, // force_init
-        None,  // load_list
-        None,  // concurrency
-        true,  // until_failure
-        false, // isolated
-        false, // subunit
-        false, // all_output
-        None,  // test_filters
-        None,  // test_args
-    );
+    let cmd = RunCommand::new(Some(base_path.clone())).with_until_failure(true);
 
     // Verify the command was created successfully
     // (The actual looping behavior would run infinitely with always-passing tests,
@@ -444,20 +382,7 @@ test_command=echo ""
     fs::write(temp.path().join(".testr.conf"), config).unwrap();
 
     // Create command with isolated set to true
-    let cmd = RunCommand::with_all_options(
-        Some(base_path.clone()),
-        false, // partial
-        false, // failing
-        false, // force_init
-        None,  // load_list
-        None,  // concurrency
-        false, // until_failure
-        true,  // isolated
-        false, // subunit
-        false, // all_output
-        None,  // test_filters
-        None,  // test_args
-    );
+    let cmd = RunCommand::new(Some(base_path.clone())).with_isolated(true);
 
     // Verify the command was created successfully
     assert_eq!(cmd.name(), "run");
@@ -563,3 +488,520 @@ test_run_concurrency=echo 2
     let concurrency = test_cmd.get_concurrency().unwrap();
     assert_eq!(concurrency, Some(2));
 }
+
+#[test]
+fn test_parallel_run_is_stored_as_single_run() {
+    use testrepository::commands::RunCommand;
+    use testrepository::repository::file::FileRepositoryFactory;
+
+    let temp = TempDir::new().unwrap();
+    let base_path = temp.path().to_string_lossy().to_string();
+
+    let factory = FileRepositoryFactory;
+    factory.initialise(temp.path()).unwrap();
+
+    // Every worker replays the same pre-recorded stream
+    let mut recorded = TestRun::new("recorded".to_string());
+    recorded.timestamp = chrono::DateTime::from_timestamp(1000000000, 0).unwrap();
+    recorded.add_result(TestResult::success("test1"));
+    recorded.add_result(TestResult::failure("test2", "Failed"));
+    let stream_path = temp.path().join("recorded.subunit");
+    let mut stream = fs::File::create(&stream_path).unwrap();
+    testrepository::subunit_stream::write_stream(&recorded, &mut stream).unwrap();
+    drop(stream);
+
+    let config = format!(
+        "[DEFAULT]\ntest_command=cat {}\n",
+        stream_path.to_string_lossy()
+    );
+    fs::write(temp.path().join(".testr.conf"), config).unwrap();
+
+    let list_path = temp.path().join("tests.txt");
+    fs::write(&list_path, "test1\ntest2\n").unwrap();
+
+    let mut ui = TestUI::new();
+    let cmd = RunCommand::new(Some(base_path.clone()))
+        .with_load_list(Some(list_path.to_string_lossy().to_string()))
+        .with_concurrency(Some(2));
+    assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+
+    let repo = factory.open(temp.path()).unwrap();
+    assert_eq!(repo.count().unwrap(), 1);
+    assert_eq!(repo.get_next_run_id().unwrap(), 1);
+
+    // The stored run carries the results of every worker, tagged by worker
+    let last = repo.get_latest_run().unwrap();
+    assert_eq!(last.total_tests(), 2);
+    assert!(last
+        .results
+        .values()
+        .all(|r| r.tags.iter().any(|t| t.starts_with("worker-"))));
+
+    let failing = repo.get_failing_tests().unwrap();
+    assert_eq!(failing.len(), 1);
+    assert_eq!(failing[0].as_str(), "test2");
+}
+
+#[test]
+fn test_isolated_run_is_stored() {
+    use testrepository::commands::RunCommand;
+    use testrepository::repository::file::FileRepositoryFactory;
+
+    let temp = TempDir::new().unwrap();
+    let base_path = temp.path().to_string_lossy().to_string();
+
+    let factory = FileRepositoryFactory;
+    factory.initialise(temp.path()).unwrap();
+
+    let mut recorded = TestRun::new("recorded".to_string());
+    recorded.timestamp = chrono::DateTime::from_timestamp(1000000000, 0).unwrap();
+    recorded.add_result(TestResult::success("test1"));
+    recorded.add_result(
+        TestResult::failure("test2", "Failed").with_details("Traceback: isolated failure"),
+    );
+    let stream_path = temp.path().join("recorded.subunit");
+    let mut stream = fs::File::create(&stream_path).unwrap();
+    testrepository::subunit_stream::write_stream(&recorded, &mut stream).unwrap();
+    drop(stream);
+
+    let config = format!(
+        "[DEFAULT]\ntest_command=cat {}\n",
+        stream_path.to_string_lossy()
+    );
+    fs::write(temp.path().join(".testr.conf"), config).unwrap();
+
+    let list_path = temp.path().join("tests.txt");
+    fs::write(&list_path, "test1\ntest2\n").unwrap();
+
+    let mut ui = TestUI::new();
+    let cmd = RunCommand::new(Some(base_path.clone()))
+        .with_load_list(Some(list_path.to_string_lossy().to_string()))
+        .with_isolated(true);
+    assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+
+    let repo = factory.open(temp.path()).unwrap();
+    assert_eq!(repo.count().unwrap(), 1);
+
+    let last = repo.get_latest_run().unwrap();
+    assert_eq!(last.total_tests(), 2);
+
+    // The failing stream keeps the traceback attachment
+    let mut failing = Vec::new();
+    repo.get_failing_tests_raw()
+        .unwrap()
+        .read_to_end(&mut failing)
+        .unwrap();
+    let failing_run =
+        testrepository::subunit_stream::parse_stream(&failing[..], "failing".to_string()).unwrap();
+    let result = failing_run
+        .results
+        .get(&testrepository::repository::TestId::new("test2"))
+        .unwrap();
+    assert_eq!(
+        result.details.as_deref(),
+        Some("Traceback: isolated failure")
+    );
+}
+
+#[test]
+fn test_retry_records_flaky_pass() {
+    use testrepository::commands::{FlakyPolicy, RunCommand};
+    use testrepository::repository::file::FileRepositoryFactory;
+
+    for (policy, exit_code) in [(FlakyPolicy::Pass, 0), (FlakyPolicy::Fail, 1)] {
+        let temp = TempDir::new().unwrap();
+        let base_path = temp.path().to_string_lossy().to_string();
+
+        let factory = FileRepositoryFactory;
+        factory.initialise(temp.path()).unwrap();
+
+        // test2 fails the first time it runs and passes after that
+        let mut first = TestRun::new("first".to_string());
+        first.add_result(TestResult::success("test1"));
+        first.add_result(TestResult::failure("test2", "Failed"));
+        let mut retried = TestRun::new("retried".to_string());
+        retried.add_result(TestResult::success("test2"));
+        for (name, run) in [("first.subunit", &first), ("retried.subunit", &retried)] {
+            let mut stream = fs::File::create(temp.path().join(name)).unwrap();
+            testrepository::subunit_stream::write_stream(run, &mut stream).unwrap();
+        }
+        fs::write(
+            temp.path().join("run.sh"),
+            "if [ -e ran ]; then cat retried.subunit; else touch ran; cat first.subunit; fi\n",
+        )
+        .unwrap();
+        fs::write(
+            temp.path().join(".testr.conf"),
+            "[DEFAULT]\ntest_command=sh run.sh\n",
+        )
+        .unwrap();
+
+        let list_path = temp.path().join("tests.txt");
+        fs::write(&list_path, "test1\ntest2\n").unwrap();
+
+        let mut ui = TestUI::new();
+        let cmd = RunCommand::new(Some(base_path.clone()))
+            .with_load_list(Some(list_path.to_string_lossy().to_string()))
+            .with_retry(2, policy);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), exit_code);
+        assert!(ui
+            .output
+            .contains(&"Retrying 1 failing test(s) (attempt 1 of 2)".to_string()));
+        assert!(ui
+            .output
+            .contains(&"1 test(s) passed on retry (flaky):".to_string()));
+
+        // Both attempts are stored in the one run, and the retry decides the result
+        let repo = factory.open(temp.path()).unwrap();
+        assert_eq!(repo.count().unwrap(), 1);
+        let last = repo.get_latest_run().unwrap();
+        assert_eq!(last.count_failures(), 0);
+        let result = &last.results[&testrepository::repository::TestId::new("test2")];
+        assert!(result.tags.contains(&"retry-1".to_string()));
+        let mut raw = Vec::new();
+        repo.get_test_run_raw("0")
+            .unwrap()
+            .read_to_end(&mut raw)
+            .unwrap();
+        assert!(raw.len() > fs::read(temp.path().join("first.subunit")).unwrap().len());
+
+        let failing = repo.get_failing_tests().unwrap();
+        match policy {
+            FlakyPolicy::Pass => assert!(failing.is_empty()),
+            FlakyPolicy::Fail => assert_eq!(failing[0].as_str(), "test2"),
+        }
+
+        // When test2 next fails, the run points out that it is flaky
+        fs::remove_file(temp.path().join("ran")).unwrap();
+        let mut ui = TestUI::new();
+        let cmd = RunCommand::new(Some(base_path));
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+        assert!(ui
+            .output
+            .contains(&"1 failed test(s) are known to be flaky:".to_string()));
+        assert!(ui
+            .output
+            .contains(&"  test2 (score 1.00, last flipped in run 0)".to_string()));
+    }
+}
+
+#[test]
+fn test_max_failures_stops_run() {
+    use testrepository::commands::RunCommand;
+    use testrepository::repository::file::FileRepositoryFactory;
+
+    for concurrency in [None, Some(2)] {
+        let temp = TempDir::new().unwrap();
+        let base_path = temp.path().to_string_lossy().to_string();
+
+        // test2 was failing before this run
+        let factory = FileRepositoryFactory;
+        let mut repo = factory.initialise(temp.path()).unwrap();
+        let mut previous = TestRun::new("previous".to_string());
+        previous.add_result(TestResult::success("test1"));
+        previous.add_result(TestResult::failure("test2", "Failed"));
+        repo.insert_test_run(previous).unwrap();
+
+        // test1 fails straight away, test2 would only finish much later
+        let mut failed = TestRun::new("failed".to_string());
+        failed.add_result(TestResult::failure("test1", "Failed"));
+        let mut passed = TestRun::new("passed".to_string());
+        passed.add_result(TestResult::success("test2"));
+        for (name, run) in [("failed.subunit", &failed), ("passed.subunit", &passed)] {
+            let mut stream = fs::File::create(temp.path().join(name)).unwrap();
+            testrepository::subunit_stream::write_stream(run, &mut stream).unwrap();
+        }
+        fs::write(
+            temp.path().join(".testr.conf"),
+            "[DEFAULT]\ntest_command=cat failed.subunit; sleep 60; cat passed.subunit\n",
+        )
+        .unwrap();
+
+        let list_path = temp.path().join("tests.txt");
+        fs::write(&list_path, "test1\ntest2\n").unwrap();
+
+        let started = std::time::Instant::now();
+        let mut ui = TestUI::new();
+        let cmd = RunCommand::new(Some(base_path))
+            .with_load_list(Some(list_path.to_string_lossy().to_string()))
+            .with_concurrency(concurrency)
+            .with_max_failures(Some(1));
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+        assert!(started.elapsed() < std::time::Duration::from_secs(30));
+        assert!(ui
+            .output
+            .contains(&"Stopped after 1 failure(s); 1 test(s) were not run".to_string()));
+
+        // Only what ran is stored, and test2 is still failing rather than passed
+        let repo = factory.open(temp.path()).unwrap();
+        let last = repo.get_latest_run().unwrap();
+        assert_eq!(last.total_tests(), 1);
+        let mut failing: Vec<String> = repo
+            .get_failing_tests()
+            .unwrap()
+            .iter()
+            .map(|id| id.as_str().to_string())
+            .collect();
+        failing.sort();
+        assert_eq!(failing, vec!["test1", "test2"]);
+        assert_eq!(repo.get_run_metadata(&last.id).unwrap().partial, Some(true));
+    }
+}
+
+#[test]
+fn test_test_timeout_records_error() {
+    use subunit::serialize::Serializable;
+    use testrepository::commands::RunCommand;
+    use testrepository::repository::file::FileRepositoryFactory;
+    use testrepository::repository::TestId;
+
+    for reschedule in [false, true] {
+        let temp = TempDir::new().unwrap();
+        let base_path = temp.path().to_string_lossy().to_string();
+
+        let factory = FileRepositoryFactory;
+        factory.initialise(temp.path()).unwrap();
+
+        // test1 starts and hangs the first time; test2 passes when it gets to run
+        let mut started = fs::File::create(temp.path().join("started.subunit")).unwrap();
+        subunit::types::event::Event::new(subunit::types::teststatus::TestStatus::InProgress)
+            .test_id("test1")
+            .build()
+            .serialize(&mut started)
+            .unwrap();
+        let mut passed = TestRun::new("passed".to_string());
+        passed.add_result(TestResult::success("test2"));
+        let mut stream = fs::File::create(temp.path().join("passed.subunit")).unwrap();
+        testrepository::subunit_stream::write_stream(&passed, &mut stream).unwrap();
+        fs::write(
+            temp.path().join("run.sh"),
+            "if [ -e ran ]; then cat passed.subunit; else touch ran; cat started.subunit; sleep 60; fi\n",
+        )
+        .unwrap();
+        fs::write(
+            temp.path().join(".testr.conf"),
+            "[DEFAULT]\ntest_command=sh run.sh\n",
+        )
+        .unwrap();
+
+        let list_path = temp.path().join("tests.txt");
+        fs::write(&list_path, "test1\ntest2\n").unwrap();
+
+        let started = std::time::Instant::now();
+        let mut ui = TestUI::new();
+        let cmd = RunCommand::new(Some(base_path))
+            .with_load_list(Some(list_path.to_string_lossy().to_string()))
+            .with_timeouts(Some("1".parse().unwrap()), None, reschedule);
+        assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+        assert!(started.elapsed() < std::time::Duration::from_secs(30));
+        assert!(ui.output.contains(&"1 test(s) timed out:".to_string()));
+        assert!(ui.output.iter().any(|line| line.starts_with("  test1 (")));
+        assert_eq!(
+            ui.output.contains(&"1 test(s) were not run".to_string()),
+            !reschedule
+        );
+
+        // The hung test is stored as failed with the reason attached
+        let repo = factory.open(temp.path()).unwrap();
+        let last = repo.get_latest_run().unwrap();
+        let result = &last.results[&TestId::new("test1")];
+        assert!(result.status.is_failure());
+        assert!(result.details.as_ref().unwrap().contains("timed out"));
+        assert_eq!(last.results.contains_key(&TestId::new("test2")), reschedule);
+        let mut raw = Vec::new();
+        repo.get_test_run_raw(&last.id)
+            .unwrap()
+            .read_to_end(&mut raw)
+            .unwrap();
+        let contains = |needle: &[u8]| raw.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(b"timed-out"));
+        assert!(contains(b"over the limit of 1.0s"));
+        assert_eq!(
+            repo.get_run_metadata(&last.id).unwrap().partial,
+            Some(!reschedule)
+        );
+    }
+}
+
+#[test]
+fn test_crashed_worker_is_attributed() {
+    use subunit::serialize::Serializable;
+    use testrepository::commands::RunCommand;
+    use testrepository::repository::file::FileRepositoryFactory;
+    use testrepository::repository::TestId;
+
+    let temp = TempDir::new().unwrap();
+    let base_path = temp.path().to_string_lossy().to_string();
+
+    let factory = FileRepositoryFactory;
+    factory.initialise(temp.path()).unwrap();
+
+    // test1 starts and takes its test process down with it; test2 never runs
+    let mut started = fs::File::create(temp.path().join("started.subunit")).unwrap();
+    subunit::types::event::Event::new(subunit::types::teststatus::TestStatus::InProgress)
+        .test_id("test1")
+        .build()
+        .serialize(&mut started)
+        .unwrap();
+    fs::write(
+        temp.path().join("run.sh"),
+        "cat started.subunit; echo 'Segmentation fault' >&2; kill -SEGV $$\n",
+    )
+    .unwrap();
+    fs::write(
+        temp.path().join(".testr.conf"),
+        "[DEFAULT]\ntest_command=exec sh run.sh\n",
+    )
+    .unwrap();
+
+    let list_path = temp.path().join("tests.txt");
+    fs::write(&list_path, "test1\ntest2\n").unwrap();
+
+    let mut ui = TestUI::new();
+    let cmd = RunCommand::new(Some(base_path))
+        .with_load_list(Some(list_path.to_string_lossy().to_string()));
+    assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+    assert!(ui.output.contains(&"1 test(s) crashed:".to_string()));
+    assert!(ui
+        .output
+        .contains(&"  test1 (killed by signal 11)".to_string()));
+    assert!(ui.output.contains(&"1 test(s) were not run".to_string()));
+
+    // The test that was running is stored as failed, not left out
+    let repo = factory.open(temp.path()).unwrap();
+    let last = repo.get_latest_run().unwrap();
+    assert_eq!(last.total_tests(), 1);
+    let result = &last.results[&TestId::new("test1")];
+    assert!(result.status.is_failure());
+    assert!(result
+        .details
+        .as_ref()
+        .unwrap()
+        .contains("test process exited before the test finished"));
+    assert_eq!(repo.get_run_metadata(&last.id).unwrap().partial, Some(true));
+
+    // The process's exit status and stderr are attached to it
+    let mut raw = Vec::new();
+    repo.get_test_run_raw(&last.id)
+        .unwrap()
+        .read_to_end(&mut raw)
+        .unwrap();
+    let contains = |needle: &[u8]| raw.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(b"exit-status"));
+    assert!(contains(b"Segmentation fault"));
+}
+
+#[test]
+fn test_failing_command_is_not_partial() {
+    use testrepository::commands::RunCommand;
+    use testrepository::repository::file::FileRepositoryFactory;
+    use testrepository::repository::TestId;
+
+    let temp = TempDir::new().unwrap();
+    let base_path = temp.path().to_string_lossy().to_string();
+
+    let factory = FileRepositoryFactory;
+    factory.initialise(temp.path()).unwrap();
+
+    // test1 fails and the command exits non-zero, as test runners do;
+    // test2 was scheduled but the command doesn't report it
+    let mut failed = TestRun::new("failed".to_string());
+    failed.add_result(TestResult::failure("test1", "Failed"));
+    let mut stream = fs::File::create(temp.path().join("failed.subunit")).unwrap();
+    testrepository::subunit_stream::write_stream(&failed, &mut stream).unwrap();
+    fs::write(temp.path().join("run.sh"), "cat failed.subunit; exit 1\n").unwrap();
+    fs::write(
+        temp.path().join(".testr.conf"),
+        "[DEFAULT]\ntest_command=sh run.sh\n",
+    )
+    .unwrap();
+
+    let list_path = temp.path().join("tests.txt");
+    fs::write(&list_path, "test1\ntest2\n").unwrap();
+
+    let mut ui = TestUI::new();
+    let cmd = RunCommand::new(Some(base_path))
+        .with_load_list(Some(list_path.to_string_lossy().to_string()));
+    assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+    assert!(!ui.output.iter().any(|line| line.contains("were not run")));
+
+    // Nothing cut the run short, so it is stored as a full run
+    let repo = factory.open(temp.path()).unwrap();
+    let last = repo.get_latest_run().unwrap();
+    assert_eq!(
+        repo.get_run_metadata(&last.id).unwrap().partial,
+        Some(false)
+    );
+    assert_eq!(
+        repo.get_failing_tests().unwrap(),
+        vec![TestId::new("test1")]
+    );
+}
+
+#[test]
+fn test_dynamic_schedule_is_stored_as_single_run() {
+    use testrepository::commands::RunCommand;
+    use testrepository::repository::file::FileRepositoryFactory;
+    use testrepository::repository::TestId;
+
+    let temp = TempDir::new().unwrap();
+    let base_path = temp.path().to_string_lossy().to_string();
+
+    let factory = FileRepositoryFactory;
+    factory.initialise(temp.path()).unwrap();
+
+    // The test command replays a recorded result for each test it is given
+    for (test_id, passed) in [("a.1", true), ("a.2", true), ("b.1", true), ("c.1", false)] {
+        let mut recorded = TestRun::new("recorded".to_string());
+        recorded.add_result(if passed {
+            TestResult::success(test_id)
+        } else {
+            TestResult::failure(test_id, "Failed")
+        });
+        let mut stream =
+            fs::File::create(temp.path().join(format!("{}.subunit", test_id))).unwrap();
+        testrepository::subunit_stream::write_stream(&recorded, &mut stream).unwrap();
+    }
+    fs::write(
+        temp.path().join("run.sh"),
+        "for id in \"$@\"; do cat \"$id.subunit\"; done\n",
+    )
+    .unwrap();
+    fs::write(
+        temp.path().join(".testr.conf"),
+        "[DEFAULT]\ntest_command=sh run.sh $IDLIST\ngroup_regex=^([^.]+)\\.\n",
+    )
+    .unwrap();
+
+    let list_path = temp.path().join("tests.txt");
+    fs::write(&list_path, "a.1\na.2\nb.1\nc.1\n").unwrap();
+
+    let mut ui = TestUI::new();
+    let cmd = RunCommand::new(Some(base_path))
+        .with_load_list(Some(list_path.to_string_lossy().to_string()))
+        .with_concurrency(Some(2))
+        .with_dynamic_schedule(true, Some(1));
+    assert_eq!(cmd.execute(&mut ui).unwrap(), 1);
+
+    let repo = factory.open(temp.path()).unwrap();
+    assert_eq!(repo.count().unwrap(), 1);
+    let last = repo.get_latest_run().unwrap();
+    assert_eq!(last.total_tests(), 4);
+
+    // One process per batch, with the grouped tests sharing one
+    let metadata = repo.get_run_metadata(&last.id).unwrap();
+    assert_eq!(metadata.exit_codes.len(), 3);
+    assert_eq!(metadata.partial, Some(false));
+    let worker = |test_id: &str| {
+        last.results[&TestId::new(test_id)]
+            .tags
+            .iter()
+            .find(|tag| tag.starts_with("worker-"))
+            .cloned()
+            .unwrap()
+    };
+    assert_eq!(worker("a.1"), worker("a.2"));
+
+    let failing = repo.get_failing_tests().unwrap();
+    assert_eq!(failing, vec![TestId::new("c.1")]);
+}
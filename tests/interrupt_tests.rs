@@ -0,0 +1,75 @@
+//! Integration tests for interrupting a run
+//!
+//! These send a real SIGINT to the test binary, so they live in their own
+//! binary where no other test can be caught by it.
+
+#![cfg(unix)]
+
+use std::fs;
+use subunit::serialize::Serializable;
+use tempfile::TempDir;
+use testrepository::commands::{Command, RunCommand};
+use testrepository::repository::file::FileRepositoryFactory;
+use testrepository::repository::{RepositoryFactory, TestId, TestStatus};
+
+mod common;
+
+use common::TestUI;
+
+#[test]
+fn test_interrupted_run_is_stored() {
+    use subunit::types::event::Event;
+    use subunit::types::teststatus::TestStatus as SubunitStatus;
+
+    let temp = TempDir::new().unwrap();
+    let base_path = temp.path().to_string_lossy().to_string();
+
+    let factory = FileRepositoryFactory;
+    factory.initialise(temp.path()).unwrap();
+
+    // test1 passes, then testr is interrupted while test2 is running
+    let mut started = fs::File::create(temp.path().join("started.subunit")).unwrap();
+    Event::new(SubunitStatus::Success)
+        .test_id("test1")
+        .build()
+        .serialize(&mut started)
+        .unwrap();
+    Event::new(SubunitStatus::InProgress)
+        .test_id("test2")
+        .build()
+        .serialize(&mut started)
+        .unwrap();
+    fs::write(
+        temp.path().join("run.sh"),
+        "cat started.subunit; kill -INT $PPID; exec sleep 30\n",
+    )
+    .unwrap();
+    fs::write(
+        temp.path().join(".testr.conf"),
+        "[DEFAULT]\ntest_command=exec sh run.sh\n",
+    )
+    .unwrap();
+
+    let list_path = temp.path().join("tests.txt");
+    fs::write(&list_path, "test1\ntest2\n").unwrap();
+
+    let mut ui = TestUI::new();
+    let cmd = RunCommand::new(Some(base_path))
+        .with_load_list(Some(list_path.to_string_lossy().to_string()));
+    assert_eq!(cmd.execute(&mut ui).unwrap(), 128 + libc::SIGINT);
+    assert!(ui
+        .output
+        .contains(&"Interrupted; 1 test(s) were not run".to_string()));
+
+    // What ran before the interrupt is kept, as a partial run
+    let repo = factory.open(temp.path()).unwrap();
+    let last = repo.get_latest_run().unwrap();
+    assert_eq!(last.total_tests(), 1);
+    assert_eq!(
+        last.results[&TestId::new("test1")].status,
+        TestStatus::Success
+    );
+    let metadata = repo.get_run_metadata(&last.id).unwrap();
+    assert_eq!(metadata.interrupted, Some(true));
+    assert_eq!(metadata.partial, Some(true));
+}